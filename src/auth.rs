@@ -0,0 +1,221 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Value which must not be revealed, such as a password or a token
+///
+/// `Debug` prints `Secret(REDACTED)` instead of the value, which is
+/// serialized as a plain string.
+#[derive(Clone, Deserialize, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new<S: Into<String>>(s: S) -> Secret {
+        Secret(s.into())
+    }
+
+    /// Returns the secret value.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Secret(REDACTED)")
+    }
+}
+
+/// Callback which returns a new bearer token
+pub type Refresh = Arc<dyn Fn() -> Result<String, String> + Send + Sync>;
+
+/// Bearer token sent in the `Authorization` header
+#[derive(Clone)]
+pub enum BearerToken {
+    /// Token which never changes
+    Static(Secret),
+    /// Token obtained from a callback, cached until the server rejects it
+    Refresh(Refresh, Arc<Mutex<Option<Secret>>>),
+}
+
+impl BearerToken {
+    /// Returns the current token, calling the refresh callback if no
+    /// token is cached.
+    pub fn token(&self) -> Result<Secret, String> {
+        match *self {
+            BearerToken::Static(ref token) => Ok(token.clone()),
+            BearerToken::Refresh(ref refresh, ref cached) => {
+                let mut cached = cached.lock().unwrap_or_else(|err| err.into_inner());
+
+                if let Some(ref token) = *cached {
+                    return Ok(token.clone());
+                }
+
+                let token = Secret::new(refresh().map_err(|err| format!("failed to refresh the bearer token: {}", err))?);
+                *cached = Some(token.clone());
+                Ok(token)
+            },
+        }
+    }
+
+    /// Discards the cached token of a refreshed token and returns `true`,
+    /// or returns `false` for a static token.
+    pub fn invalidate(&self) -> bool {
+        match *self {
+            BearerToken::Static(_) => false,
+            BearerToken::Refresh(_, ref cached) => {
+                *cached.lock().unwrap_or_else(|err| err.into_inner()) = None;
+                true
+            },
+        }
+    }
+}
+
+impl fmt::Debug for BearerToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BearerToken::Static(ref token) => f.debug_tuple("Static").field(token).finish(),
+            BearerToken::Refresh(..) => write!(f, "Refresh(..)"),
+        }
+    }
+}
+
+impl PartialEq for BearerToken {
+    /// Static tokens are equal if their values are equal and refreshed
+    /// tokens are equal if they share their callback.
+    fn eq(&self, other: &BearerToken) -> bool {
+        match (self, other) {
+            (BearerToken::Static(a), BearerToken::Static(b)) => a == b,
+            (BearerToken::Refresh(a, _), BearerToken::Refresh(b, _)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+/// Authentication of requests to a Livy server
+///
+/// Passwords and tokens are held in `Secret`s, so that they appear
+/// neither in `Debug` output nor in error messages.
+///
+/// # Examples
+/// ```
+/// use livy::auth::Authentication;
+/// use livy::client::Client;
+///
+/// let client = Client::new("https://knox.example.com:8443/gateway/default/livy/v1", None, None)
+///     .with_authentication(Authentication::basic("alice", "s3cret"));
+///
+/// assert!(!format!("{:?}", Authentication::basic("alice", "s3cret")).contains("s3cret"));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Authentication {
+    #[default]
+    None,
+    /// SPNEGO (Kerberos) with the credentials of the current user
+    Spnego { username: Option<String> },
+    /// HTTP Basic authentication
+    Basic { username: String, password: Secret },
+    /// `Authorization: Bearer` header
+    Bearer(BearerToken),
+}
+
+impl Authentication {
+    /// Constructs HTTP Basic authentication.
+    pub fn basic<U: Into<String>, P: Into<String>>(username: U, password: P) -> Authentication {
+        Authentication::Basic {
+            username: username.into(),
+            password: Secret::new(password),
+        }
+    }
+
+    /// Constructs bearer authentication with a static token.
+    pub fn bearer<T: Into<String>>(token: T) -> Authentication {
+        Authentication::Bearer(BearerToken::Static(Secret::new(token)))
+    }
+
+    /// Constructs bearer authentication with a token obtained from `refresh`.
+    ///
+    /// `refresh` is called before the first request and again whenever the
+    /// server answers `401 Unauthorized`, after which the request is retried
+    /// once.
+    ///
+    /// # Examples
+    /// ```
+    /// use livy::auth::Authentication;
+    ///
+    /// let authentication = Authentication::bearer_refresh(|| {
+    ///     Ok("token from the identity provider".to_string())
+    /// });
+    /// ```
+    pub fn bearer_refresh<F>(refresh: F) -> Authentication
+        where F: Fn() -> Result<String, String> + Send + Sync + 'static {
+        Authentication::Bearer(BearerToken::Refresh(Arc::new(refresh), Arc::new(Mutex::new(None))))
+    }
+
+    /// Converts the `gssnegotiate` and `username` arguments of `Client::new`.
+    ///
+    /// A username without SPNEGO is sent with HTTP Basic authentication
+    /// and an empty password.
+    pub fn from_options(gssnegotiate: Option<bool>, username: Option<String>) -> Authentication {
+        match (gssnegotiate, username) {
+            (Some(true), username) => Authentication::Spnego { username },
+            (_, Some(username)) => Authentication::basic(username, ""),
+            (_, None) => Authentication::None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_debug() {
+        struct TestCase {
+            authentication: Authentication,
+            expected: &'static str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                authentication: Authentication::basic("alice", "s3cret"),
+                expected: r#"Basic { username: "alice", password: Secret(REDACTED) }"#,
+            },
+            TestCase {
+                authentication: Authentication::bearer("t0ken"),
+                expected: "Bearer(Static(Secret(REDACTED)))",
+            },
+            TestCase {
+                authentication: Authentication::bearer_refresh(|| Ok("t0ken".to_string())),
+                expected: "Bearer(Refresh(..))",
+            },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected, format!("{:?}", test_case.authentication));
+        }
+    }
+
+    #[test]
+    fn test_bearer_token_refresh() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let authentication = {
+            let calls = Arc::clone(&calls);
+            Authentication::bearer_refresh(move || Ok(format!("token{}", calls.fetch_add(1, Ordering::SeqCst))))
+        };
+        let token = match authentication {
+            Authentication::Bearer(ref token) => token,
+            _ => unreachable!(),
+        };
+
+        assert_eq!("token0", token.token().unwrap().expose());
+        assert_eq!("token0", token.token().unwrap().expose());
+        assert!(token.invalidate());
+        assert_eq!("token1", token.token().unwrap().expose());
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+
+        let failing = BearerToken::Refresh(Arc::new(|| Err("expired".to_string())), Arc::new(Mutex::new(None)));
+        assert_eq!(Err("failed to refresh the bearer token: expired".to_string()), failing.token().map(|_| ()));
+    }
+}
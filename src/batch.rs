@@ -0,0 +1,324 @@
+//! Running batches to completion while following their logs
+//!
+//! # Examples
+//! ```no_run
+//! use livy::batch::BatchRunner;
+//! use livy::builder::NewBatchRequestBuilder;
+//! use livy::client::Client;
+//! use std::time::Duration;
+//!
+//! let client = Client::new("http://example.com:8998", None, None);
+//! let request = NewBatchRequestBuilder::new("hdfs:///jobs/etl.jar").class_name("com.example.Etl").build().unwrap();
+//!
+//! let result = BatchRunner::new(&client)
+//!     .timeout(Duration::from_secs(60 * 60))
+//!     .kill_on_timeout()
+//!     .on_log(|line| println!("{}", line))
+//!     .run(request)
+//!     .unwrap();
+//!
+//! if !result.is_success() {
+//!     eprintln!("batch {} ended in {:?}: {:?}", result.id, result.state, result.log);
+//! }
+//! ```
+
+use client::{Batch, Client, NewBatchRequest};
+use std::collections::{HashMap, VecDeque};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// States after which a batch no longer runs
+const FINISHED_STATES: &[&str] = &["success", "dead", "killed", "error"];
+
+/// Default polling interval
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default number of log lines kept in `BatchResult::log`
+pub const DEFAULT_TAIL: usize = 100;
+
+/// Default number of consecutive failed polls after which a runner gives up
+pub const DEFAULT_POLL_RETRIES: u32 = 5;
+
+/// Number of log lines requested at once
+const LOG_PAGE_SIZE: i64 = 100;
+
+/// Returns `true` if a batch in `state` no longer runs.
+pub fn is_finished(state: &str) -> bool {
+    FINISHED_STATES.contains(&state)
+}
+
+/// Outcome of a batch run by a `BatchRunner`
+#[derive(Debug, PartialEq)]
+pub struct BatchResult {
+    pub id: i64,
+    /// Last state reported by Livy
+    pub state: Option<String>,
+    /// YARN application id
+    pub app_id: Option<String>,
+    /// URLs of the application, e.g. `driverLogUrl` and `sparkUiUrl`
+    pub app_info: Option<HashMap<String, Option<String>>>,
+    /// Time from the submission to the end of the batch or the timeout
+    pub duration: Duration,
+    /// Last log lines
+    pub log: Vec<String>,
+    /// Whether the batch was still running at the timeout
+    pub timed_out: bool,
+    /// Whether the batch was killed at the timeout
+    pub killed: bool,
+}
+
+impl BatchResult {
+    /// Returns `true` if the batch finished in the `success` state.
+    pub fn is_success(&self) -> bool {
+        self.state.as_deref() == Some("success")
+    }
+}
+
+/// Callback which receives new log lines
+type OnLog<'a> = Box<dyn FnMut(&str) + 'a>;
+
+/// Submitter of batches which waits for them to finish
+///
+/// The runner polls the state and the log of the batch every `interval`
+/// and passes new log lines to the log callback. Lines are fetched by
+/// their index in the log, starting after the last line received. Livy
+/// keeps only the last `livy.cache-log.size` lines of a log, so once a log
+/// is that long its indexes stop growing and later lines are not followed.
+///
+/// Failed polls are retried, and the runner only gives up after
+/// `poll_retries` failures in a row, with an error naming the batch,
+/// which may still be running then.
+pub struct BatchRunner<'a> {
+    client: &'a Client,
+    interval: Duration,
+    timeout: Option<Duration>,
+    kill_on_timeout: bool,
+    tail: usize,
+    poll_retries: u32,
+    on_log: Option<OnLog<'a>>,
+}
+
+impl<'a> BatchRunner<'a> {
+    /// Constructs a new `BatchRunner` which sends requests through `client`.
+    pub fn new(client: &'a Client) -> BatchRunner<'a> {
+        BatchRunner {
+            client,
+            interval: DEFAULT_INTERVAL,
+            timeout: None,
+            kill_on_timeout: false,
+            tail: DEFAULT_TAIL,
+            poll_retries: DEFAULT_POLL_RETRIES,
+            on_log: None,
+        }
+    }
+
+    /// Sets the polling interval.
+    pub fn interval(mut self, interval: Duration) -> BatchRunner<'a> {
+        self.interval = interval;
+        self
+    }
+
+    /// Stops waiting for the batch after `timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> BatchRunner<'a> {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Kills the batch if it is still running at the timeout.
+    pub fn kill_on_timeout(mut self) -> BatchRunner<'a> {
+        self.kill_on_timeout = true;
+        self
+    }
+
+    /// Sets the number of log lines kept in the result.
+    pub fn tail(mut self, tail: usize) -> BatchRunner<'a> {
+        self.tail = tail;
+        self
+    }
+
+    /// Sets the number of consecutive failed polls which are retried.
+    pub fn poll_retries(mut self, poll_retries: u32) -> BatchRunner<'a> {
+        self.poll_retries = poll_retries;
+        self
+    }
+
+    /// Calls `on_log` with every new log line.
+    pub fn on_log<F: FnMut(&str) + 'a>(mut self, on_log: F) -> BatchRunner<'a> {
+        self.on_log = Some(Box::new(on_log));
+        self
+    }
+
+    /// Creates a batch and waits until it finishes or the timeout passes.
+    pub fn run(&mut self, request: NewBatchRequest) -> Result<BatchResult, String> {
+        let batch = self.client.create_batch(request)?;
+        let id = batch.id.ok_or("the created batch has no id")?;
+
+        self.follow(id, Some(batch))
+    }
+
+    /// Waits until the existing batch `id` finishes or the timeout passes.
+    pub fn wait(&mut self, id: i64) -> Result<BatchResult, String> {
+        self.follow(id, None)
+    }
+
+    fn follow(&mut self, id: i64, submitted: Option<Batch>) -> Result<BatchResult, String> {
+        let start = Instant::now();
+        let mut log = VecDeque::new();
+        let mut next_from = 0;
+        let mut state = submitted.as_ref().and_then(|batch| batch.state.clone());
+        let mut failures = 0;
+        let mut timed_out = false;
+        let mut killed = false;
+
+        loop {
+            let polled = match self.client.get_batch_state(id) {
+                Ok(batch_state) => self.follow_log(id, &mut log, &mut next_from).map(|_| batch_state),
+                Err(err) => Err(err),
+            };
+
+            match polled {
+                Ok(batch_state) => {
+                    failures = 0;
+                    state = batch_state.state.or(state);
+                    if state.as_deref().is_some_and(is_finished) {
+                        break;
+                    }
+                },
+                Err(err) => {
+                    failures += 1;
+                    if failures > self.poll_retries {
+                        return Err(format!("batch {} could not be polled and may still be running: {}", id, err));
+                    }
+                },
+            }
+
+            if self.timeout.is_some_and(|timeout| start.elapsed() >= timeout) {
+                timed_out = true;
+                if self.kill_on_timeout {
+                    self.client.kill_batch(id)
+                        .map_err(|err| format!("batch {} timed out and could not be killed: {}", id, err))?;
+                    killed = true;
+                }
+                break;
+            }
+
+            thread::sleep(self.interval);
+        }
+
+        let duration = start.elapsed();
+        // Livy forgets killed batches, so only the submission is known then.
+        let batch = if killed { submitted } else { self.client.get_batch(id).ok().or(submitted) };
+        let (app_id, app_info) = batch.map_or((None, None), |batch| (batch.app_id, batch.app_info));
+        let skip = log.len().saturating_sub(self.tail);
+
+        Ok(BatchResult {
+            id,
+            state,
+            app_id,
+            app_info,
+            duration,
+            log: log.into_iter().skip(skip).collect(),
+            timed_out,
+            killed,
+        })
+    }
+
+    /// Fetches the log lines from `next_from` on, passes them to the log
+    /// callback and advances `next_from` past them.
+    fn follow_log(&mut self, id: i64, log: &mut VecDeque<String>, next_from: &mut i64) -> Result<(), String> {
+        loop {
+            let page = self.client.get_batch_log(id, Some(*next_from), Some(LOG_PAGE_SIZE))?;
+            let lines = page.log.unwrap_or_default();
+
+            for line in &lines {
+                if let Some(ref mut on_log) = self.on_log {
+                    on_log(line);
+                }
+                log.push_back(line.clone());
+            }
+            let excess = log.len().saturating_sub(self.tail);
+            log.drain(..excess);
+
+            *next_from = page.from.unwrap_or(*next_from) + lines.len() as i64;
+            if lines.is_empty() || page.total.is_none_or(|total| *next_from >= total) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::NewBatchRequestBuilder;
+    use testing::FakeLivy;
+
+    #[test]
+    fn test_run() {
+        let livy = FakeLivy::start();
+        livy.set_batch_polls(2);
+        livy.set_batch_outcome("dead");
+        let client = livy.client();
+
+        let mut lines = Vec::new();
+        let result = BatchRunner::new(&client)
+            .interval(Duration::from_millis(1))
+            .tail(1)
+            .on_log(|line| lines.push(line.to_string()))
+            .run(NewBatchRequestBuilder::new("app.jar").build().unwrap())
+            .unwrap();
+
+        assert_eq!(Some("dead".to_string()), result.state);
+        assert!(!result.is_success());
+        assert_eq!(Some("application_0000000000000_1000".to_string()), result.app_id);
+        assert!(result.app_info.unwrap().contains_key("sparkUiUrl"));
+        assert_eq!(vec!["batch 0 submitted".to_string()], result.log);
+        assert_eq!(vec!["batch 0 submitted".to_string()], lines);
+        assert!(!result.timed_out);
+
+        livy.set_batch_polls(1000);
+        let result = BatchRunner::new(&client)
+            .interval(Duration::from_millis(1))
+            .timeout(Duration::from_millis(20))
+            .kill_on_timeout()
+            .run(NewBatchRequestBuilder::new("app.jar").build().unwrap())
+            .unwrap();
+
+        assert_eq!((true, true), (result.timed_out, result.killed));
+        assert_eq!(Some("starting".to_string()), result.state);
+        assert_eq!(None, livy.batch_state(1));
+    }
+
+    #[test]
+    fn test_wait() {
+        let livy = FakeLivy::start();
+        livy.set_batch_polls(2);
+        livy.set_batch_outcome("success");
+        let client = livy.client();
+
+        let id = client.create_batch(NewBatchRequestBuilder::new("app.jar").build().unwrap()).unwrap().id.unwrap();
+        livy.push_batch_log(id, "retrying");
+        livy.push_batch_log(id, "retrying");
+        livy.fail_next(503, 3);
+
+        let mut lines = Vec::new();
+        let result = BatchRunner::new(&client)
+            .interval(Duration::from_millis(1))
+            .on_log(|line| lines.push(line.to_string()))
+            .wait(id)
+            .unwrap();
+
+        assert!(result.is_success());
+        assert_eq!(vec!["batch 0 submitted", "retrying", "retrying"], lines);
+
+        let id = client.create_batch(NewBatchRequestBuilder::new("app.jar").build().unwrap()).unwrap().id.unwrap();
+        livy.fail_next(503, 2);
+        let err = BatchRunner::new(&client)
+            .interval(Duration::from_millis(1))
+            .poll_retries(1)
+            .wait(id)
+            .unwrap_err();
+
+        assert!(err.starts_with("batch 1 could not be polled and may still be running"), "{}", err);
+    }
+}
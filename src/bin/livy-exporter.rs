@@ -0,0 +1,179 @@
+//! # livy-exporter
+//! Prometheus exporter of the sessions and batches of an Apache Livy server
+
+extern crate livy;
+
+#[path = "livy/args.rs"]
+#[allow(dead_code)]
+mod args;
+
+use livy::client;
+use livy::metrics::Exporter;
+use std::env;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const USAGE: &str = "\
+Usage: livy-exporter [options]
+
+Lists the sessions and batches of a Livy server periodically and serves the
+metrics in the Prometheus text format at http://ADDRESS/metrics.
+
+Options:
+    --listen ADDRESS    address to listen on; defaults to 127.0.0.1:9898
+    --interval SECONDS  seconds between scrapes; defaults to 15
+    --page-size N       sessions or batches requested per page; defaults to 100
+    --context NAME      profile of the config file, ~/.config/livy/config.toml;
+                        defaults to $LIVY_PROFILE or currentProfile
+    --url URL           Livy endpoint; defaults to $LIVY_URL, then the profile
+    --gssnegotiate      authenticate with SPNEGO
+    --username NAME     user name sent to Livy";
+
+const OPTIONS: &[&str] = &["listen", "interval", "page-size"];
+const FLAGS: &[&str] = &["help"];
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:9898";
+const DEFAULT_INTERVAL: u64 = 15;
+/// Time after which a scraper which stops sending or receiving is dropped
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+/// Maximum size of a request, i.e. its request line and headers
+const MAX_REQUEST_SIZE: u64 = 16 * 1024;
+
+fn main() {
+    let argv: Vec<String> = env::args().skip(1).collect();
+
+    if let Err(err) = run(&argv) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run(argv: &[String]) -> Result<(), String> {
+    let options = args::with_client_options(OPTIONS, args::CLIENT_OPTIONS);
+    let flags = args::with_client_options(FLAGS, args::CLIENT_FLAGS);
+    let matches = args::parse(argv, &options, &flags)?;
+
+    if matches.has("help") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    if let Some(arg) = matches.free.first() {
+        return Err(format!("unexpected argument: {}\n\n{}", arg, USAGE));
+    }
+
+    let listen = matches.value("listen").unwrap_or(DEFAULT_LISTEN);
+    let interval = match matches.parse("interval")?.unwrap_or(DEFAULT_INTERVAL) {
+        0 => return Err("invalid interval: 0".to_string()),
+        seconds => Duration::from_secs(seconds),
+    };
+    let page_size = matches.parse("page-size")?.unwrap_or(client::DEFAULT_PAGE_SIZE);
+
+    let mut exporter = Exporter::new(args::client(&matches)?).page_size(page_size);
+    let latest = Arc::new(Mutex::new(exporter.scrape()));
+    let listener = TcpListener::bind(listen).map_err(|err| format!("failed to listen on {}: {}", listen, err))?;
+
+    {
+        let latest = Arc::clone(&latest);
+
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            let metrics = exporter.scrape();
+            *latest.lock().unwrap_or_else(|err| err.into_inner()) = metrics;
+        });
+    }
+
+    eprintln!("serving metrics at http://{}/metrics", listen);
+
+    for stream in listener.incoming().flatten() {
+        let metrics = latest.lock().unwrap_or_else(|err| err.into_inner()).clone();
+        // A slow or broken connection only affects the scraper which made it.
+        thread::spawn(move || serve(stream, &metrics));
+    }
+
+    Ok(())
+}
+
+/// Reads a request from `stream` and writes the response.
+fn serve(mut stream: TcpStream, metrics: &str) -> Result<(), String> {
+    stream.set_read_timeout(Some(CONNECTION_TIMEOUT)).map_err(|err| format!("{}", err))?;
+    stream.set_write_timeout(Some(CONNECTION_TIMEOUT)).map_err(|err| format!("{}", err))?;
+
+    let mut reader = BufReader::new(stream.try_clone().map_err(|err| format!("{}", err))?.take(MAX_REQUEST_SIZE));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).map_err(|err| format!("{}", err))?;
+
+    // The headers are read so that the connection is not reset before the
+    // scraper reads the response.
+    let mut line = String::new();
+    while reader.read_line(&mut line).map_err(|err| format!("{}", err))? > 2 {
+        line.clear();
+    }
+
+    let (status, content_type, body) = respond(&request_line, metrics);
+    let response = format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                           status, content_type, body.len(), body);
+
+    stream.write_all(response.as_bytes()).map_err(|err| format!("{}", err))
+}
+
+/// Returns the status, the content type and the body of the response to
+/// a request line such as `GET /metrics HTTP/1.1`.
+fn respond(request_line: &str, metrics: &str) -> (&'static str, &'static str, String) {
+    let mut parts = request_line.split_whitespace();
+
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", "text/plain; version=0.0.4", metrics.to_string()),
+        (Some("GET"), Some("/")) => ("200 OK", "text/plain", "Livy exporter; metrics are served at /metrics\n".to_string()),
+        (Some("GET"), Some(_)) => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain", "method not allowed\n".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_respond() {
+        struct TestCase {
+            request_line: &'static str,
+            expected_status: &'static str,
+            expected_body: &'static str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                request_line: "GET /metrics HTTP/1.1\r\n",
+                expected_status: "200 OK",
+                expected_body: "livy_up 1\n",
+            },
+            TestCase {
+                request_line: "GET /favicon.ico HTTP/1.1\r\n",
+                expected_status: "404 Not Found",
+                expected_body: "not found\n",
+            },
+            TestCase {
+                request_line: "POST /metrics HTTP/1.1\r\n",
+                expected_status: "405 Method Not Allowed",
+                expected_body: "method not allowed\n",
+            },
+        ];
+
+        for test_case in test_cases {
+            let (status, _, body) = respond(test_case.request_line, "livy_up 1\n");
+
+            assert_eq!(test_case.expected_status, status);
+            assert_eq!(test_case.expected_body, body);
+        }
+    }
+    #[test]
+    fn test_invalid_interval() {
+        let argv: Vec<String> = vec!["--url", "http://127.0.0.1:1", "--interval", "0"].into_iter().map(String::from).collect();
+
+        assert_eq!(Err("invalid interval: 0".to_string()), run(&argv));
+    }
+}
@@ -0,0 +1,167 @@
+use livy::auth::Secret;
+use livy::client::Client;
+use livy::config::{Auth, Config};
+use std::collections::HashMap;
+
+/// Options accepted by every subcommand which talks to Livy
+pub const CLIENT_OPTIONS: &[&str] = &["url", "username", "context"];
+/// Flags accepted by every subcommand which talks to Livy
+pub const CLIENT_FLAGS: &[&str] = &["gssnegotiate"];
+
+/// Parsed command line arguments
+#[derive(Debug, PartialEq)]
+pub struct Matches {
+    options: HashMap<String, Vec<String>>,
+    pub free: Vec<String>,
+}
+
+impl Matches {
+    /// Returns the last value of the option `name`.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options.get(name).and_then(|values| values.last()).map(String::as_str)
+    }
+
+    /// Returns all values of the option `name` in the order given.
+    pub fn values(&self, name: &str) -> Vec<&str> {
+        self.options.get(name).map_or_else(Vec::new, |values| values.iter().map(String::as_str).collect())
+    }
+
+    /// Returns `true` if the flag or option `name` was given.
+    pub fn has(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+
+    /// Parses the value of the option `name` into `T`.
+    pub fn parse<T>(&self, name: &str) -> Result<Option<T>, String>
+        where T: ::std::str::FromStr, T::Err: ::std::fmt::Display {
+        match self.value(name) {
+            Some(value) => value.parse()
+                .map(Some)
+                .map_err(|err| format!("invalid value for --{}: {}", name, err)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Parses `args` which are made up of `--option value`, `--option=value`,
+/// `--flag` and free arguments. Everything after `--` is a free argument.
+pub fn parse(args: &[String], options: &[&str], flags: &[&str]) -> Result<Matches, String> {
+    let mut matches = Matches {
+        options: HashMap::new(),
+        free: Vec::new(),
+    };
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            matches.free.extend(args.cloned());
+            break;
+        }
+
+        if !arg.starts_with("--") {
+            matches.free.push(arg.clone());
+            continue;
+        }
+
+        let (name, inline_value) = match arg[2..].find('=') {
+            Some(i) => (&arg[2..2 + i], Some(arg[3 + i..].to_string())),
+            None => (&arg[2..], None),
+        };
+
+        let value = if options.contains(&name) {
+            match inline_value {
+                Some(value) => value,
+                None => match args.next() {
+                    Some(value) => value.clone(),
+                    None => return Err(format!("option --{} requires a value", name)),
+                },
+            }
+        } else if flags.contains(&name) && inline_value.is_none() {
+            String::new()
+        } else {
+            return Err(format!("unknown option: {}", arg));
+        };
+
+        matches.options.entry(name.to_string()).or_default().push(value);
+    }
+
+    Ok(matches)
+}
+
+/// Concatenates option lists of a subcommand and the common client options.
+pub fn with_client_options<'a>(options: &[&'a str], common: &[&'a str]) -> Vec<&'a str> {
+    options.iter().chain(common.iter()).cloned().collect()
+}
+
+/// Constructs a `Client` from the profile of the config file selected by
+/// `--context`, overridden by `--url`, `--gssnegotiate` and `--username`.
+pub fn client(matches: &Matches) -> Result<Client, String> {
+    let mut profile = Config::load_default()?.profile(matches.value("context"))?;
+
+    if let Some(url) = matches.value("url") {
+        profile.url = Some(url.to_string());
+    }
+    if profile.url.is_none() {
+        return Err("--url, LIVY_URL or a profile with a url is required".to_string());
+    }
+
+    let username = matches.value("username").map(String::from);
+    if matches.has("gssnegotiate") {
+        profile.auth = Some(Auth::Spnego { username });
+    } else if let Some(username) = username {
+        profile.auth = Some(Auth::Basic { username, password: Some(Secret::new("")), password_env: None });
+    }
+
+    profile.client()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        struct TestCase {
+            args: Vec<String>,
+            expected: Result<(Option<String>, bool, Vec<String>), String>,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                args: strings(&["--kind", "pyspark", "file.py"]),
+                expected: Ok((Some("pyspark".to_string()), false, strings(&["file.py"]))),
+            },
+            TestCase {
+                args: strings(&["--kind=spark", "--keep"]),
+                expected: Ok((Some("spark".to_string()), true, vec![])),
+            },
+            TestCase {
+                args: strings(&["--keep", "--", "--kind", "x"]),
+                expected: Ok((None, true, strings(&["--kind", "x"]))),
+            },
+            TestCase {
+                args: strings(&["--kind"]),
+                expected: Err("option --kind requires a value".to_string()),
+            },
+            TestCase {
+                args: strings(&["--keep=1"]),
+                expected: Err("unknown option: --keep=1".to_string()),
+            },
+            TestCase {
+                args: strings(&["--unknown"]),
+                expected: Err("unknown option: --unknown".to_string()),
+            },
+        ];
+
+        for test_case in test_cases {
+            let actual = parse(&test_case.args, &["kind"], &["keep"])
+                .map(|matches| (matches.value("kind").map(String::from), matches.has("keep"), matches.free.clone()));
+
+            assert_eq!(test_case.expected, actual);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use args;
+use livy::spec::Loader;
+use serde_json;
+
+pub const USAGE: &str = "\
+Usage: livy batches submit -f FILE [--profile NAME] [--dry-run]
+
+Submits the batch of a job spec, a JSON, TOML or YAML file with a [batch]
+table of request fields and optional [profiles.NAME] tables overriding them.
+${NAME} and ${NAME:-default} in string values are replaced with environment
+variables.
+
+Options:
+    -f, --file FILE     job spec to submit
+    --profile NAME      apply the profile NAME of the spec, e.g. prod
+    --dry-run           print the batch request instead of submitting it";
+
+const OPTIONS: &[&str] = &["file", "profile"];
+const FLAGS: &[&str] = &["dry-run"];
+
+/// Runs `livy batches`.
+pub fn run(argv: &[String]) -> Result<(), String> {
+    match argv.first().map(String::as_str) {
+        Some("submit") => submit(&argv[1..]),
+        Some(command) => Err(format!("unknown command: batches {}\n\n{}", command, USAGE)),
+        None => Err(format!("a command is required\n\n{}", USAGE)),
+    }
+}
+
+/// Runs `livy batches submit`.
+fn submit(argv: &[String]) -> Result<(), String> {
+    let options = args::with_client_options(OPTIONS, args::CLIENT_OPTIONS);
+    let flags = args::with_client_options(FLAGS, args::CLIENT_FLAGS);
+    let matches = args::parse(&expand_short_options(argv), &options, &flags)?;
+    if let Some(arg) = matches.free.first() {
+        return Err(format!("unexpected argument: {}\n\n{}", arg, USAGE));
+    }
+
+    let file = matches.value("file").ok_or_else(|| format!("-f is required\n\n{}", USAGE))?;
+    let mut loader = Loader::new();
+    if let Some(profile) = matches.value("profile") {
+        loader = loader.profile(profile);
+    }
+    let request = loader.load_batch(file)?;
+
+    if matches.has("dry-run") {
+        println!("{}", serde_json::to_string_pretty(&request).map_err(|err| format!("{}", err))?);
+        return Ok(());
+    }
+
+    let batch = args::client(&matches)?.create_batch(request)?;
+
+    println!("{}", serde_json::to_string_pretty(&batch).map_err(|err| format!("{}", err))?);
+
+    Ok(())
+}
+
+/// Replaces `-f` with `--file`, which `args::parse` understands.
+fn expand_short_options(argv: &[String]) -> Vec<String> {
+    argv.iter()
+        .map(|arg| if arg == "-f" { "--file".to_string() } else { arg.clone() })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_short_options() {
+        struct TestCase {
+            argv: Vec<&'static str>,
+            expected: Vec<&'static str>,
+        }
+
+        let test_cases = vec![
+            TestCase { argv: vec!["-f", "job.toml", "--profile", "prod"], expected: vec!["--file", "job.toml", "--profile", "prod"] },
+            TestCase { argv: vec!["--file=job.toml"], expected: vec!["--file=job.toml"] },
+        ];
+
+        for test_case in test_cases {
+            let argv: Vec<String> = test_case.argv.iter().map(|arg| arg.to_string()).collect();
+
+            assert_eq!(test_case.expected, expand_short_options(&argv));
+        }
+    }
+}
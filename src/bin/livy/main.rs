@@ -0,0 +1,74 @@
+//! # livy
+//! Command line interface of the Apache Livy REST API client
+
+extern crate livy;
+extern crate serde_json;
+extern crate rustyline;
+extern crate signal_hook;
+
+mod args;
+mod batches;
+mod notebook;
+mod reap;
+mod repl;
+mod run;
+mod session;
+mod submit;
+
+use std::env;
+use std::process;
+
+const USAGE: &str = "\
+Usage: livy <command> [options]
+
+Commands:
+    batches     submit a batch from a job spec file
+    notebook    run a Jupyter notebook in a session
+    reap        kill orphaned sessions and batches
+    repl        start an interactive shell on a session
+    run         run a script split into cells in a session
+    submit      submit a batch from a spark-submit command line
+
+Options common to every command:
+    --context NAME      profile of the config file, ~/.config/livy/config.toml;
+                        defaults to $LIVY_PROFILE or currentProfile
+    --url URL           Livy endpoint; defaults to $LIVY_URL, then the profile
+    --gssnegotiate      authenticate with SPNEGO
+    --username NAME     user name sent to Livy
+
+Run `livy help <command>` for the options of a command.";
+
+fn main() {
+    let argv: Vec<String> = env::args().skip(1).collect();
+
+    let result = match argv.first().map(String::as_str) {
+        Some("batches") => batches::run(&argv[1..]),
+        Some("notebook") => notebook::run(&argv[1..]),
+        Some("reap") => reap::run(&argv[1..]),
+        Some("repl") => repl::run(&argv[1..]),
+        Some("run") => run::run(&argv[1..]),
+        Some("submit") => submit::run(&argv[1..]),
+        Some("help") | Some("--help") | Some("-h") | None => {
+            println!("{}", usage(argv.get(1).map(String::as_str)));
+            Ok(())
+        },
+        Some(command) => Err(format!("unknown command: {}\n\n{}", command, USAGE)),
+    };
+
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn usage(command: Option<&str>) -> &'static str {
+    match command {
+        Some("batches") => batches::USAGE,
+        Some("notebook") => notebook::USAGE,
+        Some("reap") => reap::USAGE,
+        Some("repl") => repl::USAGE,
+        Some("run") => run::USAGE,
+        Some("submit") => submit::USAGE,
+        _ => USAGE,
+    }
+}
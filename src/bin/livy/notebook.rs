@@ -0,0 +1,68 @@
+use args;
+use livy::notebook;
+use session::{self, Session};
+use signal_hook;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+pub const USAGE: &str = "\
+Usage: livy notebook NOTEBOOK [--output FILE] [--kind KIND | --session ID] [--keep] [--interval MILLIS]
+
+Runs the code cells of a Jupyter notebook in a session and writes their
+outputs back into the notebook, stopping at the first failing cell.
+
+Options:
+    --output FILE       write the executed notebook to FILE instead of
+                        overwriting NOTEBOOK
+    --kind KIND         kind of the session to create; guessed from the
+                        kernel of NOTEBOOK by default
+    --session ID        run the cells in an existing session
+    --keep              do not kill the created session on exit
+    --interval MILLIS   polling interval (default: 500)";
+
+/// Runs `livy notebook`.
+pub fn run(argv: &[String]) -> Result<(), String> {
+    let options = args::with_client_options(&[session::OPTIONS, &["output"]].concat(), args::CLIENT_OPTIONS);
+    let flags = args::with_client_options(session::FLAGS, args::CLIENT_FLAGS);
+    let matches = args::parse(argv, &options, &flags)?;
+    let path = match matches.free.as_slice() {
+        [path] => Path::new(path),
+        _ => return Err(format!("exactly one NOTEBOOK is required\n\n{}", USAGE)),
+    };
+    let output = matches.value("output").map_or(path, Path::new);
+    let client = args::client(&matches)?;
+
+    let source = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let mut nb = notebook::parse(&source).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let kind = matches.parse("kind")?
+        .or_else(|| notebook::kind_of(&nb))
+        .ok_or("--kind is required for notebooks whose kernel is unknown")?;
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&interrupted))
+        .map_err(|err| format!("{}", err))?;
+
+    let session = Session::open(&client, &matches, Some(kind), &interrupted)?;
+    let report = notebook::run(&client, session.id, &mut nb, session.interval, &interrupted);
+    for result in report.results.iter().filter(|result| !result.statement.is_success()) {
+        eprintln!("cell {} failed", result.cell.line);
+        session::print_statement(&result.statement);
+    }
+    if let Some(ref error) = report.error {
+        eprintln!("cell {} failed: {}", error.cell.line, error.error);
+    }
+    let report = notebook::to_string(&nb)
+        .and_then(|s| fs::write(output, s).map_err(|err| format!("{}: {}", output.display(), err)))
+        .map(|()| report);
+
+    let report = session.close_after(&client, report)?;
+
+    if report.is_success() {
+        eprintln!("{} cells succeeded in {:.3}s", report.results.len(), report.duration().as_secs_f64());
+        Ok(())
+    } else {
+        Err(format!("{} failed", path.display()))
+    }
+}
@@ -0,0 +1,185 @@
+use args;
+use livy::client::SessionState;
+use livy::reaper::{Policy, Reaper};
+use serde_json::{self, Value};
+use std::time::Duration;
+
+pub const USAGE: &str = "\
+Usage: livy reap [--idle DURATION [--log-utc-offset OFFSET]] [--owner NAME]...
+                 [--proxy-user NAME]... [--name PATTERN]... [--state STATE]...
+                 [--dry-run]
+
+Kills the sessions and batches which match all the given criteria.
+
+Options:
+    --idle DURATION     idle for longer than DURATION, e.g. 90s, 30m, 2h or 1d,
+                        judged by statement times and, with --log-utc-offset,
+                        log timestamps
+    --log-utc-offset OFFSET
+                        UTC offset of the log timestamps of the cluster,
+                        e.g. +09:00, -05:00 or Z; without it logs are ignored
+                        and batches never match --idle
+    --owner NAME        owned by NAME; may be repeated
+    --proxy-user NAME   run as NAME; may be repeated
+    --name PATTERN      named after PATTERN, e.g. tmp-*; may be repeated
+    --state STATE       in STATE, e.g. dead or error; may be repeated
+    --dry-run           print the matching sessions and batches without killing them";
+
+const OPTIONS: &[&str] = &["idle", "log-utc-offset", "owner", "proxy-user", "name", "state"];
+const FLAGS: &[&str] = &["dry-run"];
+
+/// Runs `livy reap`.
+pub fn run(argv: &[String]) -> Result<(), String> {
+    let options = args::with_client_options(OPTIONS, args::CLIENT_OPTIONS);
+    let flags = args::with_client_options(FLAGS, args::CLIENT_FLAGS);
+    let matches = args::parse(argv, &options, &flags)?;
+    if let Some(arg) = matches.free.first() {
+        return Err(format!("unexpected argument: {}\n\n{}", arg, USAGE));
+    }
+
+    let mut policy = Policy::new();
+    if let Some(idle) = matches.value("idle") {
+        policy = policy.idle_for(parse_duration(idle)?);
+    }
+    if let Some(offset) = matches.value("log-utc-offset") {
+        policy = policy.log_utc_offset(parse_utc_offset(offset)?);
+    }
+    for owner in matches.values("owner") {
+        policy = policy.owner(owner);
+    }
+    for proxy_user in matches.values("proxy-user") {
+        policy = policy.proxy_user(proxy_user);
+    }
+    for name in matches.values("name") {
+        policy = policy.name(name);
+    }
+    for state in matches.values("state") {
+        policy = policy.state(serde_json::from_value::<SessionState>(Value::from(state))
+            .map_err(|_| format!("invalid value for --state: {}", state))?);
+    }
+
+    let reaper = Reaper::new(args::client(&matches)?, policy);
+
+    if matches.has("dry-run") {
+        for candidate in reaper.find()? {
+            println!("would kill {}", candidate);
+        }
+        return Ok(());
+    }
+
+    let mut failures = 0;
+    for outcome in reaper.reap()? {
+        match outcome.result {
+            Ok(()) => println!("killed {}", outcome.candidate),
+            Err(err) => {
+                eprintln!("failed to kill {}: {}", outcome.candidate, err);
+                failures += 1;
+            },
+        }
+    }
+
+    if failures > 0 {
+        return Err(format!("failed to kill {} sessions or batches", failures));
+    }
+
+    Ok(())
+}
+
+/// Parses a duration such as `90s`, `30m`, `2h` or `1d`. A number without
+/// a unit is in seconds.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => (&s[..i], &s[i..]),
+        None => (s, "s"),
+    };
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("invalid duration: {}", s)),
+    };
+
+    number.parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(seconds))
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("invalid duration: {}", s))
+}
+
+/// Parses a UTC offset such as `+09:00`, `-0530` or `Z` into seconds.
+fn parse_utc_offset(s: &str) -> Result<i64, String> {
+    let invalid = || format!("invalid UTC offset: {}; expected e.g. +09:00, -05:00 or Z", s);
+    if s == "Z" {
+        return Ok(0);
+    }
+
+    let sign = match s.chars().next() {
+        Some('+') => 1,
+        Some('-') => -1,
+        _ => return Err(invalid()),
+    };
+    let digits = match s[1..].len() {
+        5 if s.as_bytes()[3] == b':' && s.is_ascii() => format!("{}{}", &s[1..3], &s[4..]),
+        _ => s[1..].to_string(),
+    };
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let (hours, minutes) = (digits[..2].parse::<i64>().map_err(|_| invalid())?, digits[2..].parse::<i64>().map_err(|_| invalid())?);
+    if hours > 14 || minutes > 59 {
+        return Err(invalid());
+    }
+
+    Ok(sign * (hours * 60 + minutes) * 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        struct TestCase {
+            s: &'static str,
+            expected: Result<Duration, String>,
+        }
+
+        let test_cases = vec![
+            TestCase { s: "90", expected: Ok(Duration::from_secs(90)) },
+            TestCase { s: "30m", expected: Ok(Duration::from_secs(1800)) },
+            TestCase { s: "2h", expected: Ok(Duration::from_secs(7200)) },
+            TestCase { s: "1d", expected: Ok(Duration::from_secs(86400)) },
+            TestCase { s: "h", expected: Err("invalid duration: h".to_string()) },
+            TestCase { s: "2w", expected: Err("invalid duration: 2w".to_string()) },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected, parse_duration(test_case.s));
+        }
+    }
+
+    #[test]
+    fn test_parse_utc_offset() {
+        struct TestCase {
+            s: &'static str,
+            expected: Result<i64, ()>,
+        }
+
+        let test_cases = vec![
+            TestCase { s: "Z", expected: Ok(0) },
+            TestCase { s: "+00:00", expected: Ok(0) },
+            TestCase { s: "+09:00", expected: Ok(32400) },
+            TestCase { s: "-0530", expected: Ok(-19800) },
+            TestCase { s: "09:00", expected: Err(()) },
+            TestCase { s: "+9", expected: Err(()) },
+            TestCase { s: "+0:900", expected: Err(()) },
+            TestCase { s: "+15:00", expected: Err(()) },
+            TestCase { s: "+09:60", expected: Err(()) },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected, parse_utc_offset(test_case.s).map_err(|_| ()), "{}", test_case.s);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use args;
+use livy::client::{Client, RunStatementRequest, Statement};
+use livy::wait;
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+use session::{self, Session};
+use signal_hook;
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+pub const USAGE: &str = "\
+Usage: livy repl [--kind KIND | --session ID] [--keep] [--interval MILLIS]
+
+Starts an interactive shell on a Livy session.
+
+Options:
+    --kind KIND         kind of the session to create (spark, pyspark, pyspark3, sparkr, sql)
+    --session ID        attach to an existing session instead of creating one
+    --keep              do not kill the created session on exit
+    --interval MILLIS   polling interval (default: 500)
+
+Input is sent when a blank line is entered. A single line is sent
+immediately unless it ends with `:`, `{`, `(`, `[`, `,` or `\\`.
+Lines can be edited, and the code sent earlier, including in previous
+runs, is recalled with the arrow keys from ~/.livy_history.
+Ctrl-C cancels the running statement or discards the code being typed,
+and a second Ctrl-C while the first is still being handled exits.
+`:history` prints the code sent so far and `:quit` or Ctrl-D exits.
+Attached sessions are never killed.";
+
+const PROMPT: &str = ">>> ";
+const CONTINUATION_PROMPT: &str = "... ";
+
+/// Runs `livy repl`.
+pub fn run(argv: &[String]) -> Result<(), String> {
+    let options = args::with_client_options(session::OPTIONS, args::CLIENT_OPTIONS);
+    let flags = args::with_client_options(session::FLAGS, args::CLIENT_FLAGS);
+    let matches = args::parse(argv, &options, &flags)?;
+    let client = args::client(&matches)?;
+
+    // The shutdown must be registered first so that it only sees the flag
+    // set by an earlier Ctrl-C which has not been handled yet.
+    let interrupted = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register_conditional_shutdown(signal_hook::consts::SIGINT, 130, Arc::clone(&interrupted))
+        .map_err(|err| format!("{}", err))?;
+    signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&interrupted))
+        .map_err(|err| format!("{}", err))?;
+
+    let session = Session::open(&client, &matches, None, &interrupted)?;
+    let result = repl(&client, session.id, session.interval, &interrupted);
+
+    session.close_after(&client, result)
+}
+
+fn repl(client: &Client, session_id: i64, interval: Duration, interrupted: &AtomicBool) -> Result<(), String> {
+    let mut history = History::open()?;
+    let mut buffer = String::new();
+
+    loop {
+        // Ctrl-C at a terminal prompt is read as a key rather than a signal.
+        let line = match history.editor.readline(if buffer.is_empty() { PROMPT } else { CONTINUATION_PROMPT }) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                buffer.clear();
+                continue;
+            },
+            Err(ReadlineError::Eof) => return Ok(()),
+            Err(err) => return Err(format!("{}", err)),
+        };
+
+        if buffer.is_empty() {
+            match line.trim() {
+                ":quit" => return Ok(()),
+                ":history" => {
+                    for (i, code) in history.entries.iter().enumerate() {
+                        println!("[{}]\n{}", i + 1, code);
+                    }
+                    continue;
+                },
+                _ => (),
+            }
+        }
+
+        if !buffer.is_empty() {
+            buffer.push('\n');
+        }
+        buffer.push_str(&line);
+
+        if !is_complete(&buffer, &line) {
+            continue;
+        }
+
+        let code = buffer.trim_end().to_string();
+        buffer.clear();
+
+        if code.trim().is_empty() {
+            continue;
+        }
+
+        history.push(&code);
+        // A Ctrl-C which arrived while no statement was running must not
+        // cancel this one.
+        interrupted.store(false, Ordering::SeqCst);
+
+        match execute(client, session_id, code, interval, interrupted) {
+            Ok(statement) => session::print_statement(&statement),
+            Err(err) => eprintln!("error: {}", err),
+        }
+    }
+}
+
+fn execute(client: &Client, session_id: i64, code: String, interval: Duration, interrupted: &AtomicBool) -> Result<Statement, String> {
+    let statement = client.run_statement(session_id, RunStatementRequest { code })?;
+    let statement_id = statement.id.ok_or("Livy did not return a statement id")?;
+
+    wait::wait_for_statement(client, session_id, statement_id, interval, interrupted)
+}
+
+/// Returns `true` if `buffer` whose last line is `line` should be sent.
+fn is_complete(buffer: &str, line: &str) -> bool {
+    if line.trim().is_empty() {
+        return true;
+    }
+
+    if buffer.contains('\n') {
+        return false;
+    }
+
+    !line.trim_end().ends_with(|c| ":{([,\\".contains(c))
+}
+
+/// Line editor recalling the code sent to sessions, persisted in
+/// `~/.livy_history`
+struct History {
+    editor: DefaultEditor,
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    fn open() -> Result<History, String> {
+        History::at(env::var_os("HOME").map(|home| PathBuf::from(home).join(".livy_history")))
+    }
+
+    fn at(path: Option<PathBuf>) -> Result<History, String> {
+        let mut history = History {
+            editor: DefaultEditor::new().map_err(|err| format!("{}", err))?,
+            entries: Vec::new(),
+            path,
+        };
+
+        // History is a convenience; failing to load or persist it must not
+        // end the session.
+        if let Some(ref path) = history.path {
+            let _ = history.editor.load_history(path);
+        }
+
+        Ok(history)
+    }
+
+    /// Records `code`, which is written to the file with its newlines
+    /// escaped so that every entry is read back whole.
+    fn push(&mut self, code: &str) {
+        self.entries.push(code.to_string());
+
+        let _ = self.editor.add_history_entry(code);
+        if let Some(ref path) = self.path {
+            let _ = self.editor.append_history(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_complete() {
+        struct TestCase {
+            buffer: &'static str,
+            line: &'static str,
+            expected: bool,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                buffer: "1 + 1",
+                line: "1 + 1",
+                expected: true,
+            },
+            TestCase {
+                buffer: "def f(x):",
+                line: "def f(x):",
+                expected: false,
+            },
+            TestCase {
+                buffer: "val xs = Seq(",
+                line: "val xs = Seq(",
+                expected: false,
+            },
+            TestCase {
+                buffer: "def f(x):\n    return x",
+                line: "    return x",
+                expected: false,
+            },
+            TestCase {
+                buffer: "def f(x):\n    return x\n",
+                line: "",
+                expected: true,
+            },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected, is_complete(test_case.buffer, test_case.line));
+        }
+    }
+
+    #[test]
+    fn test_history() {
+        use std::fs;
+
+        let path = env::temp_dir().join(format!("livy-history-{}", ::std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut history = History::at(Some(path.clone())).unwrap();
+        history.push("def f(x):\n\n    return x");
+        history.push("f(1)");
+
+        let history = History::at(Some(path.clone())).unwrap();
+        let entries: Vec<&String> = history.editor.history().iter().collect();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(vec!["def f(x):\n\n    return x", "f(1)"], entries);
+    }
+}
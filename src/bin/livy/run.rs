@@ -0,0 +1,81 @@
+use args;
+use livy::script::{self, Report};
+use session::{self, Session};
+use signal_hook;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+pub const USAGE: &str = "\
+Usage: livy run FILE [--kind KIND | --session ID] [--keep] [--marker MARKER] [--interval MILLIS]
+
+Splits FILE into cells at lines such as `# %%` (`// %%` for Scala) and
+runs them one by one in a session, stopping at the first failing cell.
+
+Options:
+    --kind KIND         kind of the session to create; guessed from the
+                        extension of FILE (.py, .scala, .r, .sql) by default
+    --session ID        run the cells in an existing session
+    --keep              do not kill the created session on exit
+    --marker MARKER     cell marker following the comment prefix (default: %%)
+    --interval MILLIS   polling interval (default: 500)";
+
+/// Runs `livy run`.
+pub fn run(argv: &[String]) -> Result<(), String> {
+    let options = args::with_client_options(&[session::OPTIONS, &["marker"]].concat(), args::CLIENT_OPTIONS);
+    let flags = args::with_client_options(session::FLAGS, args::CLIENT_FLAGS);
+    let matches = args::parse(argv, &options, &flags)?;
+    let path = match matches.free.as_slice() {
+        [path] => Path::new(path),
+        _ => return Err(format!("exactly one FILE is required\n\n{}", USAGE)),
+    };
+    let client = args::client(&matches)?;
+
+    let source = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let kind = matches.parse("kind")?
+        .or_else(|| script::kind_of(path))
+        .ok_or("--kind is required for files whose extension is not .py, .scala, .r or .sql")?;
+    let cells = script::parse(&source, script::comment_prefix(kind), matches.value("marker").unwrap_or(script::DEFAULT_MARKER));
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&interrupted))
+        .map_err(|err| format!("{}", err))?;
+
+    let session = Session::open(&client, &matches, Some(kind), &interrupted)?;
+    let report = script::run(&client, session.id, cells, session.interval, &interrupted);
+    print_report(&report);
+
+    let report = session.close_after(&client, Ok(report))?;
+
+    if report.is_success() {
+        Ok(())
+    } else {
+        Err(format!("{} failed", path.display()))
+    }
+}
+
+fn print_report(report: &Report) {
+    for (i, result) in report.results.iter().enumerate() {
+        println!("--- cell {} (line {}){}: {} in {:.3}s",
+                 i + 1,
+                 result.cell.line,
+                 result.cell.title.as_ref().map_or_else(String::new, |title| format!(" {}", title)),
+                 if result.statement.is_success() { "ok" } else { "failed" },
+                 result.duration.as_secs_f64());
+        session::print_statement(&result.statement);
+    }
+
+    if let Some(ref error) = report.error {
+        println!("--- cell at line {}: error: {}", error.cell.line, error.error);
+    }
+
+    for cell in &report.skipped {
+        println!("--- cell at line {}: skipped", cell.line);
+    }
+
+    println!("--- {} of {} cells succeeded in {:.3}s",
+             report.results.iter().filter(|result| result.statement.is_success()).count(),
+             report.results.len() + report.error.iter().count() + report.skipped.len(),
+             report.duration().as_secs_f64());
+}
@@ -0,0 +1,117 @@
+use args::Matches;
+use livy::builder::NewSessionRequestBuilder;
+use livy::client::{Client, SessionKind, Statement};
+use livy::wait;
+use serde_json::Value;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+/// Options of commands which run code in a session
+pub const OPTIONS: &[&str] = &["kind", "session", "interval"];
+/// Flags of commands which run code in a session
+pub const FLAGS: &[&str] = &["keep"];
+
+/// Session used by a command
+pub struct Session {
+    pub id: i64,
+    pub interval: Duration,
+    /// `true` if the session was created by the command and has to be killed on exit
+    kill_on_close: bool,
+}
+
+impl Session {
+    /// Attaches to the session given by `--session`, or creates a new one of
+    /// `--kind` (or `default_kind`), and waits for it to become idle.
+    ///
+    /// Gives up waiting, and kills a created session, once `interrupted`
+    /// becomes `true`.
+    pub fn open(client: &Client, matches: &Matches, default_kind: Option<SessionKind>, interrupted: &AtomicBool) -> Result<Session, String> {
+        let interval = Duration::from_millis(matches.parse("interval")?.unwrap_or(500));
+
+        let session = match matches.parse::<i64>("session")? {
+            Some(id) => Session {
+                id,
+                interval,
+                kill_on_close: false,
+            },
+            None => {
+                let kind = matches.parse::<SessionKind>("kind")?
+                    .or(default_kind)
+                    .ok_or("--kind or --session is required")?;
+                Session {
+                    id: create(client, kind)?,
+                    interval,
+                    kill_on_close: !matches.has("keep"),
+                }
+            },
+        };
+
+        eprintln!("waiting for session {} to become idle...", session.id);
+        if let Err(err) = wait::wait_for_session_until(client, session.id, interval, interrupted) {
+            return session.close_after(client, Err(err));
+        }
+
+        Ok(session)
+    }
+
+    /// Kills the session if it was created by `open` without `--keep`.
+    pub fn close(&self, client: &Client) -> Result<(), String> {
+        if self.kill_on_close {
+            eprintln!("killing session {}", self.id);
+            client.kill_session(self.id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Closes the session once `result` has been reported, keeping the error
+    /// of `result` if closing fails too.
+    pub fn close_after<T>(&self, client: &Client, result: Result<T, String>) -> Result<T, String> {
+        match (result, self.close(client)) {
+            (Ok(value), Ok(())) => Ok(value),
+            (Ok(_), Err(err)) | (Err(err), Ok(())) => Err(err),
+            (Err(err), Err(close_err)) => Err(format!("{}; failed to kill session {}: {}", err, self.id, close_err)),
+        }
+    }
+}
+
+fn create(client: &Client, kind: SessionKind) -> Result<i64, String> {
+    let request = NewSessionRequestBuilder::new(kind).build().map_err(|err| format!("{}", err))?;
+    let session = client.create_session(request)?;
+
+    session.id.ok_or_else(|| "Livy did not return a session id".to_string())
+}
+
+/// Prints the `text/plain` output of `statement`, or its error and traceback.
+pub fn print_statement(statement: &Statement) {
+    let output = match statement.output {
+        Some(ref output) => output,
+        None => {
+            println!("statement {:?}", statement.state);
+            return;
+        },
+    };
+
+    match output.status.as_deref() {
+        Some("error") => {
+            eprintln!("{}: {}",
+                      output.ename.as_deref().unwrap_or("Error"),
+                      output.evalue.as_deref().unwrap_or(""));
+            for line in output.traceback.iter().flat_map(|traceback| traceback.iter()) {
+                eprint!("{}", line);
+            }
+        },
+        _ => {
+            if let Some(ref data) = output.data {
+                match data.get("text/plain") {
+                    Some(Value::String(text)) => println!("{}", text),
+                    _ => {
+                        for mime_type in data.keys() {
+                            println!("<{}>", mime_type);
+                        }
+                    },
+                }
+            }
+        },
+    }
+}
@@ -0,0 +1,105 @@
+use args;
+use livy::spark_submit;
+use serde_json;
+
+pub const USAGE: &str = "\
+Usage: livy submit [livy options] [spark-submit options] FILE [ARGS...]
+
+Submits a batch from a spark-submit command line, e.g.
+
+    livy submit --url http://example.com:8998 --class com.example.Main \\
+        --conf spark.ui.enabled=false app.jar 2018-01-01
+
+Livy options, which must precede the spark-submit options:
+    --strict            fail if a spark-submit option is not supported by Livy
+    --dry-run           print the batch request instead of submitting it";
+
+const FLAGS: &[&str] = &["strict", "dry-run"];
+
+/// Runs `livy submit`.
+pub fn run(argv: &[String]) -> Result<(), String> {
+    let options = args::CLIENT_OPTIONS;
+    let flags = args::with_client_options(FLAGS, args::CLIENT_FLAGS);
+    let split = split_livy_options(argv, options, &flags);
+    let matches = args::parse(&argv[..split], options, &flags)?;
+
+    let submit = spark_submit::parse(&argv[split..])?;
+
+    for option in &submit.unsupported {
+        eprintln!("warning: {} is not supported by Livy and is ignored", option);
+    }
+    if matches.has("strict") && !submit.unsupported.is_empty() {
+        return Err("unsupported options were given".to_string());
+    }
+
+    if matches.has("dry-run") {
+        println!("{}", serde_json::to_string_pretty(&submit.request).map_err(|err| format!("{}", err))?);
+        return Ok(());
+    }
+
+    let batch = args::client(&matches)?.create_batch(submit.request)?;
+
+    println!("{}", serde_json::to_string_pretty(&batch).map_err(|err| format!("{}", err))?);
+
+    Ok(())
+}
+
+/// Returns the number of leading arguments which are Livy options.
+fn split_livy_options(argv: &[String], options: &[&str], flags: &[&str]) -> usize {
+    let mut i = 0;
+
+    while i < argv.len() {
+        let name = match argv[i].strip_prefix("--") {
+            Some(name) => name,
+            None => break,
+        };
+
+        if flags.contains(&name) || options.iter().any(|option| name.starts_with(&format!("{}=", option))) {
+            i += 1;
+        } else if options.contains(&name) {
+            i += 2;
+        } else {
+            break;
+        }
+    }
+
+    i.min(argv.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_livy_options() {
+        struct TestCase {
+            argv: Vec<&'static str>,
+            expected: usize,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                argv: vec!["app.jar"],
+                expected: 0,
+            },
+            TestCase {
+                argv: vec!["--url", "http://example.com", "--strict", "--class", "Main", "app.jar"],
+                expected: 3,
+            },
+            TestCase {
+                argv: vec!["--url=http://example.com", "--master", "yarn", "--strict", "app.jar"],
+                expected: 1,
+            },
+            TestCase {
+                argv: vec!["--url"],
+                expected: 1,
+            },
+        ];
+
+        for test_case in test_cases {
+            let argv: Vec<String> = test_case.argv.iter().map(|arg| arg.to_string()).collect();
+
+            assert_eq!(test_case.expected, split_livy_options(&argv, &["url"], &["strict"]));
+        }
+    }
+}
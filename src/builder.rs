@@ -0,0 +1,534 @@
+use client::{NewBatchRequest, NewSessionRequest, SessionKind};
+use resource::MemorySize;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+
+/// Problem found in a single field of a request
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    /// Name of the field as sent to Livy, e.g. `driverMemory` or `conf`
+    pub field: String,
+    pub message: String,
+}
+
+/// Error returned by a builder listing every problem of a request
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationError {
+    pub problems: Vec<Problem>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid request")?;
+
+        for (i, problem) in self.problems.iter().enumerate() {
+            write!(f, "{} {}: {}", if i == 0 { ":" } else { ";" }, problem.field, problem.message)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl error::Error for ValidationError {}
+
+/// Collects problems while validating a request.
+struct Validator {
+    problems: Vec<Problem>,
+}
+
+impl Validator {
+    fn new() -> Validator {
+        Validator { problems: Vec::new() }
+    }
+
+    fn problem(&mut self, field: &str, message: String) {
+        self.problems.push(Problem {
+            field: field.to_string(),
+            message,
+        });
+    }
+
+    fn memory(&mut self, field: &str, value: Option<String>) -> Option<MemorySize> {
+        match value.map(|value| value.parse::<MemorySize>()) {
+            Some(Ok(ref size)) if size.bytes() == 0 => {
+                self.problem(field, "must be positive but was 0".to_string());
+                None
+            },
+            Some(Ok(size)) => Some(size),
+            Some(Err(err)) => {
+                self.problem(field, err);
+                None
+            },
+            None => None,
+        }
+    }
+
+    fn positive(&mut self, field: &str, value: Option<i64>) {
+        if let Some(value) = value {
+            if value <= 0 {
+                self.problem(field, format!("must be positive but was {}", value));
+            }
+        }
+    }
+
+    fn not_empty(&mut self, field: &str, value: &Option<String>) {
+        if let Some(ref value) = *value {
+            if value.trim().is_empty() {
+                self.problem(field, "must not be empty".to_string());
+            }
+        }
+    }
+
+    fn paths(&mut self, field: &str, paths: &Option<Vec<String>>) {
+        for (i, path) in paths.iter().flat_map(|paths| paths.iter()).enumerate() {
+            if path.trim().is_empty() {
+                self.problem(field, format!("path #{} is empty", i + 1));
+            }
+        }
+    }
+
+    fn conf(&mut self, conf: &Option<HashMap<String, String>>) {
+        let mut keys: Vec<&String> = conf.iter().flat_map(|conf| conf.keys()).collect();
+        keys.sort();
+
+        for key in keys {
+            if key.trim().is_empty() {
+                self.problem("conf", "keys must not be empty".to_string());
+            } else if key.contains(char::is_whitespace) {
+                self.problem("conf", format!("key {:?} must not contain whitespace", key));
+            }
+        }
+    }
+
+    fn finish<T>(self, request: T) -> Result<T, ValidationError> {
+        if self.problems.is_empty() {
+            Ok(request)
+        } else {
+            Err(ValidationError { problems: self.problems })
+        }
+    }
+}
+
+fn strings<I, S>(values: I) -> Vec<String> where I: IntoIterator<Item = S>, S: Into<String> {
+    values.into_iter().map(Into::into).collect()
+}
+
+/// Builder of `NewSessionRequest` which validates the request on `build`
+///
+/// # Examples
+/// ```
+/// use livy::builder::NewSessionRequestBuilder;
+/// use livy::client::SessionKind;
+/// use livy::resource::MemorySize;
+///
+/// let request = NewSessionRequestBuilder::new(SessionKind::Pyspark)
+///     .driver_memory("4g")
+///     .num_executors(10)
+///     .conf("spark.ui.enabled", "false")
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(Some(MemorySize::from_gib(4)), request.driver_memory);
+///
+/// let err = NewSessionRequestBuilder::new(SessionKind::Pyspark)
+///     .driver_memory("4 gigs")
+///     .num_executors(0)
+///     .build()
+///     .unwrap_err();
+///
+/// assert_eq!(2, err.problems.len());
+/// ```
+#[derive(Debug)]
+pub struct NewSessionRequestBuilder {
+    request: NewSessionRequest,
+    driver_memory: Option<String>,
+    executor_memory: Option<String>,
+}
+
+impl NewSessionRequestBuilder {
+    /// Constructs a new builder of a session of `kind`.
+    pub fn new(kind: SessionKind) -> NewSessionRequestBuilder {
+        NewSessionRequestBuilder {
+            request: NewSessionRequest {
+                kind,
+                proxy_user: None,
+                jars: None,
+                py_files: None,
+                files: None,
+                driver_memory: None,
+                driver_cores: None,
+                executor_memory: None,
+                executor_cores: None,
+                num_executors: None,
+                archives: None,
+                queue: None,
+                name: None,
+                conf: None,
+                heartbeat_timeout_in_second: None,
+            },
+            driver_memory: None,
+            executor_memory: None,
+        }
+    }
+
+    /// Constructs a new builder which validates `request` on `build`, e.g.
+    /// a request loaded from a file.
+    pub fn from_request(request: NewSessionRequest) -> NewSessionRequestBuilder {
+        NewSessionRequestBuilder {
+            driver_memory: request.driver_memory.map(|size| size.to_string()),
+            executor_memory: request.executor_memory.map(|size| size.to_string()),
+            request,
+        }
+    }
+
+    /// Sets the user to impersonate when running the session.
+    pub fn proxy_user<S: Into<String>>(mut self, proxy_user: S) -> NewSessionRequestBuilder {
+        self.request.proxy_user = Some(proxy_user.into());
+        self
+    }
+
+    /// Sets the jars to be used in this session.
+    pub fn jars<I, S>(mut self, jars: I) -> NewSessionRequestBuilder where I: IntoIterator<Item = S>, S: Into<String> {
+        self.request.jars = Some(strings(jars));
+        self
+    }
+
+    /// Sets the Python files to be used in this session.
+    pub fn py_files<I, S>(mut self, py_files: I) -> NewSessionRequestBuilder where I: IntoIterator<Item = S>, S: Into<String> {
+        self.request.py_files = Some(strings(py_files));
+        self
+    }
+
+    /// Sets the files to be used in this session.
+    pub fn files<I, S>(mut self, files: I) -> NewSessionRequestBuilder where I: IntoIterator<Item = S>, S: Into<String> {
+        self.request.files = Some(strings(files));
+        self
+    }
+
+    /// Sets the driver memory, either a `MemorySize` or a string such as `"4g"`.
+    pub fn driver_memory<M: ToString>(mut self, driver_memory: M) -> NewSessionRequestBuilder {
+        self.driver_memory = Some(driver_memory.to_string());
+        self
+    }
+
+    /// Sets the number of cores to use for the driver process.
+    pub fn driver_cores(mut self, driver_cores: i64) -> NewSessionRequestBuilder {
+        self.request.driver_cores = Some(driver_cores);
+        self
+    }
+
+    /// Sets the executor memory, either a `MemorySize` or a string such as `"4g"`.
+    pub fn executor_memory<M: ToString>(mut self, executor_memory: M) -> NewSessionRequestBuilder {
+        self.executor_memory = Some(executor_memory.to_string());
+        self
+    }
+
+    /// Sets the number of cores to use for each executor.
+    pub fn executor_cores(mut self, executor_cores: i64) -> NewSessionRequestBuilder {
+        self.request.executor_cores = Some(executor_cores);
+        self
+    }
+
+    /// Sets the number of executors to launch for this session.
+    pub fn num_executors(mut self, num_executors: i64) -> NewSessionRequestBuilder {
+        self.request.num_executors = Some(num_executors);
+        self
+    }
+
+    /// Sets the archives to be used in this session.
+    pub fn archives<I, S>(mut self, archives: I) -> NewSessionRequestBuilder where I: IntoIterator<Item = S>, S: Into<String> {
+        self.request.archives = Some(strings(archives));
+        self
+    }
+
+    /// Sets the name of the YARN queue to which the session is submitted.
+    pub fn queue<S: Into<String>>(mut self, queue: S) -> NewSessionRequestBuilder {
+        self.request.queue = Some(queue.into());
+        self
+    }
+
+    /// Sets the name of this session.
+    pub fn name<S: Into<String>>(mut self, name: S) -> NewSessionRequestBuilder {
+        self.request.name = Some(name.into());
+        self
+    }
+
+    /// Sets a single Spark configuration property.
+    pub fn conf<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> NewSessionRequestBuilder {
+        self.request.conf.get_or_insert_with(HashMap::new).insert(key.into(), value.into());
+        self
+    }
+
+    /// Sets the timeout in seconds after which an orphaned session is killed.
+    pub fn heartbeat_timeout_in_second(mut self, heartbeat_timeout_in_second: i64) -> NewSessionRequestBuilder {
+        self.request.heartbeat_timeout_in_second = Some(heartbeat_timeout_in_second);
+        self
+    }
+
+    /// Validates the request and returns it, or returns every problem found.
+    pub fn build(self) -> Result<NewSessionRequest, ValidationError> {
+        let mut request = self.request;
+        let mut validator = Validator::new();
+
+        validator.not_empty("proxyUser", &request.proxy_user);
+        validator.paths("jars", &request.jars);
+        validator.paths("pyFiles", &request.py_files);
+        validator.paths("files", &request.files);
+        request.driver_memory = validator.memory("driverMemory", self.driver_memory);
+        validator.positive("driverCores", request.driver_cores);
+        request.executor_memory = validator.memory("executorMemory", self.executor_memory);
+        validator.positive("executorCores", request.executor_cores);
+        validator.positive("numExecutors", request.num_executors);
+        validator.paths("archives", &request.archives);
+        validator.not_empty("queue", &request.queue);
+        validator.not_empty("name", &request.name);
+        validator.conf(&request.conf);
+        validator.positive("heartbeatTimeoutInSecond", request.heartbeat_timeout_in_second);
+
+        validator.finish(request)
+    }
+}
+
+/// Builder of `NewBatchRequest` which validates the request on `build`
+///
+/// # Examples
+/// ```
+/// use livy::builder::NewBatchRequestBuilder;
+/// use livy::resource::MemorySize;
+///
+/// let request = NewBatchRequestBuilder::new("hdfs:///jobs/app.jar")
+///     .class_name("com.example.Main")
+///     .args(vec!["2018-01-01"])
+///     .executor_memory(MemorySize::from_gib(8))
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!("hdfs:///jobs/app.jar", request.file);
+/// ```
+#[derive(Debug)]
+pub struct NewBatchRequestBuilder {
+    request: NewBatchRequest,
+    driver_memory: Option<String>,
+    executor_memory: Option<String>,
+}
+
+impl NewBatchRequestBuilder {
+    /// Constructs a new builder of a batch which runs `file`.
+    pub fn new<S: Into<String>>(file: S) -> NewBatchRequestBuilder {
+        NewBatchRequestBuilder {
+            request: NewBatchRequest {
+                file: file.into(),
+                proxy_user: None,
+                class_name: None,
+                args: None,
+                jars: None,
+                py_files: None,
+                files: None,
+                driver_memory: None,
+                driver_cores: None,
+                executor_memory: None,
+                executor_cores: None,
+                num_executors: None,
+                archives: None,
+                queue: None,
+                name: None,
+                conf: None,
+            },
+            driver_memory: None,
+            executor_memory: None,
+        }
+    }
+
+    /// Constructs a new builder which validates `request` on `build`, e.g.
+    /// a request loaded from a file.
+    pub fn from_request(request: NewBatchRequest) -> NewBatchRequestBuilder {
+        NewBatchRequestBuilder {
+            driver_memory: request.driver_memory.map(|size| size.to_string()),
+            executor_memory: request.executor_memory.map(|size| size.to_string()),
+            request,
+        }
+    }
+
+    /// Sets the user to impersonate when running the batch.
+    pub fn proxy_user<S: Into<String>>(mut self, proxy_user: S) -> NewBatchRequestBuilder {
+        self.request.proxy_user = Some(proxy_user.into());
+        self
+    }
+
+    /// Sets the application Java/Spark main class.
+    pub fn class_name<S: Into<String>>(mut self, class_name: S) -> NewBatchRequestBuilder {
+        self.request.class_name = Some(class_name.into());
+        self
+    }
+
+    /// Sets the command line arguments of the application.
+    pub fn args<I, S>(mut self, args: I) -> NewBatchRequestBuilder where I: IntoIterator<Item = S>, S: Into<String> {
+        self.request.args = Some(strings(args));
+        self
+    }
+
+    /// Sets the jars to be used in this batch.
+    pub fn jars<I, S>(mut self, jars: I) -> NewBatchRequestBuilder where I: IntoIterator<Item = S>, S: Into<String> {
+        self.request.jars = Some(strings(jars));
+        self
+    }
+
+    /// Sets the Python files to be used in this batch.
+    pub fn py_files<I, S>(mut self, py_files: I) -> NewBatchRequestBuilder where I: IntoIterator<Item = S>, S: Into<String> {
+        self.request.py_files = Some(strings(py_files));
+        self
+    }
+
+    /// Sets the files to be used in this batch.
+    pub fn files<I, S>(mut self, files: I) -> NewBatchRequestBuilder where I: IntoIterator<Item = S>, S: Into<String> {
+        self.request.files = Some(strings(files));
+        self
+    }
+
+    /// Sets the driver memory, either a `MemorySize` or a string such as `"4g"`.
+    pub fn driver_memory<M: ToString>(mut self, driver_memory: M) -> NewBatchRequestBuilder {
+        self.driver_memory = Some(driver_memory.to_string());
+        self
+    }
+
+    /// Sets the number of cores to use for the driver process.
+    pub fn driver_cores(mut self, driver_cores: i64) -> NewBatchRequestBuilder {
+        self.request.driver_cores = Some(driver_cores);
+        self
+    }
+
+    /// Sets the executor memory, either a `MemorySize` or a string such as `"4g"`.
+    pub fn executor_memory<M: ToString>(mut self, executor_memory: M) -> NewBatchRequestBuilder {
+        self.executor_memory = Some(executor_memory.to_string());
+        self
+    }
+
+    /// Sets the number of cores to use for each executor.
+    pub fn executor_cores(mut self, executor_cores: i64) -> NewBatchRequestBuilder {
+        self.request.executor_cores = Some(executor_cores);
+        self
+    }
+
+    /// Sets the number of executors to launch for this batch.
+    pub fn num_executors(mut self, num_executors: i64) -> NewBatchRequestBuilder {
+        self.request.num_executors = Some(num_executors);
+        self
+    }
+
+    /// Sets the archives to be used in this batch.
+    pub fn archives<I, S>(mut self, archives: I) -> NewBatchRequestBuilder where I: IntoIterator<Item = S>, S: Into<String> {
+        self.request.archives = Some(strings(archives));
+        self
+    }
+
+    /// Sets the name of the YARN queue to which the batch is submitted.
+    pub fn queue<S: Into<String>>(mut self, queue: S) -> NewBatchRequestBuilder {
+        self.request.queue = Some(queue.into());
+        self
+    }
+
+    /// Sets the name of this batch.
+    pub fn name<S: Into<String>>(mut self, name: S) -> NewBatchRequestBuilder {
+        self.request.name = Some(name.into());
+        self
+    }
+
+    /// Sets a single Spark configuration property.
+    pub fn conf<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> NewBatchRequestBuilder {
+        self.request.conf.get_or_insert_with(HashMap::new).insert(key.into(), value.into());
+        self
+    }
+
+    /// Validates the request and returns it, or returns every problem found.
+    pub fn build(self) -> Result<NewBatchRequest, ValidationError> {
+        let mut request = self.request;
+        let mut validator = Validator::new();
+
+        if request.file.trim().is_empty() {
+            validator.problem("file", "must not be empty".to_string());
+        }
+        validator.not_empty("proxyUser", &request.proxy_user);
+        validator.not_empty("className", &request.class_name);
+        validator.paths("jars", &request.jars);
+        validator.paths("pyFiles", &request.py_files);
+        validator.paths("files", &request.files);
+        request.driver_memory = validator.memory("driverMemory", self.driver_memory);
+        validator.positive("driverCores", request.driver_cores);
+        request.executor_memory = validator.memory("executorMemory", self.executor_memory);
+        validator.positive("executorCores", request.executor_cores);
+        validator.positive("numExecutors", request.num_executors);
+        validator.paths("archives", &request.archives);
+        validator.not_empty("queue", &request.queue);
+        validator.not_empty("name", &request.name);
+        validator.conf(&request.conf);
+
+        validator.finish(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problem(field: &str, message: &str) -> Problem {
+        Problem {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_new_session_request_builder() {
+        let err = NewSessionRequestBuilder::new(SessionKind::Spark)
+            .jars(vec!["a.jar", " "])
+            .driver_memory("0g")
+            .executor_memory("lots")
+            .executor_cores(0)
+            .num_executors(-1)
+            .queue("")
+            .conf("spark.ui enabled", "false")
+            .conf("", "x")
+            .heartbeat_timeout_in_second(0)
+            .build()
+            .unwrap_err();
+
+        assert_eq!(vec![
+            problem("jars", "path #2 is empty"),
+            problem("driverMemory", "must be positive but was 0"),
+            problem("executorMemory", "invalid memory size: \"lots\"; expected a size such as \"512m\" or \"4g\""),
+            problem("executorCores", "must be positive but was 0"),
+            problem("numExecutors", "must be positive but was -1"),
+            problem("queue", "must not be empty"),
+            problem("conf", "keys must not be empty"),
+            problem("conf", "key \"spark.ui enabled\" must not contain whitespace"),
+            problem("heartbeatTimeoutInSecond", "must be positive but was 0"),
+        ], err.problems);
+    }
+
+    #[test]
+    fn test_new_batch_request_builder() {
+        let err = NewBatchRequestBuilder::new("")
+            .py_files(vec![""])
+            .driver_cores(0)
+            .build()
+            .unwrap_err();
+
+        assert_eq!(vec![
+            problem("file", "must not be empty"),
+            problem("pyFiles", "path #1 is empty"),
+            problem("driverCores", "must be positive but was 0"),
+        ], err.problems);
+        assert_eq!("invalid request: file: must not be empty; pyFiles: path #1 is empty; driverCores: must be positive but was 0",
+                   format!("{}", err));
+
+        let request = NewBatchRequestBuilder::new("app.py")
+            .args(vec!["--date", "2018-01-01"])
+            .num_executors(2)
+            .build()
+            .unwrap();
+
+        assert_eq!(Some(vec!["--date".to_string(), "2018-01-01".to_string()]), request.args);
+        assert_eq!(Some(2), request.num_executors);
+    }
+}
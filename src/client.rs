@@ -1,15 +1,50 @@
+use auth::Authentication;
+use cookie::CookieJar;
 use http;
 use http::Method;
 use http::Method::*;
+use http::{Detailed, Query};
+use http::Transport;
+use journal::Journal;
+use resource::MemorySize;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
-use std::collections::HashMap;
+use serde_json::{self, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Callback which returns headers to send with a single request, given
+/// its method and its path relative to the URL of the client
+pub type HeaderHook = Arc<dyn Fn(Method, &str) -> Vec<(String, String)> + Send + Sync>;
+
+/// Default number of sessions or batches requested per page by
+/// `get_all_sessions` and `get_all_batches` callers
+pub const DEFAULT_PAGE_SIZE: i64 = 100;
 
 /// Apache Livy REST API client
+///
+/// Cloning a client is cheap and the clone shares the transport, e.g.
+/// its cookies, the proxy users of created sessions and batches and the
+/// journal with the original.
+#[derive(Clone)]
 pub struct Client {
     url: String,
-    gssnegotiate: Option<bool>,
-    username: Option<String>,
+    authentication: Authentication,
+    headers: Vec<(String, String)>,
+    header_hook: Option<HeaderHook>,
+    transport: Arc<dyn Transport>,
+    proxy_user: Option<String>,
+    owners: Arc<Mutex<Owners>>,
+    journal: Option<Arc<Journal>>,
+}
+
+/// Proxy users of the sessions and batches created through a client
+#[derive(Debug, Default)]
+struct Owners {
+    sessions: HashMap<i64, String>,
+    batches: HashMap<i64, String>,
 }
 
 impl Client {
@@ -30,33 +65,227 @@ impl Client {
     pub fn new(url: &str, gssnegotiate: Option<bool>, username: Option<String>) -> Client {
         Client {
             url: http::remove_trailing_slash(url),
-            gssnegotiate,
-            username,
+            authentication: Authentication::from_options(gssnegotiate, username),
+            headers: Vec::new(),
+            header_hook: None,
+            transport: Arc::new(http::Curl),
+            proxy_user: None,
+            owners: Arc::new(Mutex::new(Owners::default())),
+            journal: None,
         }
     }
 
-    /// Sends an HTTP request and returns the result.
-    fn send<T: DeserializeOwned, U: Serialize>(&self, method: Method, path: &str, data: Option<U>) -> Result<T, String> {
-        http::send(method,
-                   format!("{}{}", self.url, path).as_str(),
-                   data,
-                   self.gssnegotiate.as_ref(),
-                   self.username.as_ref().map(String::as_ref))
+    /// Constructs a new `Client` of Livy behind an Apache Knox gateway.
+    ///
+    /// Requests are sent to `{gateway}/gateway/{topology}/livy/v1` and
+    /// reuse the session cookie issued by Knox, so that credentials are
+    /// sent only when Knox asks for them.
+    ///
+    /// # Examples
+    /// ```
+    /// use livy::auth::Authentication;
+    /// use livy::client::Client;
+    ///
+    /// let client = Client::knox("https://knox.example.com:8443", "default", Authentication::basic("alice", "s3cret"));
+    /// ```
+    pub fn knox(gateway: &str, topology: &str, authentication: Authentication) -> Client {
+        let jar = CookieJar::new(Arc::new(http::Curl)).reuse_session();
+
+        Client::new(gateway, None, None)
+            .with_base_path(&format!("gateway/{}/livy/v1", topology))
+            .with_authentication(authentication)
+            .with_transport(Arc::new(jar))
     }
 
-    /// Sends an HTTP GET request and returns the result.
-    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, String> {
-        self.send(GET, path, None::<()>)
+    /// Appends a path prefix to the URL of the server, e.g. for Livy
+    /// behind a gateway or a reverse proxy.
+    ///
+    /// # Examples
+    /// ```
+    /// use livy::client::Client;
+    ///
+    /// let client = Client::new("https://proxy.example.com/", None, None).with_base_path("/livy/");
+    ///
+    /// assert_eq!("https://proxy.example.com/livy", client.url());
+    /// ```
+    pub fn with_base_path(mut self, path: &str) -> Client {
+        let path = path.trim_matches('/');
+
+        if !path.is_empty() {
+            self.url = format!("{}/{}", self.url, path);
+        }
+        self
     }
 
-    /// Sends an HTTP POST request and returns the result.
-    fn post<T: DeserializeOwned, U: Serialize>(&self, path: &str, data: Option<U>) -> Result<T, String> {
-        self.send(POST, path, data)
+    /// Returns the URL which request paths are appended to.
+    pub fn url(&self) -> &str {
+        &self.url
     }
 
-    /// Sends an HTTP DELETE request and returns the result.
-    fn delete<T: DeserializeOwned>(&self, path: &str) -> Result<T, String> {
-        self.send(DELETE, path, None::<()>)
+    /// Replaces the authentication of requests, e.g. with HTTP Basic
+    /// authentication or a bearer token.
+    pub fn with_authentication(mut self, authentication: Authentication) -> Client {
+        self.authentication = authentication;
+        self
+    }
+
+    /// Sets the value of the `X-Requested-By` header, which Livy requires
+    /// if its CSRF protection is enabled. The default value is `x`.
+    pub fn with_requested_by(self, value: &str) -> Client {
+        self.with_header("X-Requested-By", value)
+    }
+
+    /// Sets a header sent with every request, replacing a header of the
+    /// same name.
+    ///
+    /// Headers which differ between requests are set by `with_header_hook`.
+    ///
+    /// # Examples
+    /// ```
+    /// use livy::client::Client;
+    ///
+    /// let client = Client::new("http://example.com:8998", None, None)
+    ///     .with_requested_by("etl-service")
+    ///     .with_header("X-Tenant", "analytics");
+    /// ```
+    pub fn with_header(mut self, name: &str, value: &str) -> Client {
+        self.headers.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets a callback which is called before each request and returns
+    /// headers to send with that request only. They replace the headers
+    /// of the same names set by `with_header`.
+    ///
+    /// # Examples
+    /// ```
+    /// use livy::client::Client;
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// let counter = AtomicUsize::new(0);
+    /// let client = Client::new("http://example.com:8998", None, None)
+    ///     .with_header_hook(Arc::new(move |_, _| {
+    ///         vec![("X-Request-Id".to_string(), counter.fetch_add(1, Ordering::SeqCst).to_string())]
+    ///     }));
+    /// ```
+    pub fn with_header_hook(mut self, hook: HeaderHook) -> Client {
+        self.header_hook = Some(hook);
+        self
+    }
+
+    /// Derives a client which creates sessions and batches on behalf of
+    /// `user`, i.e. sets `proxy_user` of their requests to `user`.
+    ///
+    /// The derived client shares the URL, authentication, headers and
+    /// transport of the original, as well as the record of which user
+    /// each created session and batch belongs to.
+    ///
+    /// # Examples
+    /// ```
+    /// use livy::client::Client;
+    ///
+    /// let client = Client::new("http://example.com:8998", None, None);
+    /// let alice = client.as_user("alice");
+    ///
+    /// assert_eq!(Some("alice"), alice.proxy_user());
+    /// assert_eq!(None, client.proxy_user());
+    /// ```
+    pub fn as_user(&self, user: &str) -> Client {
+        let mut client = self.clone();
+        client.proxy_user = Some(user.to_string());
+        client
+    }
+
+    /// Returns the user whom the client impersonates.
+    pub fn proxy_user(&self) -> Option<&str> {
+        self.proxy_user.as_deref()
+    }
+
+    /// Returns the proxy user of a session created through this client
+    /// or a client sharing its record, unless the session has been killed.
+    pub fn session_user(&self, session_id: i64) -> Option<String> {
+        self.owners().sessions.get(&session_id).cloned()
+    }
+
+    /// Returns the proxy user of a batch created through this client
+    /// or a client sharing its record, unless the batch has been killed.
+    pub fn batch_user(&self, batch_id: i64) -> Option<String> {
+        self.owners().batches.get(&batch_id).cloned()
+    }
+
+    fn owners(&self) -> MutexGuard<'_, Owners> {
+        self.owners.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Sets `proxy_user` of a creation request to the impersonated user.
+    fn impersonate(&self, proxy_user: &mut Option<String>) -> Result<(), String> {
+        match (self.proxy_user.as_ref(), proxy_user.as_ref()) {
+            (Some(user), Some(requested)) if user != requested => {
+                return Err(format!("the request has proxy user {} but the client impersonates {}", requested, user));
+            },
+            (Some(user), None) => *proxy_user = Some(user.clone()),
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the transport which sends requests, e.g. with a
+    /// `fixture::Recorder` or a `fixture::Replayer`.
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Client {
+        self.transport = transport;
+        self
+    }
+
+    /// Records the sessions and batches created and killed through the
+    /// client in `journal`. Records which cannot be written do not fail
+    /// the call; they are reported by `Journal::take_failures`.
+    pub fn with_journal(mut self, journal: Arc<Journal>) -> Client {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Records that a session or batch was created, if the client has a
+    /// journal. A failure is kept in the journal rather than returned, as
+    /// the session or batch exists either way.
+    fn journal_open<T: Serialize>(&self, target: Target, request: &T) {
+        if let Some(ref journal) = self.journal {
+            let recorded = serde_json::to_value(request)
+                .map_err(|err| format!("{}", err))
+                .and_then(|request| journal.record_open(&self.url, target, &request));
+            if let Err(err) = recorded {
+                journal.fail(format!("{} was created but not journaled: {}", target, err));
+            }
+        }
+    }
+
+    /// Records that a session or batch was killed, if the client has a
+    /// journal. A failure is kept in the journal rather than returned.
+    fn journal_close(&self, target: Target) {
+        if let Some(ref journal) = self.journal {
+            if let Err(err) = journal.record_close(&self.url, target) {
+                journal.fail(format!("{} was killed but not journaled: {}", target, err));
+            }
+        }
+    }
+
+    /// Returns a view of the client whose methods return the metadata
+    /// of responses, e.g. their status and headers, next to their bodies.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use livy::builder::NewBatchRequestBuilder;
+    /// use livy::client::Client;
+    ///
+    /// let client = Client::new("http://example.com:8998", None, None);
+    /// let batch = client.detailed().create_batch(NewBatchRequestBuilder::new("app.jar").build().unwrap()).unwrap();
+    ///
+    /// println!("{} {:?} in {:?}", batch.status, batch.header("Location"), batch.elapsed);
+    /// ```
+    pub fn detailed(&self) -> DetailedClient<'_> {
+        DetailedClient { client: self }
     }
 
     /// Gets information of sessions and returns it.
@@ -64,12 +293,22 @@ impl Client {
     /// # HTTP Request
     /// GET /sessions
     pub fn get_sessions(&self, from: Option<i64>, size: Option<i64>) -> Result<Sessions, String> {
-        let params = http::params(vec![
-            http::param("from", from),
-            http::param("size", size)
-        ]);
+        self.detailed().get_sessions(from, size).map(Detailed::into_body)
+    }
 
-        self.get(format!("/sessions{}", params).as_str())
+    /// Gets information of all sessions, requesting pages of `page_size`
+    /// sessions until `total` sessions are received.
+    ///
+    /// A session which appears on two pages because sessions were created
+    /// or deleted in between is returned once.
+    ///
+    /// # HTTP Request
+    /// GET /sessions
+    pub fn get_all_sessions(&self, page_size: i64) -> Result<Vec<Session>, String> {
+        paginate(page_size, |from| {
+            let page = self.get_sessions(Some(from), Some(page_size))?;
+            Ok((page.total, page.sessions.unwrap_or_default()))
+        }, |session| session.id)
     }
 
     /// Creates a new session.
@@ -77,7 +316,7 @@ impl Client {
     /// # HTTP Request
     /// POST /sessions
     pub fn create_session(&self, new_session_request: NewSessionRequest) -> Result<Session, String> {
-        self.post("/sessions", Some(new_session_request))
+        self.detailed().create_session(new_session_request).map(Detailed::into_body)
     }
 
     /// Gets information of a single session and returns it.
@@ -85,7 +324,7 @@ impl Client {
     /// # HTTP Request
     /// GET /sessions/{sessionId}
     pub fn get_session(&self, session_id: i64) -> Result<Session, String> {
-        self.get(format!("/sessions/{}", session_id).as_str())
+        self.detailed().get_session(session_id).map(Detailed::into_body)
     }
 
     /// Gets session state information of a single session and returns it.
@@ -93,7 +332,7 @@ impl Client {
     /// # HTTP Request
     /// GET /sessions/{sessionId}/state
     pub fn get_session_state(&self, session_id: i64) -> Result<SessionStateOnly, String> {
-        self.get(format!("/sessions/{}/state", session_id).as_str())
+        self.detailed().get_session_state(session_id).map(Detailed::into_body)
     }
 
     /// Kills the session whose id is equal to `session_id`.
@@ -101,20 +340,15 @@ impl Client {
     /// # HTTP Request
     /// DELETE /sessions/{sessionId}
     pub fn kill_session(&self, session_id: i64) -> Result<SessionKillResult, String> {
-        self.delete(format!("/sessions/{}", session_id).as_str())
+        self.detailed().kill_session(session_id).map(Detailed::into_body)
     }
 
     /// Gets the log lines of a single session and returns them.
     ///
     /// # HTTP Request
     /// GET /sessions/{sessionId}/log
-    pub fn get_session_log(&self, session_id: i64, from: Option<i64>, size: Option<i64>)-> Result<SessionLog, String> {
-        let params = http::params(vec![
-            http::param("from", from),
-            http::param("size", size)
-        ]);
-
-        self.get(format!("/sessions/{}/log{}", session_id, params).as_str())
+    pub fn get_session_log(&self, session_id: i64, from: Option<i64>, size: Option<i64>) -> Result<SessionLog, String> {
+        self.detailed().get_session_log(session_id, from, size).map(Detailed::into_body)
     }
 
     /// Gets the statements of a single session and returns them.
@@ -122,7 +356,7 @@ impl Client {
     /// # HTTP Request
     /// GET /sessions/{sessionId}/statements
     pub fn get_statements(&self, session_id: i64) -> Result<Statements, String> {
-        self.get(format!("/sessions/{}/statements", session_id).as_str())
+        self.detailed().get_statements(session_id).map(Detailed::into_body)
     }
 
     /// Runs a statement in a session.
@@ -130,7 +364,7 @@ impl Client {
     /// # HTTP Request
     /// POST /sessions/{sessionId}/statements
     pub fn run_statement(&self, session_id: i64, run_statement_request: RunStatementRequest) -> Result<Statement, String> {
-        self.post(format!("/sessions/{}/statements", session_id).as_str(), Some(run_statement_request))
+        self.detailed().run_statement(session_id, run_statement_request).map(Detailed::into_body)
     }
 
     /// Gets a single statement of a single session and returns it.
@@ -138,7 +372,7 @@ impl Client {
     /// # HTTP Request
     /// GET /sessions/{sessionId}/statements/{statementId}
     pub fn get_statement(&self, session_id: i64, statement_id: i64) -> Result<Statement, String> {
-        self.get(format!("/sessions/{}/statements/{}", session_id, statement_id).as_str())
+        self.detailed().get_statement(session_id, statement_id).map(Detailed::into_body)
     }
 
     /// Cancel a single statement.
@@ -146,7 +380,7 @@ impl Client {
     /// # HTTP Request
     /// POST /sessions/{sessionId}/statements/{statementId}/cancel
     pub fn cancel_statement(&self, session_id: i64, statement_id: i64) -> Result<StatementCancelResult, String> {
-        self.post(format!("/sessions/{}/statements/{}/cancel", session_id, statement_id).as_str(), None::<()>)
+        self.detailed().cancel_statement(session_id, statement_id).map(Detailed::into_body)
     }
 
     /// Gets information of batches and returns it.
@@ -154,12 +388,19 @@ impl Client {
     /// # HTTP Request
     /// GET /batches
     pub fn get_batches(&self, from: Option<i64>, size: Option<i64>) -> Result<Batches, String> {
-        let params = http::params(vec![
-            http::param("from", from),
-            http::param("size", size)
-        ]);
+        self.detailed().get_batches(from, size).map(Detailed::into_body)
+    }
 
-        self.get(format!("/batches{}", params).as_str())
+    /// Gets information of all batches, requesting pages of `page_size`
+    /// batches until `total` batches are received.
+    ///
+    /// # HTTP Request
+    /// GET /batches
+    pub fn get_all_batches(&self, page_size: i64) -> Result<Vec<Batch>, String> {
+        paginate(page_size, |from| {
+            let page = self.get_batches(Some(from), Some(page_size))?;
+            Ok((page.total, page.sessions.unwrap_or_default()))
+        }, |batch| batch.id)
     }
 
     /// Creates a new batch.
@@ -167,7 +408,7 @@ impl Client {
     /// # HTTP Request
     /// POST /batches
     pub fn create_batch(&self, new_batch_request: NewBatchRequest) -> Result<Batch, String> {
-        self.post("/batches", Some(new_batch_request))
+        self.detailed().create_batch(new_batch_request).map(Detailed::into_body)
     }
 
     /// Gets a batch and returns it.
@@ -175,7 +416,7 @@ impl Client {
     /// # HTTP Request
     /// GET /batches/{batchId}
     pub fn get_batch(&self, batch_id: i64) -> Result<Batch, String> {
-        self.get(format!("/batches/{}", batch_id).as_str())
+        self.detailed().get_batch(batch_id).map(Detailed::into_body)
     }
 
     /// Gets the state of batch session.
@@ -183,7 +424,7 @@ impl Client {
     /// # HTTP Request
     /// GET /batches/{batchId}/state
     pub fn get_batch_state(&self, batch_id: i64) -> Result<BatchStateOnly, String> {
-        self.get(format!("/batches/{}/state", batch_id).as_str())
+        self.detailed().get_batch_state(batch_id).map(Detailed::into_body)
     }
 
     /// Kills the batch job.
@@ -191,7 +432,7 @@ impl Client {
     /// # HTTP Request
     /// DELETE /batches/{batchId}
     pub fn kill_batch(&self, batch_id: i64) -> Result<BatchKillResult, String> {
-        self.delete(format!("/batches/{}", batch_id).as_str())
+        self.detailed().kill_batch(batch_id).map(Detailed::into_body)
     }
 
     /// Gets the log lines from a batch and returns them.
@@ -199,12 +440,232 @@ impl Client {
     /// # HTTP Request
     /// GET /batches/{batchId}/log
     pub fn get_batch_log(&self, batch_id: i64, from: Option<i64>, size: Option<i64>) -> Result<BatchLog, String> {
-        let params = http::params(vec![
-            http::param("from", from),
-            http::param("size", size)
-        ]);
+        self.detailed().get_batch_log(batch_id, from, size).map(Detailed::into_body)
+    }
+}
+
+/// Collects the items of pages returned by `get_page`, which is called with
+/// the index of the first item of each page, skipping items whose ids were
+/// already seen.
+fn paginate<T, F, I>(page_size: i64, get_page: F, id: I) -> Result<Vec<T>, String>
+    where F: Fn(i64) -> Result<(Option<i64>, Vec<T>), String>, I: Fn(&T) -> Option<i64> {
+    if page_size <= 0 {
+        return Err(format!("invalid page size: {}", page_size));
+    }
+
+    let mut items = Vec::new();
+    let mut ids = HashSet::new();
+    let mut from = 0;
+
+    loop {
+        let (total, page) = get_page(from)?;
+        let received = page.len() as i64;
+        from += received;
 
-        self.get(format!("/batches/{}/log{}", batch_id, params).as_str())
+        items.extend(page.into_iter().filter(|item| id(item).is_none_or(|id| ids.insert(id))));
+
+        if received == 0 || total.is_none_or(|total| from >= total) {
+            return Ok(items);
+        }
+    }
+}
+
+/// View of a `Client` whose methods return the metadata of responses
+/// next to their bodies
+pub struct DetailedClient<'a> {
+    client: &'a Client,
+}
+
+impl<'a> DetailedClient<'a> {
+    /// Sends an HTTP request and returns the result.
+    fn send<T: DeserializeOwned, U: Serialize>(&self, method: Method, path: &str, data: Option<U>) -> Result<Detailed<T>, String> {
+        let mut headers = self.client.headers.clone();
+        if let Some(ref hook) = self.client.header_hook {
+            for (name, value) in hook(method, path) {
+                headers.retain(|(key, _)| !key.eq_ignore_ascii_case(&name));
+                headers.push((name, value));
+            }
+        }
+
+        http::send_detailed_with(&*self.client.transport,
+                                 method,
+                                 format!("{}{}", self.client.url, path).as_str(),
+                                 data,
+                                 &self.client.authentication,
+                                 &headers)
+    }
+
+    /// Sends an HTTP GET request and returns the result.
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<Detailed<T>, String> {
+        self.send(GET, path, None::<()>)
+    }
+
+    /// Sends an HTTP POST request and returns the result.
+    fn post<T: DeserializeOwned, U: Serialize>(&self, path: &str, data: Option<U>) -> Result<Detailed<T>, String> {
+        self.send(POST, path, data)
+    }
+
+    /// Sends an HTTP DELETE request and returns the result.
+    fn delete<T: DeserializeOwned>(&self, path: &str) -> Result<Detailed<T>, String> {
+        self.send(DELETE, path, None::<()>)
+    }
+
+    /// Same as `Client::get_sessions`, with the metadata of the response.
+    pub fn get_sessions(&self, from: Option<i64>, size: Option<i64>) -> Result<Detailed<Sessions>, String> {
+        instrument!("get_sessions");
+
+        let query = Query::new()
+            .param("from", from)
+            .param("size", size);
+
+        self.get(format!("/sessions{}", query).as_str())
+    }
+
+    /// Same as `Client::create_session`, with the metadata of the response.
+    pub fn create_session(&self, mut new_session_request: NewSessionRequest) -> Result<Detailed<Session>, String> {
+        self.client.impersonate(&mut new_session_request.proxy_user)?;
+        instrument!("create_session", proxy_user = ?new_session_request.proxy_user);
+        let requested = new_session_request.proxy_user.clone();
+
+        let session: Detailed<Session> = self.post("/sessions", Some(&new_session_request))?;
+
+        if let (Some(id), Some(user)) = (session.body.id, session.body.proxy_user.clone().or(requested)) {
+            self.client.owners().sessions.insert(id, user);
+        }
+        if let Some(id) = session.body.id {
+            self.client.journal_open(Target::Session(id), &new_session_request);
+        }
+
+        Ok(session)
+    }
+
+    /// Same as `Client::get_session`, with the metadata of the response.
+    pub fn get_session(&self, session_id: i64) -> Result<Detailed<Session>, String> {
+        instrument!("get_session", session_id);
+
+        self.get(format!("/sessions/{}", session_id).as_str())
+    }
+
+    /// Same as `Client::get_session_state`, with the metadata of the response.
+    pub fn get_session_state(&self, session_id: i64) -> Result<Detailed<SessionStateOnly>, String> {
+        instrument!("get_session_state", session_id);
+
+        self.get(format!("/sessions/{}/state", session_id).as_str())
+    }
+
+    /// Same as `Client::kill_session`, with the metadata of the response.
+    pub fn kill_session(&self, session_id: i64) -> Result<Detailed<SessionKillResult>, String> {
+        instrument!("kill_session", session_id);
+
+        let result = self.delete(format!("/sessions/{}", session_id).as_str())?;
+        self.client.owners().sessions.remove(&session_id);
+        self.client.journal_close(Target::Session(session_id));
+
+        Ok(result)
+    }
+
+    /// Same as `Client::get_session_log`, with the metadata of the response.
+    pub fn get_session_log(&self, session_id: i64, from: Option<i64>, size: Option<i64>)-> Result<Detailed<SessionLog>, String> {
+        instrument!("get_session_log", session_id);
+
+        let query = Query::new()
+            .param("from", from)
+            .param("size", size);
+
+        self.get(format!("/sessions/{}/log{}", session_id, query).as_str())
+    }
+
+    /// Same as `Client::get_statements`, with the metadata of the response.
+    pub fn get_statements(&self, session_id: i64) -> Result<Detailed<Statements>, String> {
+        instrument!("get_statements", session_id);
+
+        self.get(format!("/sessions/{}/statements", session_id).as_str())
+    }
+
+    /// Same as `Client::run_statement`, with the metadata of the response.
+    pub fn run_statement(&self, session_id: i64, run_statement_request: RunStatementRequest) -> Result<Detailed<Statement>, String> {
+        instrument!("run_statement", session_id);
+
+        self.post(format!("/sessions/{}/statements", session_id).as_str(), Some(run_statement_request))
+    }
+
+    /// Same as `Client::get_statement`, with the metadata of the response.
+    pub fn get_statement(&self, session_id: i64, statement_id: i64) -> Result<Detailed<Statement>, String> {
+        instrument!("get_statement", session_id, statement_id);
+
+        self.get(format!("/sessions/{}/statements/{}", session_id, statement_id).as_str())
+    }
+
+    /// Same as `Client::cancel_statement`, with the metadata of the response.
+    pub fn cancel_statement(&self, session_id: i64, statement_id: i64) -> Result<Detailed<StatementCancelResult>, String> {
+        instrument!("cancel_statement", session_id, statement_id);
+
+        self.post(format!("/sessions/{}/statements/{}/cancel", session_id, statement_id).as_str(), None::<()>)
+    }
+
+    /// Same as `Client::get_batches`, with the metadata of the response.
+    pub fn get_batches(&self, from: Option<i64>, size: Option<i64>) -> Result<Detailed<Batches>, String> {
+        instrument!("get_batches");
+
+        let query = Query::new()
+            .param("from", from)
+            .param("size", size);
+
+        self.get(format!("/batches{}", query).as_str())
+    }
+
+    /// Same as `Client::create_batch`, with the metadata of the response.
+    pub fn create_batch(&self, mut new_batch_request: NewBatchRequest) -> Result<Detailed<Batch>, String> {
+        self.client.impersonate(&mut new_batch_request.proxy_user)?;
+        instrument!("create_batch", proxy_user = ?new_batch_request.proxy_user);
+        let requested = new_batch_request.proxy_user.clone();
+
+        let batch: Detailed<Batch> = self.post("/batches", Some(&new_batch_request))?;
+
+        if let (Some(id), Some(user)) = (batch.body.id, batch.body.proxy_user.clone().or(requested)) {
+            self.client.owners().batches.insert(id, user);
+        }
+        if let Some(id) = batch.body.id {
+            self.client.journal_open(Target::Batch(id), &new_batch_request);
+        }
+
+        Ok(batch)
+    }
+
+    /// Same as `Client::get_batch`, with the metadata of the response.
+    pub fn get_batch(&self, batch_id: i64) -> Result<Detailed<Batch>, String> {
+        instrument!("get_batch", batch_id);
+
+        self.get(format!("/batches/{}", batch_id).as_str())
+    }
+
+    /// Same as `Client::get_batch_state`, with the metadata of the response.
+    pub fn get_batch_state(&self, batch_id: i64) -> Result<Detailed<BatchStateOnly>, String> {
+        instrument!("get_batch_state", batch_id);
+
+        self.get(format!("/batches/{}/state", batch_id).as_str())
+    }
+
+    /// Same as `Client::kill_batch`, with the metadata of the response.
+    pub fn kill_batch(&self, batch_id: i64) -> Result<Detailed<BatchKillResult>, String> {
+        instrument!("kill_batch", batch_id);
+
+        let result = self.delete(format!("/batches/{}", batch_id).as_str())?;
+        self.client.owners().batches.remove(&batch_id);
+        self.client.journal_close(Target::Batch(batch_id));
+
+        Ok(result)
+    }
+
+    /// Same as `Client::get_batch_log`, with the metadata of the response.
+    pub fn get_batch_log(&self, batch_id: i64, from: Option<i64>, size: Option<i64>) -> Result<Detailed<BatchLog>, String> {
+        instrument!("get_batch_log", batch_id);
+
+        let query = Query::new()
+            .param("from", from)
+            .param("size", size);
+
+        self.get(format!("/batches/{}/log{}", batch_id, query).as_str())
     }
 }
 
@@ -231,11 +692,11 @@ pub struct NewSessionRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub files: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub driver_memory: Option<String>,
+    pub driver_memory: Option<MemorySize>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub driver_cores: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub executor_memory: Option<String>,
+    pub executor_memory: Option<MemorySize>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub executor_cores: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -257,6 +718,8 @@ pub struct NewSessionRequest {
 #[serde(rename_all = "camelCase")]
 pub struct Session {
     pub id: Option<i64>,
+    /// Name of the session, available since Livy 0.7
+    pub name: Option<String>,
     pub app_id: Option<String>,
     pub owner: Option<String>,
     pub proxy_user: Option<String>,
@@ -307,8 +770,24 @@ pub struct RunStatementRequest {
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
 pub struct Statement {
     pub id: Option<i64>,
+    /// Code of the statement, available since Livy 0.4
+    pub code: Option<String>,
     pub state: Option<StatementState>,
     pub output: Option<StatementOutput>,
+    /// Progress between 0 and 1, available since Livy 0.5
+    pub progress: Option<f64>,
+    /// Start time in milliseconds since the epoch, available since Livy 0.7
+    pub started: Option<i64>,
+    /// Completion time in milliseconds since the epoch, available since Livy 0.7
+    pub completed: Option<i64>,
+}
+
+impl Statement {
+    /// Returns `true` if the statement is available and its output status is `ok`.
+    pub fn is_success(&self) -> bool {
+        self.state == Some(StatementState::Available)
+            && self.output.as_ref().and_then(|output| output.status.as_deref()) == Some("ok")
+    }
 }
 
 /// Statement output
@@ -316,7 +795,11 @@ pub struct Statement {
 pub struct StatementOutput {
     pub status: Option<String>,
     pub execution_count: Option<i64>,
-    pub data: Option<HashMap<String, Option<String>>>,
+    /// Output keyed by MIME type, e.g. `text/plain`, `application/json` or `image/png`
+    pub data: Option<HashMap<String, Value>>,
+    pub ename: Option<String>,
+    pub evalue: Option<String>,
+    pub traceback: Option<Vec<String>>,
 }
 
 /// Statement cancel result
@@ -338,6 +821,11 @@ pub struct Batches {
 #[serde(rename_all = "camelCase")]
 pub struct Batch {
     pub id: Option<i64>,
+    /// Name of the batch, available since Livy 0.7
+    pub name: Option<String>,
+    /// Owner and proxy user of the batch, available since Livy 0.6
+    pub owner: Option<String>,
+    pub proxy_user: Option<String>,
     pub app_id: Option<String>,
     pub app_info: Option<HashMap<String, Option<String>>>,
     pub log: Option<Vec<String>>,
@@ -345,7 +833,7 @@ pub struct Batch {
 }
 
 /// New batch request information
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct NewBatchRequest {
     pub file: String,
@@ -362,11 +850,11 @@ pub struct NewBatchRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub files: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub driver_memory: Option<String>,
+    pub driver_memory: Option<MemorySize>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub driver_cores: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub executor_memory: Option<String>,
+    pub executor_memory: Option<MemorySize>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub executor_cores: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -404,31 +892,109 @@ pub struct BatchLog {
 }
 
 /// Session state
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
-#[serde(rename_all = "camelCase")]
+///
+/// States are (de)serialized in snake case as Livy reports them, e.g.
+/// `not_started`. Before version 0.5.0 of this crate they were in camel
+/// case, so `NotStarted` and `ShuttingDown` never matched a response.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SessionState {
     NotStarted,
     Starting,
+    /// Session being recovered after a restart of the server, since Livy 0.5
+    Recovering,
     Idle,
     Busy,
     ShuttingDown,
     Error,
     Dead,
+    /// Session killed by a user, since Livy 0.5
+    Killed,
     Success,
 }
 
+impl SessionState {
+    /// Returns `true` if the session can no longer run statements.
+    pub fn is_finished(&self) -> bool {
+        matches!(*self, SessionState::ShuttingDown | SessionState::Error | SessionState::Dead | SessionState::Killed | SessionState::Success)
+    }
+}
+
+impl Display for SessionState {
+    /// Writes the name of the state as Livy reports it, e.g. `not_started`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            SessionState::NotStarted => "not_started",
+            SessionState::Starting => "starting",
+            SessionState::Recovering => "recovering",
+            SessionState::Idle => "idle",
+            SessionState::Busy => "busy",
+            SessionState::ShuttingDown => "shutting_down",
+            SessionState::Error => "error",
+            SessionState::Dead => "dead",
+            SessionState::Killed => "killed",
+            SessionState::Success => "success",
+        })
+    }
+}
+
+/// Session or batch
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Target {
+    Session(i64),
+    Batch(i64),
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Target::Session(id) => write!(f, "session {}", id),
+            Target::Batch(id) => write!(f, "batch {}", id),
+        }
+    }
+}
+
 /// Session kind
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub enum SessionKind {
     Spark,
     Pyspark,
     Pyspark3,
     Sparkr,
+    /// Spark SQL, since Livy 0.5
+    Sql,
+    /// Session whose statements each specify their kind, since Livy 0.5
+    Shared,
+}
+
+impl FromStr for SessionKind {
+    type Err = String;
+
+    /// Parses a session kind from its Livy name, e.g. `"pyspark"`.
+    ///
+    /// # Examples
+    /// ```
+    /// use livy::client::SessionKind;
+    ///
+    /// assert_eq!(Ok(SessionKind::Pyspark), "pyspark".parse());
+    /// assert!("python".parse::<SessionKind>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<SessionKind, String> {
+        match s {
+            "spark" => Ok(SessionKind::Spark),
+            "pyspark" => Ok(SessionKind::Pyspark),
+            "pyspark3" => Ok(SessionKind::Pyspark3),
+            "sparkr" => Ok(SessionKind::Sparkr),
+            "sql" => Ok(SessionKind::Sql),
+            "shared" => Ok(SessionKind::Shared),
+            _ => Err(format!("invalid session kind: {}", s)),
+        }
+    }
 }
 
 /// Statement state
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub enum StatementState {
     Waiting,
@@ -439,9 +1005,65 @@ pub enum StatementState {
     Cancelled,
 }
 
+impl StatementState {
+    /// Returns `true` if the statement has completed, failed or been cancelled.
+    pub fn is_finished(&self) -> bool {
+        matches!(*self, StatementState::Available | StatementState::Error | StatementState::Cancelled)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json;
+    use std::fmt::Debug;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use builder::{NewBatchRequestBuilder, NewSessionRequestBuilder};
+    use testing::FakeLivy;
+
+    /// Sample responses of every endpoint for each supported Livy version
+    const CORPUS: &[(&str, &str)] = &[
+        ("0.3", include_str!("../tests/corpus/livy-0.3.json")),
+        ("0.4", include_str!("../tests/corpus/livy-0.4.json")),
+        ("0.5", include_str!("../tests/corpus/livy-0.5.json")),
+        ("0.6", include_str!("../tests/corpus/livy-0.6.json")),
+        ("0.7", include_str!("../tests/corpus/livy-0.7.json")),
+        ("0.8", include_str!("../tests/corpus/livy-0.8.json")),
+    ];
+
+    /// Deserializes `sample`, serializes the result and checks that it
+    /// deserializes to the same value and keeps every modeled field.
+    fn round_trip<T: DeserializeOwned + Serialize + PartialEq + Debug>(sample: &Value) -> Result<(), String> {
+        let value: T = serde_json::from_value(sample.clone()).map_err(|err| format!("{}", err))?;
+        let json = serde_json::to_value(&value).map_err(|err| format!("{}", err))?;
+        let again: T = serde_json::from_value(json.clone()).map_err(|err| format!("{}", err))?;
+
+        if value != again {
+            return Err(format!("{:?} != {:?}", value, again));
+        }
+
+        modeled_fields_equal(&json, sample)
+    }
+
+    fn modeled_fields_equal(json: &Value, sample: &Value) -> Result<(), String> {
+        match (json, sample) {
+            (Value::Object(json), Value::Object(sample)) => {
+                for (key, value) in json {
+                    match sample.get(key) {
+                        Some(sample) => modeled_fields_equal(value, sample).map_err(|err| format!("{}: {}", key, err))?,
+                        None if value.is_null() => (),
+                        None => return Err(format!("{}: unexpected value {}", key, value)),
+                    }
+                }
+                Ok(())
+            },
+            (Value::Array(json), Value::Array(sample)) if json.len() == sample.len() => {
+                json.iter().zip(sample).try_for_each(|(json, sample)| modeled_fields_equal(json, sample))
+            },
+            _ if json == sample => Ok(()),
+            _ => Err(format!("{} != {}", json, sample)),
+        }
+    }
 
     #[test]
     fn test_client_new() {
@@ -450,6 +1072,7 @@ mod tests {
             expected_url: String,
             gssnegotiate: Option<bool>,
             username: Option<String>,
+            expected_authentication: Authentication,
         }
 
         let test_cases = vec![
@@ -458,27 +1081,359 @@ mod tests {
                 expected_url: "http://example.com:8998".to_string(),
                 gssnegotiate: None,
                 username: None,
+                expected_authentication: Authentication::None,
             },
             TestCase {
                 url: "http://example.com:8998/",
                 expected_url: "http://example.com:8998".to_string(),
                 gssnegotiate: Some(false),
                 username: Some("".to_string()),
+                expected_authentication: Authentication::basic("", ""),
             },
             TestCase {
                 url: "http://example.com:8998",
                 expected_url: "http://example.com:8998".to_string(),
                 gssnegotiate: Some(true),
                 username: Some("user".to_string()),
+                expected_authentication: Authentication::Spnego { username: Some("user".to_string()) },
             },
         ];
 
         for test_case in test_cases {
-            let client = Client::new(test_case.url, test_case.gssnegotiate.clone(), test_case.username.clone());
+            let client = Client::new(test_case.url, test_case.gssnegotiate, test_case.username.clone());
 
             assert_eq!(test_case.expected_url, client.url);
-            assert_eq!(test_case.gssnegotiate, client.gssnegotiate);
-            assert_eq!(test_case.username, client.username);
+            assert_eq!(test_case.expected_authentication, client.authentication);
+        }
+    }
+
+    #[test]
+    fn test_with_base_path() {
+        struct TestCase {
+            url: &'static str,
+            path: &'static str,
+            expected: &'static str,
+        }
+
+        let test_cases = vec![
+            TestCase { url: "http://example.com:8998", path: "", expected: "http://example.com:8998" },
+            TestCase { url: "http://example.com:8998", path: "/", expected: "http://example.com:8998" },
+            TestCase { url: "https://knox:8443/", path: "gateway/default/livy/v1", expected: "https://knox:8443/gateway/default/livy/v1" },
+            TestCase { url: "https://knox:8443/gateway", path: "/default/livy/v1/", expected: "https://knox:8443/gateway/default/livy/v1" },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected, Client::new(test_case.url, None, None).with_base_path(test_case.path).url());
         }
     }
+
+    #[test]
+    fn test_session_kind_from_str() {
+        struct TestCase {
+            s: &'static str,
+            expected: Result<SessionKind, String>,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                s: "spark",
+                expected: Ok(SessionKind::Spark),
+            },
+            TestCase {
+                s: "pyspark",
+                expected: Ok(SessionKind::Pyspark),
+            },
+            TestCase {
+                s: "pyspark3",
+                expected: Ok(SessionKind::Pyspark3),
+            },
+            TestCase {
+                s: "sparkr",
+                expected: Ok(SessionKind::Sparkr),
+            },
+            TestCase {
+                s: "sql",
+                expected: Ok(SessionKind::Sql),
+            },
+            TestCase {
+                s: "Spark",
+                expected: Err("invalid session kind: Spark".to_string()),
+            },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected, test_case.s.parse::<SessionKind>());
+        }
+    }
+
+    #[test]
+    fn test_contract_corpus() {
+        let endpoints = [
+            "GET /sessions",
+            "POST /sessions",
+            "GET /sessions/{sessionId}",
+            "GET /sessions/{sessionId}/state",
+            "DELETE /sessions/{sessionId}",
+            "GET /sessions/{sessionId}/log",
+            "GET /sessions/{sessionId}/statements",
+            "POST /sessions/{sessionId}/statements",
+            "GET /sessions/{sessionId}/statements/{statementId}",
+            "POST /sessions/{sessionId}/statements/{statementId}/cancel",
+            "GET /batches",
+            "POST /batches",
+            "GET /batches/{batchId}",
+            "GET /batches/{batchId}/state",
+            "DELETE /batches/{batchId}",
+            "GET /batches/{batchId}/log",
+        ];
+
+        for &(version, corpus) in CORPUS {
+            let corpus: Value = serde_json::from_str(corpus).unwrap();
+            let responses = corpus["responses"].as_object().unwrap();
+
+            for endpoint in endpoints.iter() {
+                // Statements can be cancelled since Livy 0.4.
+                let expected = version != "0.3" || !endpoint.ends_with("/cancel");
+                assert_eq!(expected, responses.contains_key(*endpoint), "Livy {}: {}", version, endpoint);
+            }
+
+            for (endpoint, samples) in responses {
+                for sample in samples.as_array().unwrap() {
+                    let result = match endpoint.as_str() {
+                        "GET /sessions" => round_trip::<Sessions>(sample),
+                        "POST /sessions" | "GET /sessions/{sessionId}" => round_trip::<Session>(sample),
+                        "GET /sessions/{sessionId}/state" => round_trip::<SessionStateOnly>(sample),
+                        "DELETE /sessions/{sessionId}" => round_trip::<SessionKillResult>(sample),
+                        "GET /sessions/{sessionId}/log" => round_trip::<SessionLog>(sample),
+                        "GET /sessions/{sessionId}/statements" => round_trip::<Statements>(sample),
+                        "POST /sessions/{sessionId}/statements" |
+                        "GET /sessions/{sessionId}/statements/{statementId}" => round_trip::<Statement>(sample),
+                        "POST /sessions/{sessionId}/statements/{statementId}/cancel" => round_trip::<StatementCancelResult>(sample),
+                        "GET /batches" => round_trip::<Batches>(sample),
+                        "POST /batches" | "GET /batches/{batchId}" => round_trip::<Batch>(sample),
+                        "GET /batches/{batchId}/state" => round_trip::<BatchStateOnly>(sample),
+                        "DELETE /batches/{batchId}" => round_trip::<BatchKillResult>(sample),
+                        "GET /batches/{batchId}/log" => round_trip::<BatchLog>(sample),
+                        _ => Err("unknown endpoint".to_string()),
+                    };
+
+                    assert_eq!(Ok(()), result, "Livy {}: {}: {}", version, endpoint, sample);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_session_state_serde() {
+        assert_eq!(SessionState::NotStarted, serde_json::from_str("\"not_started\"").unwrap());
+        assert_eq!(SessionState::ShuttingDown, serde_json::from_str("\"shutting_down\"").unwrap());
+        assert_eq!("\"not_started\"", serde_json::to_string(&SessionState::NotStarted).unwrap());
+        assert!(serde_json::from_str::<SessionState>("\"notStarted\"").is_err());
+
+        for state in &[SessionState::NotStarted, SessionState::Recovering, SessionState::ShuttingDown, SessionState::Killed] {
+            assert_eq!(serde_json::to_value(state).unwrap(), Value::from(state.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_missing_and_extra_fields() {
+        struct TestCase {
+            json: &'static str,
+            expected: Result<Statement, ()>,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                json: "{}",
+                expected: Ok(Statement {
+                    id: None,
+                    code: None,
+                    state: None,
+                    output: None,
+                    progress: None,
+                    started: None,
+                    completed: None,
+                }),
+            },
+            TestCase {
+                json: r#"{"id": 1, "state": "available", "output": {"status": "error", "ename": "E"}, "unknown": [1]}"#,
+                expected: Ok(Statement {
+                    id: Some(1),
+                    code: None,
+                    state: Some(StatementState::Available),
+                    output: Some(StatementOutput {
+                        status: Some("error".to_string()),
+                        execution_count: None,
+                        data: None,
+                        ename: Some("E".to_string()),
+                        evalue: None,
+                        traceback: None,
+                    }),
+                    progress: None,
+                    started: None,
+                    completed: None,
+                }),
+            },
+            TestCase {
+                json: r#"{"id": 1, "state": "unknown"}"#,
+                expected: Err(()),
+            },
+            TestCase {
+                json: r#"{"id": "1"}"#,
+                expected: Err(()),
+            },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected, serde_json::from_str(test_case.json).map_err(|_| ()), "{}", test_case.json);
+        }
+    }
+
+    #[test]
+    fn test_authentication() {
+        let livy = FakeLivy::start();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let refresh = {
+            let calls = Arc::clone(&calls);
+            Authentication::bearer_refresh(move || Ok(format!("token{}", calls.fetch_add(1, Ordering::SeqCst))))
+        };
+
+        struct TestCase {
+            authentication: Authentication,
+            expected: Option<&'static str>,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                authentication: Authentication::None,
+                expected: None,
+            },
+            TestCase {
+                authentication: Authentication::basic("alice", "s3cret"),
+                expected: Some("Basic YWxpY2U6czNjcmV0"),
+            },
+            TestCase {
+                authentication: Authentication::bearer("t0ken"),
+                expected: Some("Bearer t0ken"),
+            },
+            TestCase {
+                authentication: refresh.clone(),
+                expected: Some("Bearer token0"),
+            },
+        ];
+
+        for test_case in test_cases {
+            livy.client().with_authentication(test_case.authentication).get_sessions(None, None).unwrap();
+
+            let request = livy.requests().pop().unwrap();
+            assert_eq!(test_case.expected, request.header("Authorization"));
+        }
+
+        livy.fail_next(401, 1);
+        livy.client().with_authentication(refresh).get_sessions(None, None).unwrap();
+        assert_eq!(Some("Bearer token1"), livy.requests().pop().unwrap().header("Authorization"));
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+
+        livy.fail_next(401, 1);
+        let err = livy.client().with_authentication(Authentication::bearer("t0ken")).get_sessions(None, None).unwrap_err();
+        assert!(err.contains("401") && !err.contains("t0ken"), "{}", err);
+    }
+
+    #[test]
+    fn test_headers() {
+        let livy = FakeLivy::start();
+        let client = livy.client()
+            .with_requested_by("etl-service")
+            .with_header("X-Tenant", "analytics")
+            .with_header("x-tenant", "reporting");
+
+        client.clone().with_header("X-Request-Id", "42").get_sessions(None, None).unwrap();
+        client.get_batches(None, None).unwrap();
+
+        let hooked = client.clone().with_header_hook(Arc::new(|method, path| {
+            vec![("X-Request-Id".to_string(), format!("{:?} {}", method, path)),
+                 ("X-Tenant".to_string(), "hooked".to_string())]
+        }));
+        hooked.get_sessions(None, None).unwrap();
+        hooked.get_batches(None, None).unwrap();
+
+        let requests = livy.requests();
+        assert_eq!(Some("etl-service"), requests[0].header("X-Requested-By"));
+        assert_eq!(Some("application/json"), requests[0].header("Content-Type"));
+        assert_eq!(Some("reporting"), requests[0].header("X-Tenant"));
+        assert_eq!(Some("42"), requests[0].header("X-Request-Id"));
+        assert_eq!(None, requests[1].header("X-Request-Id"));
+        assert_eq!(1, requests[1].headers.iter().filter(|(name, _)| name == "x-requested-by").count());
+        assert!(requests[2].header("X-Request-Id").is_some_and(|id| id.starts_with("GET /sessions")));
+        assert!(requests[3].header("X-Request-Id").is_some_and(|id| id.starts_with("GET /batches")));
+        assert_eq!(Some("hooked"), requests[3].header("X-Tenant"));
+        assert_eq!(1, requests[3].headers.iter().filter(|(name, _)| name == "x-tenant").count());
+
+        let err = client.with_header("X-Token", "secret\r\nX-Injected: 1").get_sessions(None, None).unwrap_err();
+        assert_eq!("invalid header: \"X-Token\"", err);
+    }
+
+    #[test]
+    fn test_as_user() {
+        let livy = FakeLivy::start();
+        let client = livy.client();
+        let alice = client.as_user("alice");
+        let bob = alice.as_user("bob");
+
+        let session = alice.create_session(NewSessionRequestBuilder::new(SessionKind::Pyspark).build().unwrap()).unwrap();
+        let batch = bob.create_batch(NewBatchRequestBuilder::new("app.jar").build().unwrap()).unwrap();
+        let own = client.create_batch(NewBatchRequestBuilder::new("app.jar").build().unwrap()).unwrap();
+
+        assert_eq!(Some("alice".to_string()), session.proxy_user);
+        assert_eq!(Some("bob".to_string()), batch.proxy_user);
+        assert_eq!(Some("alice".to_string()), client.session_user(session.id.unwrap()));
+        assert_eq!(Some("bob".to_string()), alice.batch_user(batch.id.unwrap()));
+        assert_eq!(None, client.batch_user(own.id.unwrap()));
+
+        let err = alice.create_batch(NewBatchRequestBuilder::new("app.jar").proxy_user("bob").build().unwrap()).unwrap_err();
+        assert_eq!("the request has proxy user bob but the client impersonates alice", err);
+
+        bob.kill_batch(batch.id.unwrap()).unwrap();
+        assert_eq!(None, alice.batch_user(batch.id.unwrap()));
+    }
+
+    #[test]
+    fn test_detailed() {
+        let livy = FakeLivy::start();
+        let client = livy.client();
+
+        let batch = client.detailed().create_batch(NewBatchRequestBuilder::new("app.jar").build().unwrap()).unwrap();
+        assert_eq!(201, batch.status);
+        assert_eq!(Some("/batches/0"), batch.header("location"));
+        assert_eq!(format!("{}/batches", livy.url()), batch.url);
+        assert_eq!(Some(0), batch.body.id);
+
+        let batches = client.detailed().get_batches(Some(0), Some(10)).unwrap();
+        assert_eq!(200, batches.status);
+        assert_eq!(Some("application/json"), batches.header("Content-Type"));
+        assert_eq!(format!("{}/batches?from=0&size=10", livy.url()), batches.url);
+        assert_eq!(Some(1), batches.into_body().total);
+    }
+
+    #[test]
+    fn test_get_all() {
+        let livy = FakeLivy::start();
+        let client = livy.client();
+
+        for _ in 0..5 {
+            client.create_session(NewSessionRequestBuilder::new(SessionKind::Spark).build().unwrap()).unwrap();
+        }
+        client.create_batch(NewBatchRequestBuilder::new("app.jar").build().unwrap()).unwrap();
+
+        let ids: Vec<Option<i64>> = client.get_all_sessions(2).unwrap().iter().map(|session| session.id).collect();
+        assert_eq!(vec![Some(0), Some(1), Some(2), Some(3), Some(4)], ids);
+        assert_eq!(1, client.get_all_batches(100).unwrap().len());
+        assert_eq!(Err("invalid page size: 0".to_string()), client.get_all_sessions(0).map(|_| ()));
+
+        let paths: Vec<String> = livy.requests().into_iter()
+            .filter(|request| request.method == "GET" && request.path.starts_with("/sessions"))
+            .map(|request| request.path)
+            .collect();
+        assert_eq!(vec!["/sessions?from=0&size=2", "/sessions?from=2&size=2", "/sessions?from=4&size=2"], paths);
+    }
 }
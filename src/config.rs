@@ -0,0 +1,387 @@
+//! Client configuration profiles from a file and the environment
+//!
+//! The config file, `$LIVY_CONFIG` or else `~/.config/livy/config.toml`
+//! (`$XDG_CONFIG_HOME/livy/config.toml` if `XDG_CONFIG_HOME` is set), has
+//! named profiles of the Livy servers a user talks to:
+//!
+//! ```toml
+//! currentProfile = "dev"
+//!
+//! [profiles.dev]
+//! url = "http://localhost:8998"
+//!
+//! [profiles.prod]
+//! url = "https://livy.example.com:8998"
+//! connectTimeoutSeconds = 10
+//! timeoutSeconds = 300
+//! caFile = "/etc/pki/livy-ca.pem"
+//! auth = { type = "spnego" }
+//!
+//! [profiles.prod.headers]
+//! X-Requested-By = "etl-service"
+//!
+//! [profiles.gateway]
+//! url = "https://gateway.example.com/livy"
+//! auth = { type = "basic", username = "alice", passwordEnv = "LIVY_GATEWAY_PASSWORD" }
+//! ```
+//!
+//! The profile is the one given by name, else `$LIVY_PROFILE`, else
+//! `currentProfile`, else `default` if it exists. The environment variables
+//! `LIVY_URL` and `LIVY_TOKEN` override the URL and the authentication of
+//! the profile; the latter with a bearer token.
+//!
+//! # Examples
+//! ```no_run
+//! use livy::config;
+//!
+//! let client = config::client(Some("prod")).unwrap();
+//! let batches = client.get_batches(None, None).unwrap();
+//! ```
+
+use auth::{Authentication, Secret};
+use client::Client;
+use format;
+use http::CurlOptions;
+use serde_json;
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Profile used if none is selected and the file has a profile of this name
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Contents of a config file
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    /// Profile used if none is given by name or `LIVY_PROFILE`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Connection settings of a Livy server
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth: Option<Auth>,
+    /// Headers sent with every request, e.g. `X-Requested-By`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// User whom created sessions and batches run as
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_timeout_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u64>,
+    /// CA certificates which verify the server instead of the system's
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_file: Option<PathBuf>,
+    /// Client certificate in PEM
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<PathBuf>,
+    /// Private key of the client certificate in PEM
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<PathBuf>,
+    /// Skips the verification of the server certificate
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub insecure: bool,
+}
+
+/// Authentication of a profile
+///
+/// Passwords and tokens can be read from environment variables named by
+/// `passwordEnv` and `tokenEnv` instead of being written in the file.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Auth {
+    None,
+    Spnego {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Basic {
+        username: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        password: Option<Secret>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        password_env: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Bearer {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        token: Option<Secret>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        token_env: Option<String>,
+    },
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+/// Constructs a client of the profile named `profile`, or of the profile
+/// selected by the environment and the default config file, with the
+/// environment overrides applied.
+pub fn client(profile: Option<&str>) -> Result<Client, String> {
+    Config::load_default()?.profile(profile)?.client()
+}
+
+/// Returns the path of the default config file, if the home directory is
+/// known.
+pub fn default_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("LIVY_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .map(|dir| dir.join("livy").join("config.toml"))
+}
+
+impl Config {
+    /// Loads a config from a `.toml`, `.json`, `.yaml` or `.yml` file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, String> {
+        let path = path.as_ref();
+
+        serde_json::from_value(format::load(path)?).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    /// Loads the default config file, or returns an empty config if it
+    /// does not exist.
+    pub fn load_default() -> Result<Config, String> {
+        match default_path() {
+            Some(ref path) if path.exists() => Config::load(path),
+            _ => Ok(Config::default()),
+        }
+    }
+
+    /// Returns the profile named `name`, or the one selected by
+    /// `LIVY_PROFILE` or `currentProfile`, with the environment overrides
+    /// applied. Without any profile selected, the result has only the
+    /// overrides.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile, String> {
+        self.resolve(name, &|name| env::var(name).ok())
+    }
+
+    fn resolve(&self, name: Option<&str>, env: &dyn Fn(&str) -> Option<String>) -> Result<Profile, String> {
+        let name = name.map(String::from)
+            .or_else(|| env("LIVY_PROFILE").filter(|name| !name.is_empty()))
+            .or_else(|| self.current_profile.clone())
+            .or_else(|| Some(DEFAULT_PROFILE.to_string()).filter(|name| self.profiles.contains_key(name)));
+
+        let mut profile = match name {
+            Some(name) => self.profiles.get(&name).cloned().ok_or_else(|| {
+                let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+                format!("unknown profile: {}; the config has {}", name,
+                        if names.is_empty() { "no profiles".to_string() } else { names.join(", ") })
+            })?,
+            None => Profile::default(),
+        };
+
+        if let Some(url) = env("LIVY_URL").filter(|url| !url.is_empty()) {
+            profile.url = Some(url);
+        }
+        if let Some(token) = env("LIVY_TOKEN").filter(|token| !token.is_empty()) {
+            profile.auth = Some(Auth::Bearer { token: Some(Secret::new(token)), token_env: None });
+        }
+
+        // Secrets are read from the environment here so that a missing
+        // variable is reported before any request is sent.
+        profile.auth = match profile.auth {
+            Some(Auth::Basic { username, password, password_env: Some(variable) }) => Some(Auth::Basic {
+                username,
+                password: Some(password.or_else(|| env(&variable).map(Secret::new)).ok_or_else(|| format!("environment variable {} is not set", variable))?),
+                password_env: None,
+            }),
+            Some(Auth::Bearer { token, token_env: Some(variable) }) => Some(Auth::Bearer {
+                token: Some(token.or_else(|| env(&variable).map(Secret::new)).ok_or_else(|| format!("environment variable {} is not set", variable))?),
+                token_env: None,
+            }),
+            auth => auth,
+        };
+
+        Ok(profile)
+    }
+}
+
+impl Profile {
+    /// Returns the authentication of the profile.
+    pub fn authentication(&self) -> Result<Authentication, String> {
+        match self.auth {
+            None | Some(Auth::None) => Ok(Authentication::None),
+            Some(Auth::Spnego { ref username }) => Ok(Authentication::Spnego { username: username.clone() }),
+            Some(Auth::Basic { ref username, ref password, .. }) => Ok(Authentication::basic(username.as_str(), password.as_ref().map(Secret::expose).unwrap_or_default())),
+            Some(Auth::Bearer { token: Some(ref token), .. }) => Ok(Authentication::bearer(token.expose())),
+            Some(Auth::Bearer { token: None, .. }) => Err("bearer authentication requires token or tokenEnv".to_string()),
+        }
+    }
+
+    /// Returns the timeouts and TLS settings of the profile, or `None` if
+    /// the defaults of curl are used.
+    pub fn curl_options(&self) -> Option<CurlOptions> {
+        let options = CurlOptions {
+            connect_timeout: self.connect_timeout_seconds.map(Duration::from_secs),
+            timeout: self.timeout_seconds.map(Duration::from_secs),
+            ca_file: self.ca_file.clone(),
+            client_cert: self.client_cert.clone(),
+            client_key: self.client_key.clone(),
+            insecure: self.insecure,
+        };
+
+        if options == CurlOptions::default() { None } else { Some(options) }
+    }
+
+    /// Constructs a client of the profile.
+    pub fn client(&self) -> Result<Client, String> {
+        let url = self.url.as_ref().ok_or("the profile has no url; set url in the config file or LIVY_URL")?;
+        let mut client = Client::new(url, None, None).with_authentication(self.authentication()?);
+
+        for (name, value) in &self.headers {
+            client = client.with_header(name, value);
+        }
+        if let Some(options) = self.curl_options() {
+            client = client.with_transport(Arc::new(options));
+        }
+        if let Some(ref proxy_user) = self.proxy_user {
+            client = client.as_user(proxy_user);
+        }
+
+        Ok(client)
+    }
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod tests {
+    use super::*;
+    use format::Format;
+    use std::collections::HashMap;
+
+    const CONFIG: &str = r#"
+        currentProfile = "dev"
+
+        [profiles.dev]
+        url = "http://localhost:8998"
+
+        [profiles.prod]
+        url = "https://livy.example.com:8998"
+        timeoutSeconds = 300
+        caFile = "/etc/pki/livy-ca.pem"
+        auth = { type = "spnego" }
+        headers = { X-Requested-By = "etl-service" }
+
+        [profiles.gateway]
+        url = "https://gateway.example.com/livy"
+        auth = { type = "basic", username = "alice", passwordEnv = "GATEWAY_PASSWORD" }
+    "#;
+
+    fn config() -> Config {
+        serde_json::from_value(format::parse(CONFIG, Format::Toml).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_resolve() {
+        struct TestCase {
+            name: Option<&'static str>,
+            env: Vec<(&'static str, &'static str)>,
+            expected_url: Result<Option<&'static str>, String>,
+            expected_authentication: Authentication,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                name: None,
+                env: vec![],
+                expected_url: Ok(Some("http://localhost:8998")),
+                expected_authentication: Authentication::None,
+            },
+            TestCase {
+                name: None,
+                env: vec![("LIVY_PROFILE", "prod")],
+                expected_url: Ok(Some("https://livy.example.com:8998")),
+                expected_authentication: Authentication::Spnego { username: None },
+            },
+            TestCase {
+                name: Some("prod"),
+                env: vec![("LIVY_PROFILE", "dev"), ("LIVY_URL", "http://override:8998"), ("LIVY_TOKEN", "t0ken")],
+                expected_url: Ok(Some("http://override:8998")),
+                expected_authentication: Authentication::bearer("t0ken"),
+            },
+            TestCase {
+                name: Some("gateway"),
+                env: vec![("GATEWAY_PASSWORD", "s3cret")],
+                expected_url: Ok(Some("https://gateway.example.com/livy")),
+                expected_authentication: Authentication::basic("alice", "s3cret"),
+            },
+            TestCase {
+                name: Some("gateway"),
+                env: vec![],
+                expected_url: Err("environment variable GATEWAY_PASSWORD is not set".to_string()),
+                expected_authentication: Authentication::None,
+            },
+            TestCase {
+                name: Some("staging"),
+                env: vec![],
+                expected_url: Err("unknown profile: staging; the config has dev, gateway, prod".to_string()),
+                expected_authentication: Authentication::None,
+            },
+        ];
+
+        for test_case in test_cases {
+            let env: HashMap<&str, &str> = test_case.env.into_iter().collect();
+            let profile = config().resolve(test_case.name, &|name| env.get(name).map(|value| value.to_string()));
+
+            assert_eq!(test_case.expected_url, profile.as_ref().map(|profile| profile.url.as_deref()).map_err(Clone::clone), "{:?}", test_case.name);
+            if let Ok(profile) = profile {
+                assert_eq!(test_case.expected_authentication, profile.authentication().unwrap(), "{:?}", test_case.name);
+            }
+        }
+
+        let empty = Config::default().resolve(None, &|name| if name == "LIVY_URL" { Some("http://env:8998".to_string()) } else { None });
+        assert_eq!(Some("http://env:8998".to_string()), empty.unwrap().url);
+    }
+
+    #[test]
+    fn test_debug_redacts_secrets() {
+        let config = config();
+        let env = |name: &str| match name {
+            "GATEWAY_PASSWORD" => Some("s3cret".to_string()),
+            "LIVY_TOKEN" => Some("t0ken".to_string()),
+            _ => None,
+        };
+
+        let gateway = config.resolve(Some("gateway"), &|name| env(name).filter(|_| name != "LIVY_TOKEN")).unwrap();
+        let debug = format!("{:?}", gateway);
+        assert!(!debug.contains("s3cret") && debug.contains("REDACTED"), "{}", debug);
+
+        let debug = format!("{:?}", config.resolve(Some("dev"), &env).unwrap());
+        assert!(!debug.contains("t0ken") && debug.contains("REDACTED"), "{}", debug);
+    }
+
+    #[test]
+    fn test_client() {
+        let config = config();
+        let prod = config.resolve(Some("prod"), &|_| None).unwrap();
+
+        assert_eq!(Some(CurlOptions {
+            timeout: Some(Duration::from_secs(300)),
+            ca_file: Some(PathBuf::from("/etc/pki/livy-ca.pem")),
+            ..CurlOptions::default()
+        }), prod.curl_options());
+        assert_eq!(None, config.resolve(Some("dev"), &|_| None).unwrap().curl_options());
+        assert_eq!("https://livy.example.com:8998", prod.client().unwrap().url());
+
+        let err = Profile::default().client().err().unwrap();
+        assert_eq!("the profile has no url; set url in the config file or LIVY_URL", err);
+    }
+}
@@ -0,0 +1,189 @@
+use auth::Authentication;
+use http::{Request, Response, Transport};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Transport which keeps the cookies set by the server and sends them
+/// back with later requests
+///
+/// A jar belongs to the client of a single server, so the `Domain` and
+/// `Path` attributes of cookies are ignored. A cookie is removed when the
+/// server sets it again with `Max-Age=0` or an empty value.
+///
+/// Gateways such as Apache Knox authenticate the first request and then
+/// issue a session cookie. With `reuse_session`, requests are sent without
+/// credentials while the jar has cookies, and are sent again with
+/// credentials when the server rejects the cookies with `401 Unauthorized`.
+///
+/// # Examples
+/// ```
+/// use livy::auth::Authentication;
+/// use livy::client::Client;
+/// use livy::cookie::CookieJar;
+/// use livy::http::Curl;
+/// use std::sync::Arc;
+///
+/// let jar = CookieJar::new(Arc::new(Curl)).reuse_session();
+/// let client = Client::new("https://knox.example.com:8443/gateway/default/livy/v1", None, None)
+///     .with_authentication(Authentication::basic("alice", "s3cret"))
+///     .with_transport(Arc::new(jar));
+/// ```
+pub struct CookieJar {
+    transport: Arc<dyn Transport>,
+    cookies: Mutex<BTreeMap<String, String>>,
+    reuse_session: bool,
+}
+
+impl CookieJar {
+    /// Constructs a new empty `CookieJar` which sends requests through `transport`.
+    pub fn new(transport: Arc<dyn Transport>) -> CookieJar {
+        CookieJar {
+            transport,
+            cookies: Mutex::new(BTreeMap::new()),
+            reuse_session: false,
+        }
+    }
+
+    /// Sends credentials only while the jar has no cookies.
+    pub fn reuse_session(mut self) -> CookieJar {
+        self.reuse_session = true;
+        self
+    }
+
+    /// Returns the cookies as pairs of names and values.
+    pub fn cookies(&self) -> Vec<(String, String)> {
+        self.lock().iter().map(|(name, value)| (name.clone(), value.clone())).collect()
+    }
+
+    /// Removes all cookies.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, String>> {
+        self.cookies.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn send_with_cookies(&self, request: &Request, authenticate: bool) -> Result<Response, String> {
+        let cookie = self.lock().iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        let mut request = request.clone();
+        if !cookie.is_empty() {
+            request.headers.push(("Cookie".to_string(), cookie));
+        }
+        if !authenticate {
+            request.authentication = Authentication::None;
+        }
+
+        let response = self.transport.send(&request)?;
+        self.store(&response);
+
+        Ok(response)
+    }
+
+    fn store(&self, response: &Response) {
+        let mut cookies = self.lock();
+
+        for set_cookie in response.headers("Set-Cookie") {
+            if let Some((name, value, expired)) = parse_set_cookie(set_cookie) {
+                if expired {
+                    cookies.remove(&name);
+                } else {
+                    cookies.insert(name, value);
+                }
+            }
+        }
+    }
+}
+
+impl Transport for CookieJar {
+    fn send(&self, request: &Request) -> Result<Response, String> {
+        let has_session = self.reuse_session && !self.lock().is_empty();
+        let response = self.send_with_cookies(request, !has_session)?;
+
+        if has_session && response.status == 401 {
+            self.clear();
+            return self.send_with_cookies(request, true);
+        }
+
+        Ok(response)
+    }
+}
+
+/// Parses a `Set-Cookie` header value into the name and the value of the
+/// cookie and whether it is expired.
+fn parse_set_cookie(s: &str) -> Option<(String, String, bool)> {
+    let mut attributes = s.split(';').map(str::trim);
+    let pair = attributes.next()?;
+    let i = pair.find('=')?;
+    let (name, value) = (pair[..i].trim(), pair[i + 1..].trim());
+
+    if name.is_empty() {
+        return None;
+    }
+
+    let expired = value.is_empty() || attributes.any(|attribute| {
+        let attribute = attribute.to_lowercase();
+        attribute.strip_prefix("max-age=").and_then(|max_age| max_age.parse::<i64>().ok()).is_some_and(|max_age| max_age <= 0)
+    });
+
+    Some((name.to_string(), value.to_string(), expired))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::Client;
+    use http::Curl;
+    use testing::FakeLivy;
+
+    #[test]
+    fn test_parse_set_cookie() {
+        struct TestCase {
+            s: &'static str,
+            expected: Option<(&'static str, &'static str, bool)>,
+        }
+
+        let test_cases = vec![
+            TestCase { s: "KNOXSESSIONID=abc; Path=/gateway/default; Secure; HttpOnly", expected: Some(("KNOXSESSIONID", "abc", false)) },
+            TestCase { s: "hadoop-jwt=a=b", expected: Some(("hadoop-jwt", "a=b", false)) },
+            TestCase { s: "KNOXSESSIONID=deleteMe; Max-Age=0", expected: Some(("KNOXSESSIONID", "deleteMe", true)) },
+            TestCase { s: "rememberMe=; Path=/", expected: Some(("rememberMe", "", true)) },
+            TestCase { s: "=abc", expected: None },
+            TestCase { s: "invalid", expected: None },
+        ];
+
+        for test_case in test_cases {
+            let expected = test_case.expected.map(|(name, value, expired)| (name.to_string(), value.to_string(), expired));
+            assert_eq!(expected, parse_set_cookie(test_case.s), "{}", test_case.s);
+        }
+    }
+
+    #[test]
+    fn test_reuse_session() {
+        let livy = FakeLivy::start();
+        livy.set_session_cookie("KNOXSESSIONID");
+
+        let jar = Arc::new(CookieJar::new(Arc::new(Curl)).reuse_session());
+        let client = Client::new(&livy.url(), None, None)
+            .with_authentication(Authentication::basic("alice", "s3cret"))
+            .with_transport(jar.clone());
+
+        client.get_sessions(None, None).unwrap();
+        client.get_batches(None, None).unwrap();
+        livy.expire_session_cookies();
+        client.get_sessions(None, None).unwrap();
+
+        let authorization: Vec<bool> = livy.requests().iter()
+            .map(|request| request.header("Authorization").is_some())
+            .collect();
+        assert_eq!(vec![true, false, false, true], authorization);
+        assert_eq!(vec![("KNOXSESSIONID".to_string(), "session1".to_string())], jar.cookies());
+
+        let unauthenticated = Client::new(&livy.url(), None, None);
+        assert!(unauthenticated.get_sessions(None, None).unwrap_err().contains("401"));
+    }
+}
@@ -0,0 +1,99 @@
+//! Loading of JSON, TOML and YAML files into JSON values
+
+use serde_json::{self, Value};
+use std::fs;
+use std::path::Path;
+#[cfg(feature = "toml")]
+use toml;
+#[cfg(feature = "yaml")]
+use serde_norway;
+
+/// Format of a definition file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    /// Returns the format of `path` judged by its extension.
+    pub fn of(path: &Path) -> Result<Format, String> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Ok(Format::Json),
+            Some("toml") => Ok(Format::Toml),
+            Some("yaml") | Some("yml") => Ok(Format::Yaml),
+            _ => Err(format!("{}: unknown format; expected a .json, .toml, .yaml or .yml file", path.display())),
+        }
+    }
+}
+
+/// Reads the file at `path` in the format of its extension.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Value, String> {
+    let path = path.as_ref();
+    let format = Format::of(path)?;
+    let s = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    parse(&s, format).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+/// Parses `s` in `format`.
+///
+/// TOML and YAML are only supported with the `toml` and `yaml` features.
+pub fn parse(s: &str, format: Format) -> Result<Value, String> {
+    match format {
+        Format::Json => serde_json::from_str(s).map_err(|err| format!("{}", err)),
+        Format::Toml => parse_toml(s),
+        Format::Yaml => parse_yaml(s),
+    }
+}
+
+#[cfg(feature = "toml")]
+fn parse_toml(s: &str) -> Result<Value, String> {
+    toml::from_str(s).map_err(|err| format!("{}", err).trim_end().to_string())
+}
+
+#[cfg(not(feature = "toml"))]
+fn parse_toml(_: &str) -> Result<Value, String> {
+    Err("TOML files require the `toml` feature of livy".to_string())
+}
+
+#[cfg(feature = "yaml")]
+fn parse_yaml(s: &str) -> Result<Value, String> {
+    serde_norway::from_str(s).map_err(|err| format!("{}", err))
+}
+
+#[cfg(not(feature = "yaml"))]
+fn parse_yaml(_: &str) -> Result<Value, String> {
+    Err("YAML files require the `yaml` feature of livy".to_string())
+}
+
+#[cfg(all(test, all(feature = "toml", feature = "yaml")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        struct TestCase {
+            s: &'static str,
+            format: Format,
+        }
+
+        let test_cases = vec![
+            TestCase { s: r#"{"name": "etl", "args": ["a", 1], "conf": {"k": "v"}}"#, format: Format::Json },
+            TestCase { s: "name = \"etl\"\nargs = [\"a\", 1]\n[conf]\nk = \"v\"\n", format: Format::Toml },
+            TestCase { s: "name: etl\nargs: [a, 1]\nconf:\n  k: v\n", format: Format::Yaml },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(Ok(json!({"name": "etl", "args": ["a", 1], "conf": {"k": "v"}})),
+                       parse(test_case.s, test_case.format),
+                       "{:?}", test_case.format);
+        }
+
+        assert!(parse("name = ", Format::Toml).is_err());
+        assert_eq!(Ok(Format::Yaml), Format::of(Path::new("flow.yml")));
+        assert_eq!(Err("flow.ini: unknown format; expected a .json, .toml, .yaml or .yml file".to_string()),
+                   Format::of(Path::new("flow.ini")));
+    }
+}
@@ -1,20 +1,43 @@
+use auth::{Authentication, Secret};
 use curl;
 use curl::easy::{Auth, Easy2, Handler, List, WriteError};
+#[cfg(feature = "instrumentation")]
+use fixture::{redact_body, redact_url};
 use serde::Serialize;
 use serde::de::DeserializeOwned;
 use serde_json;
-use std::fmt::Display;
+use std::fmt::{self, Display};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
-struct Collector(Vec<u8>);
+struct Collector {
+    body: Vec<u8>,
+    headers: Vec<(String, String)>,
+}
 
 impl Handler for Collector {
     fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
-        self.0.extend_from_slice(data);
+        self.body.extend_from_slice(data);
         Ok(data.len())
     }
+
+    fn header(&mut self, data: &[u8]) -> bool {
+        let line = String::from_utf8_lossy(data);
+        let line = line.trim_end();
+
+        // Only the headers of the last response are kept, e.g. after `100 Continue`.
+        if line.starts_with("HTTP/") {
+            self.headers.clear();
+        } else if let Some(i) = line.find(':') {
+            self.headers.push((line[..i].trim().to_string(), line[i + 1..].trim().to_string()));
+        }
+
+        true
+    }
 }
 
 /// HTTP Method
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Method {
     GET,
     POST,
@@ -23,9 +46,125 @@ pub enum Method {
 
 use self::Method::*;
 
-/// Constructs a new `String` which represents a key-value
-/// parameter string from `key` and `value` and returns the
-/// result as a form of `Some(String)`.
+impl Method {
+    /// Returns the name of the method, e.g. `"GET"`.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            GET => "GET",
+            POST => "POST",
+            DELETE => "DELETE",
+        }
+    }
+}
+
+/// Percent-encodes `s` for use in a query string.
+///
+/// Every byte except unreserved characters of RFC 3986
+/// (`A-Z`, `a-z`, `0-9`, `-`, `.`, `_` and `~`) is encoded.
+///
+/// # Examples
+/// ```
+/// use livy::http;
+///
+/// assert_eq!("a%20b%26c%3Dd%23", http::encode("a b&c=d#"));
+/// assert_eq!("caf%C3%A9", http::encode("café"));
+/// ```
+pub fn encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+
+    for &b in s.as_bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(b as char),
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+
+    encoded
+}
+
+/// Decodes a percent-encoded query string component.
+///
+/// `+` is decoded as a space. Returns `None` if `s` contains an invalid
+/// escape or does not decode to UTF-8.
+///
+/// # Examples
+/// ```
+/// use livy::http;
+///
+/// assert_eq!(Some("a b&c".to_string()), http::decode("a%20b%26c"));
+/// assert_eq!(None, http::decode("%2"));
+/// ```
+pub fn decode(s: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+
+    while let Some(b) = bytes.next() {
+        match b {
+            b'%' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                let hex = ::std::str::from_utf8(&hex).ok()?;
+                decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            },
+            b'+' => decoded.push(b' '),
+            _ => decoded.push(b),
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+/// Query string builder which percent-encodes keys and values
+///
+/// # Examples
+/// ```
+/// use livy::http::Query;
+///
+/// let query = Query::new()
+///     .param("from", Some(0))
+///     .param("size", None::<i64>)
+///     .param("name", Some("nightly etl"));
+///
+/// assert_eq!("?from=0&name=nightly%20etl", query.to_string());
+/// assert_eq!("", Query::new().to_string());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Query {
+    params: Vec<String>,
+}
+
+impl Query {
+    /// Constructs a new empty `Query`.
+    pub fn new() -> Query {
+        Query::default()
+    }
+
+    /// Adds a parameter if `value` is `Some`.
+    pub fn param<T: Display>(mut self, key: &str, value: Option<T>) -> Query {
+        self.params.extend(param(key, value));
+        self
+    }
+
+    /// Returns `true` if the query has no parameter.
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+}
+
+impl fmt::Display for Query {
+    /// Formats the query as `"?key1=value1&key2=value2&..."`, or as an
+    /// empty string if it has no parameter.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.is_empty() {
+            write!(f, "?{}", self.params.join("&"))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Constructs a new `String` which represents a percent-encoded
+/// key-value parameter string from `key` and `value` and returns
+/// the result as a form of `Some(String)`.
 ///
 /// Returns `None` if `value` is `None`.
 ///
@@ -34,18 +173,17 @@ use self::Method::*;
 /// use livy::http;
 ///
 /// assert_eq!(Some("from=2".to_string()), http::param("from", Some(2)));
+/// assert_eq!(Some("name=a%26b".to_string()), http::param("name", Some("a&b")));
 /// assert_eq!(None, http::param::<i32>("from", None));
 /// ```
 pub fn param<T: Display>(key: &str, value: Option<T>) -> Option<String> {
-    match value {
-        Some(value) => Some(format!("{}={}", key, value)),
-        None => None
-    }
+    value.map(|value| format!("{}={}", encode(key), encode(&value.to_string())))
 }
 
 /// Constructs a new `String` which represents a key-value parameters
 /// string as a form of `"?key1=value1&key2=value2&..."`.
 ///
+/// The parameters are expected to be encoded by `param`.
 /// Returns an empty string if there is no `Some(String)` value in `params`.
 ///
 /// # Examples
@@ -69,24 +207,9 @@ pub fn param<T: Display>(key: &str, value: Option<T>) -> Option<String> {
 ///                              Some("key2=value2".to_string())]));
 /// ```
 pub fn params(params: Vec<Option<String>>) -> String {
-    let mut s = String::new();
-
-    for param in params {
-        match param {
-            Some(param) => {
-                if s.is_empty() {
-                    s.push('?');
-                } else {
-                    s.push('&');
-                }
-
-                s.push_str(param.as_str());
-            },
-            None => (),
-        }
-    }
+    let params: Vec<String> = params.into_iter().flatten().collect();
 
-    s
+    Query { params }.to_string()
 }
 
 /// Removes the trailing slash of `s` if it exists,
@@ -101,20 +224,196 @@ pub fn params(params: Vec<Option<String>>) -> String {
 ///            http::remove_trailing_slash("http://example.com/"));
 /// ```
 pub fn remove_trailing_slash(s: &str) -> String {
-    if s.ends_with("/") {
-        s[..s.len()-1].to_string()
-    } else {
-        s.to_string()
+    s.strip_suffix('/').unwrap_or(s).to_string()
+}
+
+/// Headers sent with every request unless replaced by `Request::headers`
+///
+/// Livy rejects POST and DELETE requests without `X-Requested-By` if
+/// its CSRF protection is enabled.
+pub const DEFAULT_HEADERS: &[(&str, &str)] = &[
+    ("Content-Type", "application/json"),
+    ("X-Requested-By", "x"),
+];
+
+/// HTTP request passed to a `Transport`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Request {
+    pub method: Method,
+    pub url: String,
+    /// JSON request body
+    pub body: Option<String>,
+    pub authentication: Authentication,
+    /// Headers which are sent in addition to `DEFAULT_HEADERS` or
+    /// replace the default headers of the same names
+    pub headers: Vec<(String, String)>,
+}
+
+/// HTTP response returned by a `Transport`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+    pub status: u32,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Response {
+    /// Returns the values of the headers named `name`, ignoring case.
+    pub fn headers(&self, name: &str) -> Vec<&str> {
+        self.headers.iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// Returns the value of the first header named `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers(name).into_iter().next()
+    }
+}
+
+/// Way of exchanging HTTP requests with a Livy server
+///
+/// `Curl` and `CurlOptions` send requests over the network.
+/// `fixture::Recorder` and `fixture::Replayer` record and replay exchanges
+/// for tests.
+pub trait Transport: Send + Sync {
+    /// Sends `request` and returns the response, whatever its status is.
+    fn send(&self, request: &Request) -> Result<Response, String>;
+}
+
+/// Transport which sends requests with curl
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Curl;
+
+impl Transport for Curl {
+    fn send(&self, request: &Request) -> Result<Response, String> {
+        CurlOptions::default().send(request)
+    }
+}
+
+/// Transport which sends requests with curl using timeouts and TLS settings
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CurlOptions {
+    /// Time allowed for connecting to the server
+    pub connect_timeout: Option<Duration>,
+    /// Time allowed for the whole request
+    pub timeout: Option<Duration>,
+    /// CA certificates which verify the server instead of the system's
+    pub ca_file: Option<PathBuf>,
+    /// Client certificate in PEM presented to the server
+    pub client_cert: Option<PathBuf>,
+    /// Private key of the client certificate in PEM
+    pub client_key: Option<PathBuf>,
+    /// Skips the verification of the server certificate and host name
+    pub insecure: bool,
+}
+
+impl Transport for CurlOptions {
+    fn send(&self, request: &Request) -> Result<Response, String> {
+        let mut easy = Easy2::new(Collector { body: Vec::new(), headers: Vec::new() });
+        let mut auth = Auth::new();
+        let token = match request.authentication {
+            Authentication::Bearer(ref token) => Some(token.token()?),
+            _ => None,
+        };
+
+        if let Err(err) = self.configure(&mut easy).and_then(|()| perform(&mut easy, &mut auth, request, token.as_ref())) {
+            return Err(format!("{}", err));
+        }
+
+        let status = easy.response_code().map_err(|err| format!("{}", err))?;
+        let collector = easy.get_mut();
+
+        Ok(Response {
+            status,
+            headers: collector.headers.drain(..).collect(),
+            body: String::from_utf8_lossy(&collector.body).into_owned(),
+        })
+    }
+}
+
+impl CurlOptions {
+    fn configure(&self, easy: &mut Easy2<Collector>) -> Result<(), curl::Error> {
+        if let Some(connect_timeout) = self.connect_timeout {
+            easy.connect_timeout(connect_timeout)?;
+        }
+        if let Some(timeout) = self.timeout {
+            easy.timeout(timeout)?;
+        }
+        if let Some(ref ca_file) = self.ca_file {
+            easy.cainfo(ca_file)?;
+        }
+        if let Some(ref client_cert) = self.client_cert {
+            easy.ssl_cert(client_cert)?;
+        }
+        if let Some(ref client_key) = self.client_key {
+            easy.ssl_key(client_key)?;
+        }
+        if self.insecure {
+            easy.ssl_verify_peer(false)?;
+            easy.ssl_verify_host(false)?;
+        }
+
+        Ok(())
     }
 }
 
 /// Sends an HTTP request, deserializes the response body and
 /// returns the result.
 pub fn send<T: DeserializeOwned, U: Serialize>(method: Method, url: &str, data: Option<U>, gssnegotiate: Option<&bool>, username: Option<&str>) -> Result<T, String> {
-    let mut easy = Easy2::new(Collector(Vec::new()));
-    let mut auth = Auth::new();
+    let authentication = Authentication::from_options(gssnegotiate.cloned(), username.map(String::from));
+
+    send_with(&Curl, method, url, data, &authentication, &[])
+}
+
+/// Decoded response body with the metadata of the response
+#[derive(Clone, Debug, PartialEq)]
+pub struct Detailed<T> {
+    pub body: T,
+    pub status: u32,
+    pub headers: Vec<(String, String)>,
+    /// URL the request was sent to
+    pub url: String,
+    /// Time from sending the request to receiving the response,
+    /// including a retry after refreshing a bearer token
+    pub elapsed: Duration,
+}
 
-    let data = match data {
+impl<T> Detailed<T> {
+    /// Returns the value of the first header named `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Discards the metadata and returns the body.
+    pub fn into_body(self) -> T {
+        self.body
+    }
+}
+
+/// Sends an HTTP request through `transport`, deserializes the response
+/// body and returns the result.
+///
+/// A request rejected with `401 Unauthorized` is retried once if its
+/// bearer token can be refreshed.
+pub fn send_with<T: DeserializeOwned, U: Serialize>(transport: &dyn Transport, method: Method, url: &str, data: Option<U>, authentication: &Authentication, headers: &[(String, String)]) -> Result<T, String> {
+    send_detailed_with(transport, method, url, data, authentication, headers).map(Detailed::into_body)
+}
+
+/// Same as `send_with`, but returns the metadata of the response next
+/// to the deserialized body.
+pub fn send_detailed_with<T: DeserializeOwned, U: Serialize>(transport: &dyn Transport, method: Method, url: &str, data: Option<U>, authentication: &Authentication, headers: &[(String, String)]) -> Result<Detailed<T>, String> {
+    for (name, value) in headers {
+        // The value is left out of the error message since it may be a secret.
+        if name.is_empty() || name.contains([':', '\r', '\n']) || value.contains(['\r', '\n']) {
+            return Err(format!("invalid header: {:?}", name));
+        }
+    }
+
+    let body = match data {
         Some(data) => {
             match serde_json::to_string(&data) {
                 Ok(data) => Some(data),
@@ -124,54 +423,115 @@ pub fn send<T: DeserializeOwned, U: Serialize>(method: Method, url: &str, data:
         None => None,
     };
 
-    if let Err(err) = perform(&mut easy, &mut auth, method, url, data.as_ref().map(String::as_bytes), gssnegotiate, username) {
-        return Err(format!("{}", err));
+    let request = Request {
+        method,
+        url: url.to_string(),
+        body,
+        authentication: authentication.clone(),
+        headers: headers.to_vec(),
+    };
+    #[cfg(feature = "instrumentation")]
+    {
+        debug!(method = method.as_str(), url = %redact_url(url), "sending request");
+        if let Some(ref body) = request.body {
+            trace!(body = %redact_body(body), "request body");
+        }
     }
 
-    match easy.response_code() {
-        Err(err) => return Err(format!("{}", err)),
-        Ok(status_code) if status_code >= 200 && status_code <= 308 => (),
-        Ok(status_code) =>  return Err(format!("invalid status code; code: {}, response: {}",
-                                               status_code,
-                                               String::from_utf8_lossy(&easy.get_ref().0))),
+    let start = Instant::now();
+    let mut response = match transport.send(&request) {
+        Ok(response) => response,
+        Err(err) => {
+            #[cfg(feature = "instrumentation")]
+            warn!(error = %err, "request failed");
+
+            return Err(err);
+        },
+    };
+
+    if let (401, Authentication::Bearer(token)) = (response.status, authentication) {
+        if token.invalidate() {
+            #[cfg(feature = "instrumentation")]
+            debug!("retrying with a refreshed bearer token");
+
+            response = transport.send(&request)?;
+        }
     }
 
-    let res = String::from_utf8_lossy(&easy.get_ref().0);
+    let elapsed = start.elapsed();
+
+    #[cfg(feature = "instrumentation")]
+    {
+        debug!(status = response.status, elapsed_ms = elapsed.as_millis() as u64, "received response");
+        trace!(body = %redact_body(&response.body), "response body");
+    }
 
-    let res = serde_json::from_str(res.as_ref());
+    if !(200..=308).contains(&response.status) {
+        return Err(format!("invalid status code; code: {}, response: {}",
+                           response.status,
+                           response.body));
+    }
 
-    match res {
-        Ok(res) => Ok(res),
-        Err(err) => Err(format!("{}", err)),
+    match serde_json::from_str(&response.body) {
+        Ok(body) => Ok(Detailed {
+            body,
+            status: response.status,
+            headers: response.headers,
+            url: request.url,
+            elapsed,
+        }),
+        Err(err) => {
+            #[cfg(feature = "instrumentation")]
+            warn!(status = response.status, error = %err, "failed to decode the response body");
+
+            Err(format!("{}", err))
+        },
     }
 }
 
-fn perform(easy: &mut Easy2<Collector>, auth: &mut Auth, method: Method, url: &str, data: Option<&[u8]>, gssnegotiate: Option<&bool>, username: Option<&str>) -> Result<(), curl::Error> {
-    match method {
+fn perform(easy: &mut Easy2<Collector>, auth: &mut Auth, request: &Request, token: Option<&Secret>) -> Result<(), curl::Error> {
+    match request.method {
         GET => easy.get(true)?,
         POST => {
             easy.post(true)?;
-            if let Some(data) = data {
-                easy.post_fields_copy(data)?;
+            if let Some(ref data) = request.body {
+                easy.post_fields_copy(data.as_bytes())?;
             }
         },
         DELETE => easy.custom_request("DELETE")?,
     };
 
-    easy.url(url)?;
+    easy.url(&request.url)?;
 
-    if let Some(gssnegotiate) = gssnegotiate {
-        auth.gssnegotiate(*gssnegotiate);
-        easy.http_auth(&auth)?;
-    }
-
-    if let Some(username) = username {
-        easy.username(username)?;
+    match request.authentication {
+        Authentication::None | Authentication::Bearer(_) => (),
+        Authentication::Spnego { ref username } => {
+            auth.gssnegotiate(true);
+            easy.http_auth(auth)?;
+            if let Some(ref username) = *username {
+                easy.username(username)?;
+            }
+        },
+        Authentication::Basic { ref username, ref password } => {
+            auth.basic(true);
+            easy.http_auth(auth)?;
+            easy.username(username)?;
+            easy.password(password.expose())?;
+        },
     }
 
     let mut headers = List::new();
-    headers.append("Content-Type: application/json")?;
-    headers.append("X-Requested-By: x")?;
+    for &(name, value) in DEFAULT_HEADERS {
+        if !request.headers.iter().any(|(key, _)| key.eq_ignore_ascii_case(name)) {
+            headers.append(&format!("{}: {}", name, value))?;
+        }
+    }
+    if let Some(token) = token {
+        headers.append(&format!("Authorization: Bearer {}", token.expose()))?;
+    }
+    for (name, value) in &request.headers {
+        headers.append(&format!("{}: {}", name, value))?;
+    }
     easy.http_headers(headers)?;
 
     easy.perform()
@@ -200,6 +560,11 @@ mod tests {
                 value: None,
                 expected: None,
             },
+            TestCase {
+                key: "proxy user",
+                value: Some(-1),
+                expected: Some("proxy%20user=-1".to_string()),
+            },
         ];
 
         for test_case in test_cases {
@@ -246,6 +611,62 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_query() {
+        struct TestCase {
+            query: Query,
+            expected: &'static str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                query: Query::new(),
+                expected: "",
+            },
+            TestCase {
+                query: Query::new().param("from", None::<i64>),
+                expected: "",
+            },
+            TestCase {
+                query: Query::new().param("from", Some(0)).param("size", Some(20)),
+                expected: "?from=0&size=20",
+            },
+            TestCase {
+                query: Query::new().param("name", Some("a b&c=d#e/f+g?")),
+                expected: "?name=a%20b%26c%3Dd%23e%2Ff%2Bg%3F",
+            },
+            TestCase {
+                query: Query::new().param("user", Some("日本")),
+                expected: "?user=%E6%97%A5%E6%9C%AC",
+            },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected, test_case.query.to_string());
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        struct TestCase {
+            s: &'static str,
+            expected: Option<&'static str>,
+        }
+
+        let test_cases = vec![
+            TestCase { s: "plain", expected: Some("plain") },
+            TestCase { s: "a%20b+c", expected: Some("a b c") },
+            TestCase { s: "%E6%97%A5%E6%9C%AC", expected: Some("日本") },
+            TestCase { s: "%zz", expected: None },
+            TestCase { s: "%4", expected: None },
+            TestCase { s: "%FF", expected: None },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected.map(String::from), decode(test_case.s), "{}", test_case.s);
+        }
+    }
+
     #[test]
     fn test_remove_trailing_slash() {
         struct TestCase {
@@ -268,4 +689,91 @@ mod tests {
             assert_eq!(test_case.expected, remove_trailing_slash(test_case.s));
         }
     }
+
+    #[cfg(feature = "instrumentation")]
+    #[test]
+    fn test_instrumentation() {
+        use builder::NewSessionRequestBuilder;
+        use client::{Client, SessionKind};
+        use fixture::{Exchange, Replayer};
+        use std::fmt::Write;
+        use std::sync::{Arc, Mutex};
+        use testing::FakeLivy;
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        /// Subscriber which records spans and events as lines of text
+        struct Capture(Arc<Mutex<Vec<String>>>);
+
+        struct Line(String);
+
+        impl Visit for Line {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                write!(self.0, " {}={:?}", field.name(), value).unwrap();
+            }
+        }
+
+        impl Subscriber for Capture {
+            fn enabled(&self, _: &Metadata) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &Attributes) -> Id {
+                let mut line = Line(format!("span {}", span.metadata().name()));
+                span.record(&mut line);
+                let mut lines = self.0.lock().unwrap();
+                lines.push(line.0);
+                Id::from_u64(lines.len() as u64)
+            }
+
+            fn record(&self, _: &Id, _: &Record) {}
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, event: &Event) {
+                let mut line = Line(format!("{}", event.metadata().level()));
+                event.record(&mut line);
+                self.0.lock().unwrap().push(line.0);
+            }
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let livy = FakeLivy::start();
+        let client = Client::new(&livy.url(), None, None)
+            .with_authentication(Authentication::basic("alice", "s3cret"))
+            .as_user("bob");
+        let replayer = Arc::new(Replayer::new(vec![Exchange {
+            method: "GET".to_string(),
+            path: "/sessions/0".to_string(),
+            body: None,
+            status: 200,
+            response: serde_json::Value::from("not json"),
+        }]));
+
+        tracing::subscriber::with_default(Capture(lines.clone()), || {
+            let request = NewSessionRequestBuilder::new(SessionKind::Spark)
+                .conf("spark.ssl.keyPassword", "s3cret")
+                .build()
+                .unwrap();
+            client.create_session(request).unwrap();
+            assert!(client.with_transport(replayer).get_session(0).is_err());
+        });
+
+        let lines = lines.lock().unwrap();
+        let expected = [
+            r#"span create_session proxy_user=Some("bob")"#.to_string(),
+            format!("DEBUG message=sending request method=\"POST\" url={}/sessions", livy.url()),
+            r#"TRACE message=request body body={"conf":{"spark.ssl.keyPassword":"REDACTED"},"kind":"spark","proxyUser":"bob"}"#.to_string(),
+        ];
+        assert_eq!(expected[..], lines[..3]);
+        assert!(lines.contains(&"span get_session session_id=0".to_string()));
+        assert!(lines.iter().any(|line| line.starts_with("DEBUG message=received response status=201")));
+        assert!(lines.iter().any(|line| line.starts_with("WARN message=failed to decode the response body status=200")));
+        assert!(!lines.iter().any(|line| line.contains("s3cret")), "{:?}", lines);
+    }
 }
@@ -0,0 +1,414 @@
+//! Persistent journal of the sessions and batches created by a client
+//!
+//! A `Journal` appends a record to a local file whenever a client with the
+//! journal creates or kills a session or batch, so that a process which
+//! died in between can find its sessions and batches again with `recover`.
+//! The file holds one JSON record per line:
+//!
+//! ```json
+//! {"event":"open","kind":"session","id":3,"url":"http://example.com:8998","tag":"nightly-etl","request":{"kind":"pyspark"},"time":1705314612000}
+//! {"event":"close","kind":"session","id":3,"url":"http://example.com:8998","tag":"nightly-etl","request":null,"time":1705318212000}
+//! ```
+//!
+//! Every record carries the tag of the journal, so that several programs
+//! can share a file and each recovers only its own entries. They take an
+//! OS lock on a `.lock` file next to the journal around every access.
+//! Values of requests whose names contain e.g. `password` or `token` are
+//! redacted.
+//!
+//! A session or batch is still returned by the client when its record
+//! cannot be written; the error is kept for `take_failures` instead.
+//!
+//! # Examples
+//! ```no_run
+//! use livy::client::Client;
+//! use livy::journal::{Journal, Recovery};
+//! use std::sync::Arc;
+//!
+//! let journal = Arc::new(Journal::open("/var/lib/etl/livy.journal", "nightly-etl").unwrap());
+//! let client = Client::new("http://example.com:8998", None, None).with_journal(journal.clone());
+//!
+//! // Kill what the previous run left behind before starting over.
+//! for recovered in journal.recover(&client, Recovery::Kill).unwrap() {
+//!     println!("{} {:?}", recovered.entry.target, recovered.status);
+//! }
+//! ```
+
+use batch;
+use client::{Client, Target};
+use fixture;
+use serde_json::{self, Value};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Journal file shared by the clients of one program
+pub struct Journal {
+    path: PathBuf,
+    lock_path: PathBuf,
+    tag: String,
+    failures: Mutex<Vec<String>>,
+}
+
+/// Session or batch which was created and not killed
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub target: Target,
+    /// Base URL of the Livy server
+    pub url: String,
+    pub tag: String,
+    /// Creation request with secrets redacted
+    pub request: Value,
+    /// Creation time in milliseconds since the epoch
+    pub created: i64,
+}
+
+/// What `recover` does with sessions and batches which are still running
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Recovery {
+    /// Leaves them running and their entries open for the caller to use
+    Reattach,
+    /// Kills them and closes their entries
+    Kill,
+}
+
+/// Result of recovering an entry
+#[derive(Debug, PartialEq)]
+pub enum RecoveryStatus {
+    /// Not finished, in the given state if Livy reported one; the entry
+    /// stays open
+    Reattached(Option<String>),
+    /// Killed by `recover`
+    Killed,
+    /// Already finished in the given state
+    Finished(String),
+    /// Unknown to the server, e.g. after it was restarted
+    Missing,
+    /// The server could not be asked; the entry stays open
+    Failed(String),
+}
+
+/// Entry and what `recover` did with it
+#[derive(Debug, PartialEq)]
+pub struct Recovered {
+    pub entry: Entry,
+    pub status: RecoveryStatus,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Record {
+    event: String,
+    kind: String,
+    id: i64,
+    url: String,
+    tag: String,
+    request: Option<Value>,
+    time: i64,
+}
+
+impl Journal {
+    /// Opens the journal file at `path`, creating it if it does not
+    /// exist, for entries tagged with `tag`.
+    pub fn open<P: AsRef<Path>>(path: P, tag: &str) -> Result<Journal, String> {
+        let path = path.as_ref();
+        OpenOptions::new().create(true).append(true).open(path)
+            .map_err(|err| format!("failed to open {}: {}", path.display(), err))?;
+
+        let mut lock_path = OsString::from(path);
+        lock_path.push(".lock");
+
+        Ok(Journal {
+            path: path.to_path_buf(),
+            lock_path: PathBuf::from(lock_path),
+            tag: tag.to_string(),
+            failures: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Returns the tag of the entries of the journal.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Records that a session or batch was created on the server at `url`.
+    pub fn record_open(&self, url: &str, target: Target, request: &Value) -> Result<(), String> {
+        let request = serde_json::from_str(&fixture::redact_body(&request.to_string())).unwrap_or(Value::Null);
+
+        self.append(&self.record("open", url, target, Some(request)))
+    }
+
+    /// Records that a session or batch on the server at `url` was killed.
+    pub fn record_close(&self, url: &str, target: Target) -> Result<(), String> {
+        self.append(&self.record("close", url, target, None))
+    }
+
+    /// Returns and forgets the errors of the records which clients failed
+    /// to write, e.g. of sessions created while the disk was full.
+    pub fn take_failures(&self) -> Vec<String> {
+        self.failures.lock().map(|mut failures| failures.drain(..).collect()).unwrap_or_default()
+    }
+
+    /// Keeps the error of a record which a client failed to write.
+    pub(crate) fn fail(&self, error: String) {
+        #[cfg(feature = "instrumentation")]
+        warn!(error = %error, "failed to write the journal");
+
+        if let Ok(mut failures) = self.failures.lock() {
+            failures.push(error);
+        }
+    }
+
+    /// Returns the entries of the journal's tag which were not closed.
+    pub fn entries(&self) -> Result<Vec<Entry>, String> {
+        let _lock = self.lock(false)?;
+
+        Ok(self.read()?.into_iter().filter(|entry| entry.tag == self.tag).collect())
+    }
+
+    /// Checks the open entries of the journal's tag on the server of
+    /// `client` and closes those which finished or are unknown to the
+    /// server. Entries of other servers are left alone.
+    ///
+    /// With `Recovery::Kill`, sessions and batches which are still running
+    /// are killed; with `Recovery::Reattach`, their states are returned.
+    pub fn recover(&self, client: &Client, recovery: Recovery) -> Result<Vec<Recovered>, String> {
+        let mut recovered = Vec::new();
+
+        for entry in self.entries()?.into_iter().filter(|entry| entry.url == client.url()) {
+            let state = match entry.target {
+                Target::Session(id) => client.get_session_state(id)
+                    .map(|state| state.state.map(|state| (state.to_string(), state.is_finished()))),
+                Target::Batch(id) => client.get_batch_state(id)
+                    .map(|state| state.state.map(|state| (state.clone(), batch::is_finished(&state)))),
+            };
+
+            let status = match state {
+                Ok(Some((state, true))) => RecoveryStatus::Finished(state),
+                Ok(state) if recovery == Recovery::Reattach => RecoveryStatus::Reattached(state.map(|(state, _)| state)),
+                Ok(_) => {
+                    let killed = match entry.target {
+                        Target::Session(id) => client.kill_session(id).map(|_| ()),
+                        Target::Batch(id) => client.kill_batch(id).map(|_| ()),
+                    };
+                    match killed {
+                        Ok(()) => RecoveryStatus::Killed,
+                        Err(err) => RecoveryStatus::Failed(err),
+                    }
+                },
+                Err(ref err) if err.contains("code: 404") => RecoveryStatus::Missing,
+                Err(err) => RecoveryStatus::Failed(err),
+            };
+
+            if let RecoveryStatus::Finished(_) | RecoveryStatus::Killed | RecoveryStatus::Missing = status {
+                self.record_close(&entry.url, entry.target)?;
+            }
+
+            recovered.push(Recovered { entry, status });
+        }
+
+        Ok(recovered)
+    }
+
+    /// Rewrites the file with only the open entries of every tag.
+    pub fn compact(&self) -> Result<(), String> {
+        let _lock = self.lock(true)?;
+        let entries = self.read()?;
+
+        let mut lines = String::new();
+        for entry in entries {
+            let record = Record {
+                event: "open".to_string(),
+                kind: kind(entry.target).to_string(),
+                id: id(entry.target),
+                url: entry.url,
+                tag: entry.tag,
+                request: Some(entry.request),
+                time: entry.created,
+            };
+            lines.push_str(&serde_json::to_string(&record).map_err(|err| format!("{}", err))?);
+            lines.push('\n');
+        }
+
+        let temporary = self.path.with_extension("tmp");
+        File::create(&temporary)
+            .and_then(|mut file| file.write_all(lines.as_bytes()).and_then(|_| file.sync_all()))
+            .map_err(|err| format!("failed to write {}: {}", temporary.display(), err))?;
+        fs::rename(&temporary, &self.path).map_err(|err| format!("failed to replace {}: {}", self.path.display(), err))
+    }
+
+    /// Takes a lock on the lock file shared by every process using the
+    /// journal, which is released when the returned file is dropped.
+    fn lock(&self, exclusive: bool) -> Result<File, String> {
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(&self.lock_path)
+            .map_err(|err| format!("failed to open {}: {}", self.lock_path.display(), err))?;
+        if exclusive { file.lock() } else { file.lock_shared() }
+            .map_err(|err| format!("failed to lock {}: {}", self.lock_path.display(), err))?;
+
+        Ok(file)
+    }
+
+    fn record(&self, event: &str, url: &str, target: Target, request: Option<Value>) -> Record {
+        Record {
+            event: event.to_string(),
+            kind: kind(target).to_string(),
+            id: id(target),
+            url: url.to_string(),
+            tag: self.tag.clone(),
+            request,
+            time: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as i64),
+        }
+    }
+
+    fn append(&self, record: &Record) -> Result<(), String> {
+        let line = serde_json::to_string(record).map_err(|err| format!("{}", err))? + "\n";
+        let _lock = self.lock(true)?;
+
+        let mut file = OpenOptions::new().append(true).open(&self.path)
+            .map_err(|err| format!("failed to open {}: {}", self.path.display(), err))?;
+        file.write_all(line.as_bytes())
+            .and_then(|_| file.sync_data())
+            .map_err(|err| format!("failed to write {}: {}", self.path.display(), err))
+    }
+
+    /// Replays the records of the file into the entries left open.
+    fn read(&self) -> Result<Vec<Entry>, String> {
+        let s = fs::read_to_string(&self.path).map_err(|err| format!("failed to read {}: {}", self.path.display(), err))?;
+        let mut entries = BTreeMap::new();
+
+        for (i, line) in s.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let record: Record = serde_json::from_str(line)
+                .map_err(|err| format!("invalid record at {}:{}: {}", self.path.display(), i + 1, err))?;
+            let target = match record.kind.as_str() {
+                "session" => Target::Session(record.id),
+                "batch" => Target::Batch(record.id),
+                kind => return Err(format!("invalid kind at {}:{}: {}", self.path.display(), i + 1, kind)),
+            };
+            let key = (record.url.clone(), kind(target), record.id);
+
+            if record.event == "open" {
+                entries.insert(key, (i, Entry {
+                    target,
+                    url: record.url,
+                    tag: record.tag,
+                    request: record.request.unwrap_or(Value::Null),
+                    created: record.time,
+                }));
+            } else {
+                entries.remove(&key);
+            }
+        }
+
+        let mut entries: Vec<(usize, Entry)> = entries.into_values().collect();
+        entries.sort_by_key(|(i, _)| *i);
+
+        Ok(entries.into_iter().map(|(_, entry)| entry).collect())
+    }
+}
+
+fn kind(target: Target) -> &'static str {
+    match target {
+        Target::Session(_) => "session",
+        Target::Batch(_) => "batch",
+    }
+}
+
+fn id(target: Target) -> i64 {
+    match target {
+        Target::Session(id) | Target::Batch(id) => id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::{NewBatchRequestBuilder, NewSessionRequestBuilder};
+    use client::{SessionKind, SessionState};
+    use std::env;
+    use std::sync::Arc;
+    use testing::FakeLivy;
+
+    #[test]
+    fn test_journal() {
+        let path = env::temp_dir().join(format!("livy-journal-{}.jsonl", ::std::process::id()));
+        let livy = FakeLivy::start();
+        livy.set_batch_polls(100);
+        let session = || NewSessionRequestBuilder::new(SessionKind::Spark).conf("spark.ssl.keyPassword", "s3cret").build().unwrap();
+
+        {
+            let journal = Arc::new(Journal::open(&path, "etl").unwrap());
+            let client = livy.client().with_journal(journal.clone());
+            let other = livy.client().with_journal(Arc::new(Journal::open(&path, "other").unwrap()));
+
+            client.create_session(session()).unwrap();
+            client.create_session(session()).unwrap();
+            client.create_session(session()).unwrap();
+            client.create_batch(NewBatchRequestBuilder::new("app.jar").build().unwrap()).unwrap();
+            other.create_session(session()).unwrap();
+            client.kill_session(0).unwrap();
+
+            let entries = journal.entries().unwrap();
+            let targets: Vec<Target> = entries.iter().map(|entry| entry.target).collect();
+            assert_eq!(vec![Target::Session(1), Target::Session(2), Target::Batch(0)], targets);
+            assert_eq!(livy.url(), entries[0].url);
+            assert_eq!(json!("REDACTED"), entries[0].request["conf"]["spark.ssl.keyPassword"]);
+        }
+
+        livy.set_session_state(1, SessionState::Idle);
+        livy.set_session_state(2, SessionState::Dead);
+        livy.set_batch_state(0, "running");
+
+        let journal = Journal::open(&path, "etl").unwrap();
+        let client = livy.client();
+        let statuses = |recovered: Vec<Recovered>| -> Vec<(Target, RecoveryStatus)> {
+            recovered.into_iter().map(|recovered| (recovered.entry.target, recovered.status)).collect()
+        };
+
+        assert_eq!(vec![
+            (Target::Session(1), RecoveryStatus::Reattached(Some("idle".to_string()))),
+            (Target::Session(2), RecoveryStatus::Finished("dead".to_string())),
+            (Target::Batch(0), RecoveryStatus::Reattached(Some("running".to_string()))),
+        ], statuses(journal.recover(&client, Recovery::Reattach).unwrap()));
+
+        client.kill_session(1).unwrap();
+        assert_eq!(vec![
+            (Target::Session(1), RecoveryStatus::Missing),
+            (Target::Batch(0), RecoveryStatus::Killed),
+        ], statuses(journal.recover(&client, Recovery::Kill).unwrap()));
+        assert_eq!(None, livy.batch_state(0));
+        assert!(journal.entries().unwrap().is_empty());
+
+        journal.compact().unwrap();
+        let other = Journal::open(&path, "other").unwrap();
+        assert_eq!(vec![Target::Session(3)], other.entries().unwrap().iter().map(|entry| entry.target).collect::<Vec<_>>());
+        assert_eq!(1, fs::read_to_string(&path).unwrap().lines().count());
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(path.with_extension("jsonl.lock")).unwrap();
+    }
+
+    #[test]
+    fn test_journal_failure() {
+        let dir = env::temp_dir().join(format!("livy-journal-failure-{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let journal = Arc::new(Journal::open(dir.join("livy.journal"), "etl").unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let livy = FakeLivy::start();
+        let client = livy.client().with_journal(journal.clone());
+
+        let session = client.create_session(NewSessionRequestBuilder::new(SessionKind::Spark).build().unwrap()).unwrap();
+        assert_eq!(Some(0), session.id);
+        client.kill_session(0).unwrap();
+
+        let failures = journal.take_failures();
+        assert_eq!(2, failures.len());
+        assert!(failures[0].starts_with("session 0 was created but not journaled"), "{}", failures[0]);
+        assert!(failures[1].starts_with("session 0 was killed but not journaled"), "{}", failures[1]);
+        assert!(journal.take_failures().is_empty());
+    }
+}
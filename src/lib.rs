@@ -4,14 +4,90 @@
 //! ## Supported Versions of Apache Livy
 //! * 0.3.0
 //! * 0.4.0
+//! * 0.5.0
+//! * 0.6.0
+//! * 0.7.x
+//! * 0.8.0
+//!
+//! ## Features
+//! * `instrumentation`: emits `tracing` spans for every `Client` method and
+//!   events for every request. Request and response bodies are logged at
+//!   the trace level only, with secrets redacted.
+//! * `testing`: enables `livy::testing`, an in-process fake Livy server.
 
 extern crate curl;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+#[cfg_attr(any(test, feature = "testing"), macro_use)]
 extern crate serde_json;
+#[cfg(feature = "yaml")]
+extern crate serde_norway;
+#[cfg(feature = "toml")]
+extern crate toml;
+#[cfg(feature = "instrumentation")]
+#[macro_use]
+extern crate tracing;
+
+/// Enters a debug span for the rest of the enclosing block if the
+/// `instrumentation` feature is enabled.
+#[cfg(feature = "instrumentation")]
+macro_rules! instrument {
+    ($name:expr) => {
+        let _span = debug_span!($name).entered();
+    };
+    ($name:expr, $($fields:tt)*) => {
+        let _span = debug_span!($name, $($fields)*).entered();
+    };
+}
+
+#[cfg(not(feature = "instrumentation"))]
+macro_rules! instrument {
+    ($($tokens:tt)*) => {};
+}
 
+/// Authentication of requests to a Livy server
+pub mod auth;
 /// Utilities for sending an HTTP request and receiving an HTTP response
 pub mod http;
-/// Apache Livy 0.3.0 REST API client
+/// Apache Livy REST API client
 pub mod client;
+/// Builders which validate session and batch requests
+pub mod builder;
+/// Running batches to completion while following their logs
+pub mod batch;
+/// Client configuration profiles from a file and the environment
+pub mod config;
+/// Cookie persistence across requests, e.g. for Apache Knox sessions
+pub mod cookie;
+/// Recording and replaying HTTP exchanges for tests
+pub mod fixture;
+/// Loading of JSON, TOML and YAML files into JSON values
+mod format;
+/// Persistent journal of created sessions and batches for crash recovery
+pub mod journal;
+/// Prometheus metrics of the sessions and batches of a Livy server
+pub mod metrics;
+/// Running Jupyter notebooks in a session
+pub mod notebook;
+/// Loading Java properties and `spark-defaults.conf` files into requests
+pub mod properties;
+/// Finding and killing orphaned sessions and batches
+pub mod reaper;
+/// Memory sizes and cluster resources requested by sessions and batches
+pub mod resource;
+/// Running scripts split into cells in a session
+pub mod script;
+/// Translation of `spark-submit` command lines into batch requests
+pub mod spark_submit;
+/// Loading session and batch requests from job spec files with profiles
+pub mod spec;
+/// Statement code with placeholders for safely encoded values
+pub mod template;
+/// Fake Livy server for testing code built on the client
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+/// Utilities for waiting for sessions and statements to finish
+pub mod wait;
+/// Running batches which depend on each other with retries
+pub mod workflow;
@@ -0,0 +1,303 @@
+//! Prometheus metrics of the sessions and batches of a Livy server
+//!
+//! An `Exporter` lists every session and batch of a server and renders the
+//! following metrics in the Prometheus text format:
+//!
+//! * `livy_up`: 1 if the last scrape succeeded, 0 otherwise
+//! * `livy_sessions{state, kind, owner, proxy_user}`: number of sessions
+//! * `livy_batches{state, owner, proxy_user}`: number of batches
+//! * `livy_session_age_seconds{session_id, kind, owner, proxy_user}`: time
+//!   since the exporter first saw the session, since Livy does not report
+//!   when a session was created
+//! * `livy_session_statements{session_id, state}`: number of statements of
+//!   each session which has not finished
+//! * `livy_scrape_errors_total{endpoint}`: number of failed requests
+//! * `livy_scrape_duration_seconds`: duration of the last scrape
+//!
+//! Labels of values which the server does not report are empty.
+//!
+//! # Examples
+//! ```no_run
+//! use livy::client::Client;
+//! use livy::metrics::Exporter;
+//!
+//! let mut exporter = Exporter::new(Client::new("http://example.com:8998", None, None));
+//!
+//! print!("{}", exporter.scrape());
+//! ```
+
+use client::{Batch, Client, Session, Statements, DEFAULT_PAGE_SIZE};
+use serde::Serialize;
+use serde_json::{self, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::time::Instant;
+
+/// Endpoints whose failures are counted by `livy_scrape_errors_total`
+const ENDPOINTS: &[&str] = &["sessions", "batches", "statements"];
+
+/// Collector of the metrics of a Livy server
+pub struct Exporter {
+    client: Client,
+    page_size: i64,
+    first_seen: HashMap<i64, Instant>,
+    errors: BTreeMap<&'static str, u64>,
+}
+
+impl Exporter {
+    /// Constructs a new `Exporter` which sends requests through `client`.
+    pub fn new(client: Client) -> Exporter {
+        Exporter {
+            client,
+            page_size: DEFAULT_PAGE_SIZE,
+            first_seen: HashMap::new(),
+            errors: ENDPOINTS.iter().map(|endpoint| (*endpoint, 0)).collect(),
+        }
+    }
+
+    /// Sets the number of sessions or batches requested per page.
+    pub fn page_size(mut self, page_size: i64) -> Exporter {
+        self.page_size = page_size;
+        self
+    }
+
+    /// Lists the sessions and batches of the server and returns the
+    /// metrics in the Prometheus text format.
+    ///
+    /// Failed requests are counted instead of being returned, so that the
+    /// metrics of the other endpoints are still exported.
+    pub fn scrape(&mut self) -> String {
+        let start = Instant::now();
+        let mut up = true;
+
+        let sessions = self.client.get_all_sessions(self.page_size).unwrap_or_else(|_| {
+            self.count_error("sessions");
+            up = false;
+            Vec::new()
+        });
+        let batches = self.client.get_all_batches(self.page_size).unwrap_or_else(|_| {
+            self.count_error("batches");
+            up = false;
+            Vec::new()
+        });
+
+        let mut statements = BTreeMap::new();
+        for session in &sessions {
+            let id = match session.id {
+                Some(id) if !session.state.is_some_and(|state| state.is_finished()) => id,
+                _ => continue,
+            };
+
+            match self.client.get_statements(id) {
+                Ok(result) => {
+                    statements.insert(id, result);
+                },
+                Err(_) => {
+                    self.count_error("statements");
+                    up = false;
+                },
+            }
+        }
+
+        let now = Instant::now();
+        if up {
+            self.first_seen.retain(|id, _| sessions.iter().any(|session| session.id == Some(*id)));
+        }
+        for id in sessions.iter().filter_map(|session| session.id) {
+            self.first_seen.entry(id).or_insert(now);
+        }
+
+        let mut out = String::new();
+        gauge(&mut out, "livy_up", "Whether the last scrape of the Livy server succeeded", &single(if up { 1.0 } else { 0.0 }));
+        gauge(&mut out, "livy_sessions", "Number of interactive sessions", &count_sessions(&sessions));
+        gauge(&mut out, "livy_batches", "Number of batches", &count_batches(&batches));
+        gauge(&mut out, "livy_session_age_seconds", "Seconds since the session was first seen", &self.ages(&sessions, now));
+        gauge(&mut out, "livy_session_statements", "Number of statements of a session", &count_statements(&statements));
+        family(&mut out, "livy_scrape_errors_total", "counter", "Number of failed requests to the Livy server", &self.error_samples());
+        gauge(&mut out, "livy_scrape_duration_seconds", "Duration of the last scrape in seconds", &single(start.elapsed().as_secs_f64()));
+
+        out
+    }
+
+    fn count_error(&mut self, endpoint: &'static str) {
+        *self.errors.entry(endpoint).or_default() += 1;
+    }
+
+    fn ages(&self, sessions: &[Session], now: Instant) -> BTreeMap<String, f64> {
+        sessions.iter()
+            .filter_map(|session| {
+                let id = session.id?;
+                let first_seen = self.first_seen.get(&id)?;
+                let labels = labels(&[
+                    ("session_id", id.to_string()),
+                    ("kind", name(&session.kind)),
+                    ("owner", session.owner.clone().unwrap_or_default()),
+                    ("proxy_user", session.proxy_user.clone().unwrap_or_default()),
+                ]);
+
+                Some((labels, now.duration_since(*first_seen).as_secs_f64()))
+            })
+            .collect()
+    }
+
+    fn error_samples(&self) -> BTreeMap<String, f64> {
+        self.errors.iter()
+            .map(|(endpoint, count)| (labels(&[("endpoint", endpoint.to_string())]), *count as f64))
+            .collect()
+    }
+}
+
+fn count_sessions(sessions: &[Session]) -> BTreeMap<String, f64> {
+    let mut samples = BTreeMap::new();
+
+    for session in sessions {
+        *samples.entry(labels(&[
+            ("state", name(&session.state)),
+            ("kind", name(&session.kind)),
+            ("owner", session.owner.clone().unwrap_or_default()),
+            ("proxy_user", session.proxy_user.clone().unwrap_or_default()),
+        ])).or_default() += 1.0;
+    }
+
+    samples
+}
+
+fn count_batches(batches: &[Batch]) -> BTreeMap<String, f64> {
+    let mut samples = BTreeMap::new();
+
+    for batch in batches {
+        *samples.entry(labels(&[
+            ("state", batch.state.clone().unwrap_or_default()),
+            ("owner", batch.owner.clone().unwrap_or_default()),
+            ("proxy_user", batch.proxy_user.clone().unwrap_or_default()),
+        ])).or_default() += 1.0;
+    }
+
+    samples
+}
+
+fn count_statements(statements: &BTreeMap<i64, Statements>) -> BTreeMap<String, f64> {
+    let mut samples = BTreeMap::new();
+
+    for (id, result) in statements {
+        for statement in result.statements.iter().flatten() {
+            *samples.entry(labels(&[
+                ("session_id", id.to_string()),
+                ("state", name(&statement.state)),
+            ])).or_default() += 1.0;
+        }
+    }
+
+    samples
+}
+
+/// Returns the Livy name of an enum value, e.g. `idle`, or an empty string.
+fn name<T: Serialize>(value: &Option<T>) -> String {
+    match value.as_ref().map(serde_json::to_value) {
+        Some(Ok(Value::String(s))) => s,
+        _ => String::new(),
+    }
+}
+
+fn single(value: f64) -> BTreeMap<String, f64> {
+    let mut samples = BTreeMap::new();
+    samples.insert(String::new(), value);
+    samples
+}
+
+/// Formats labels, e.g. `{state="idle",owner="alice"}`.
+fn labels(pairs: &[(&str, String)]) -> String {
+    let pairs: Vec<String> = pairs.iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+        .collect();
+
+    format!("{{{}}}", pairs.join(","))
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, samples: &BTreeMap<String, f64>) {
+    family(out, name, "gauge", help, samples);
+}
+
+/// Writes a metric family with samples keyed by their formatted labels.
+fn family(out: &mut String, name: &str, kind: &str, help: &str, samples: &BTreeMap<String, f64>) {
+    let _ = writeln!(out, "# HELP {} {}.", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{}{} {}", name, labels, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::{NewBatchRequestBuilder, NewSessionRequestBuilder};
+    use client::{RunStatementRequest, SessionKind, SessionState};
+    use testing::FakeLivy;
+
+    #[test]
+    fn test_labels() {
+        struct TestCase {
+            pairs: Vec<(&'static str, String)>,
+            expected: &'static str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                pairs: vec![("state", "idle".to_string()), ("owner", String::new())],
+                expected: r#"{state="idle",owner=""}"#,
+            },
+            TestCase {
+                pairs: vec![("name", "a \"b\"\\c\nd".to_string())],
+                expected: r#"{name="a \"b\"\\c\nd"}"#,
+            },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected, labels(&test_case.pairs));
+        }
+    }
+
+    #[test]
+    fn test_scrape() {
+        let livy = FakeLivy::start();
+        let client = livy.client();
+
+        for proxy_user in &["alice", "alice", "bob"] {
+            client.create_session(NewSessionRequestBuilder::new(SessionKind::Pyspark).proxy_user(*proxy_user).build().unwrap()).unwrap();
+        }
+        livy.set_session_state(0, SessionState::Idle);
+        livy.set_session_state(1, SessionState::Idle);
+        client.run_statement(0, RunStatementRequest { code: "1 + 1".to_string() }).unwrap();
+        livy.set_session_state(2, SessionState::Dead);
+        client.create_batch(NewBatchRequestBuilder::new("app.jar").build().unwrap()).unwrap();
+
+        let mut exporter = Exporter::new(client).page_size(2);
+        let metrics = exporter.scrape();
+
+        for line in &[
+            "# TYPE livy_up gauge",
+            "livy_up 1",
+            r#"livy_sessions{state="idle",kind="pyspark",owner="",proxy_user="alice"} 2"#,
+            r#"livy_sessions{state="dead",kind="pyspark",owner="",proxy_user="bob"} 1"#,
+            r#"livy_batches{state="starting",owner="",proxy_user=""} 1"#,
+            r#"livy_session_statements{session_id="0",state="waiting"} 1"#,
+            r#"livy_scrape_errors_total{endpoint="sessions"} 0"#,
+            "# TYPE livy_scrape_errors_total counter",
+        ] {
+            assert!(metrics.lines().any(|l| l == *line), "{} not in\n{}", line, metrics);
+        }
+        assert!(metrics.contains(r#"livy_session_age_seconds{session_id="2",kind="pyspark",owner="",proxy_user="bob"} "#));
+        assert!(!metrics.contains(r#"livy_session_statements{session_id="2""#));
+
+        livy.fail_next(500, 1);
+        let metrics = exporter.scrape();
+        assert!(metrics.contains("livy_up 0\n"));
+        assert!(metrics.contains("livy_scrape_errors_total{endpoint=\"sessions\"} 1\n"));
+        assert!(metrics.contains(r#"livy_batches{state="#));
+    }
+}
@@ -0,0 +1,311 @@
+use client::{Client, SessionKind, Statement};
+use script::{self, Cell, Report};
+use serde::Serialize;
+use serde_json::{self, Map, Value};
+use serde_json::ser::{PrettyFormatter, Serializer};
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+/// Parses a Jupyter notebook in nbformat 4 and returns it.
+pub fn parse(s: &str) -> Result<Value, String> {
+    let notebook: Value = serde_json::from_str(s).map_err(|err| format!("{}", err))?;
+
+    match notebook.get("nbformat").and_then(Value::as_i64) {
+        Some(4) => (),
+        Some(version) => return Err(format!("unsupported nbformat: {}", version)),
+        None => return Err("nbformat is missing".to_string()),
+    }
+
+    if !notebook.get("cells").is_some_and(Value::is_array) {
+        return Err("cells are missing".to_string());
+    }
+
+    Ok(notebook)
+}
+
+/// Serializes `notebook` the way Jupyter does, i.e. with an indentation
+/// of a single space and a trailing newline.
+pub fn to_string(notebook: &Value) -> Result<String, String> {
+    let mut buf = Vec::new();
+    let mut serializer = Serializer::with_formatter(&mut buf, PrettyFormatter::with_indent(b" "));
+
+    notebook.serialize(&mut serializer).map_err(|err| format!("{}", err))?;
+    buf.push(b'\n');
+
+    String::from_utf8(buf).map_err(|err| format!("{}", err))
+}
+
+/// Guesses the session kind from the kernel and language of `notebook`.
+///
+/// Both sparkmagic kernels (e.g. `pysparkkernel`) and plain language
+/// kernels (e.g. `python3`) are recognized.
+///
+/// # Examples
+/// ```
+/// use livy::client::SessionKind;
+/// use livy::notebook;
+///
+/// let notebook = notebook::parse(r#"{
+///     "nbformat": 4,
+///     "nbformat_minor": 2,
+///     "metadata": {"kernelspec": {"name": "sparkkernel", "language": ""}},
+///     "cells": []
+/// }"#).unwrap();
+///
+/// assert_eq!(Some(SessionKind::Spark), notebook::kind_of(&notebook));
+/// ```
+pub fn kind_of(notebook: &Value) -> Option<SessionKind> {
+    let metadata = notebook.get("metadata")?;
+    let kernel = metadata.pointer("/kernelspec/name").and_then(Value::as_str);
+
+    match kernel {
+        Some("pysparkkernel") => return Some(SessionKind::Pyspark),
+        Some("sparkkernel") => return Some(SessionKind::Spark),
+        Some("sparkrkernel") => return Some(SessionKind::Sparkr),
+        _ => (),
+    }
+
+    let language = metadata.pointer("/language_info/name")
+        .or_else(|| metadata.pointer("/kernelspec/language"))
+        .and_then(Value::as_str)?;
+
+    match language.to_lowercase().as_str() {
+        "python" => Some(SessionKind::Pyspark),
+        "scala" => Some(SessionKind::Spark),
+        "r" => Some(SessionKind::Sparkr),
+        _ => None,
+    }
+}
+
+/// Returns the non-empty code cells of `notebook` as script cells.
+///
+/// `Cell::line` holds the one-based position of the cell in the notebook.
+pub fn code_cells(notebook: &Value) -> Vec<Cell> {
+    let cells = match notebook.get("cells").and_then(Value::as_array) {
+        Some(cells) => cells,
+        None => return Vec::new(),
+    };
+
+    cells.iter()
+        .enumerate()
+        .filter(|&(_, cell)| cell.get("cell_type").and_then(Value::as_str) == Some("code"))
+        .map(|(i, cell)| Cell {
+            line: i + 1,
+            title: None,
+            code: source(cell),
+        })
+        .filter(|cell| !cell.code.trim().is_empty())
+        .collect()
+}
+
+/// Returns the source of a cell, which is either a string or a list of lines.
+fn source(cell: &Value) -> String {
+    match cell.get("source") {
+        Some(Value::String(source)) => source.clone(),
+        Some(Value::Array(lines)) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+/// Converts the output of `statement` into nbformat cell outputs.
+///
+/// A successful statement becomes an `execute_result` holding the MIME
+/// bundle of `StatementOutput::data` as is; a failed one becomes an `error`.
+pub fn outputs(statement: &Statement) -> Vec<Value> {
+    let output = match statement.output {
+        Some(ref output) => output,
+        None => return Vec::new(),
+    };
+
+    let mut result = Map::new();
+
+    if output.status.as_deref() == Some("error") {
+        result.insert("output_type".to_string(), Value::from("error"));
+        result.insert("ename".to_string(), Value::from(output.ename.clone().unwrap_or_default()));
+        result.insert("evalue".to_string(), Value::from(output.evalue.clone().unwrap_or_default()));
+        result.insert("traceback".to_string(), Value::from(output.traceback.clone().unwrap_or_default()));
+    } else {
+        let data = output.data.clone().unwrap_or_default();
+
+        if data.is_empty() {
+            return Vec::new();
+        }
+
+        result.insert("output_type".to_string(), Value::from("execute_result"));
+        result.insert("execution_count".to_string(), output.execution_count.map_or(Value::Null, Value::from));
+        result.insert("data".to_string(), Value::Object(data.into_iter().collect()));
+        result.insert("metadata".to_string(), Value::Object(Map::new()));
+    }
+
+    vec![Value::Object(result)]
+}
+
+/// Runs the code cells of `notebook` in the session whose id is equal to
+/// `session_id` and stores the results into the cells' `outputs` and
+/// `execution_count`.
+///
+/// Execution stops at the first failing cell as `script::run` does; the
+/// outputs and `execution_count` of the cells which were not run are
+/// cleared, so that no results of an earlier execution are kept.
+pub fn run(client: &Client, session_id: i64, notebook: &mut Value, interval: Duration, interrupted: &AtomicBool) -> Report {
+    let report = script::run(client, session_id, code_cells(notebook), interval, interrupted);
+
+    if let Some(cells) = notebook.get_mut("cells").and_then(Value::as_array_mut) {
+        let results = report.results.iter().map(|result| (&result.cell, Some(&result.statement)));
+        let not_run = report.error.iter().map(|error| &error.cell).chain(&report.skipped).map(|cell| (cell, None));
+
+        for (cell, statement) in results.chain(not_run) {
+            let cell = match cells.get_mut(cell.line - 1).and_then(Value::as_object_mut) {
+                Some(cell) => cell,
+                None => continue,
+            };
+            let execution_count = statement.and_then(|statement| statement.output.as_ref()).and_then(|output| output.execution_count);
+
+            cell.insert("outputs".to_string(), Value::from(statement.map(outputs).unwrap_or_default()));
+            cell.insert("execution_count".to_string(), execution_count.map_or(Value::Null, Value::from));
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::NewSessionRequestBuilder;
+    use client::{StatementOutput, StatementState};
+    use std::collections::HashMap;
+    use testing::FakeLivy;
+    use wait;
+
+    #[test]
+    fn test_code_cells() {
+        let notebook = parse(r##"{
+            "nbformat": 4,
+            "nbformat_minor": 2,
+            "metadata": {},
+            "cells": [
+                {"cell_type": "markdown", "metadata": {}, "source": "# Title"},
+                {"cell_type": "code", "metadata": {}, "source": ["x = 1\n", "x + 1"], "outputs": []},
+                {"cell_type": "code", "metadata": {}, "source": "", "outputs": []},
+                {"cell_type": "code", "metadata": {}, "source": "print(x)", "outputs": []}
+            ]
+        }"##).unwrap();
+
+        assert_eq!(vec![
+            Cell { line: 2, title: None, code: "x = 1\nx + 1".to_string() },
+            Cell { line: 4, title: None, code: "print(x)".to_string() },
+        ], code_cells(&notebook));
+    }
+
+    #[test]
+    fn test_outputs() {
+        struct TestCase {
+            output: StatementOutput,
+            expected: Vec<Value>,
+        }
+
+        let mut data = HashMap::new();
+        data.insert("text/plain".to_string(), Value::from("2"));
+        data.insert("application/json".to_string(), json!({"x": 2}));
+
+        let test_cases = vec![
+            TestCase {
+                output: StatementOutput {
+                    status: Some("ok".to_string()),
+                    execution_count: Some(3),
+                    data: Some(data),
+                    ename: None,
+                    evalue: None,
+                    traceback: None,
+                },
+                expected: vec![json!({
+                    "output_type": "execute_result",
+                    "execution_count": 3,
+                    "data": {"text/plain": "2", "application/json": {"x": 2}},
+                    "metadata": {},
+                })],
+            },
+            TestCase {
+                output: StatementOutput {
+                    status: Some("error".to_string()),
+                    execution_count: Some(4),
+                    data: None,
+                    ename: Some("NameError".to_string()),
+                    evalue: Some("name 'y' is not defined".to_string()),
+                    traceback: Some(vec!["Traceback\n".to_string()]),
+                },
+                expected: vec![json!({
+                    "output_type": "error",
+                    "ename": "NameError",
+                    "evalue": "name 'y' is not defined",
+                    "traceback": ["Traceback\n"],
+                })],
+            },
+            TestCase {
+                output: StatementOutput {
+                    status: Some("ok".to_string()),
+                    execution_count: Some(5),
+                    data: Some(HashMap::new()),
+                    ename: None,
+                    evalue: None,
+                    traceback: None,
+                },
+                expected: vec![],
+            },
+        ];
+
+        for test_case in test_cases {
+            let statement = Statement {
+                id: Some(0),
+                code: None,
+                state: Some(StatementState::Available),
+                output: Some(test_case.output),
+                progress: None,
+                started: None,
+                completed: None,
+            };
+
+            assert_eq!(test_case.expected, outputs(&statement));
+        }
+    }
+    #[test]
+    fn test_run() {
+        let livy = FakeLivy::start();
+        livy.on_statement("1 + 1", FakeLivy::text_output("2"));
+        livy.on_statement("fail()", FakeLivy::error_output("NameError", "name 'fail' is not defined"));
+        let client = livy.client();
+        let session_id = client.create_session(NewSessionRequestBuilder::new(SessionKind::Pyspark).build().unwrap()).unwrap().id.unwrap();
+        wait::wait_for_session(&client, session_id, Duration::from_millis(1)).unwrap();
+
+        // The outputs of an earlier execution are replaced or cleared.
+        let stale = r#""execution_count": 7, "outputs": [{"output_type": "stream", "name": "stdout", "text": "stale"}]"#;
+        let mut notebook = parse(&format!(r##"{{
+            "nbformat": 4,
+            "nbformat_minor": 2,
+            "metadata": {{}},
+            "cells": [
+                {{"cell_type": "markdown", "metadata": {{}}, "source": "# Title"}},
+                {{"cell_type": "code", "metadata": {{}}, "source": "1 + 1", {stale}}},
+                {{"cell_type": "code", "metadata": {{}}, "source": "fail()", {stale}}},
+                {{"cell_type": "code", "metadata": {{}}, "source": "x", {stale}}}
+            ]
+        }}"##, stale = stale)).unwrap();
+
+        let report = run(&client, session_id, &mut notebook, Duration::from_millis(1), &AtomicBool::new(false));
+
+        assert!(!report.is_success());
+        assert_eq!(json!({"cell_type": "markdown", "metadata": {}, "source": "# Title"}), notebook["cells"][0]);
+        assert_eq!(json!(0), notebook["cells"][1]["execution_count"]);
+        assert_eq!(json!([{
+            "output_type": "execute_result",
+            "execution_count": 0,
+            "data": {"text/plain": "2"},
+            "metadata": {},
+        }]), notebook["cells"][1]["outputs"]);
+        assert_eq!(json!(1), notebook["cells"][2]["execution_count"]);
+        assert_eq!(json!("error"), notebook["cells"][2]["outputs"][0]["output_type"]);
+        assert_eq!(Value::Null, notebook["cells"][3]["execution_count"]);
+        assert_eq!(json!([]), notebook["cells"][3]["outputs"]);
+    }
+}
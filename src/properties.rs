@@ -0,0 +1,380 @@
+use client::{NewBatchRequest, NewSessionRequest};
+use resource::MemorySize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Which value wins when a field or `conf` key is set both in a request
+/// and in loaded properties
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Precedence {
+    /// Values already set in the request are kept; properties only fill
+    /// in what is missing, as `spark-defaults.conf` does for `spark-submit`.
+    Request,
+    /// Values of the properties replace those of the request.
+    Properties,
+}
+
+/// Parses `s` in Java `.properties` syntax, which is also the syntax of
+/// `spark-defaults.conf`, and returns the key-value pairs in order of
+/// appearance.
+///
+/// Lines starting with `#` or `!` are comments. A key is separated from
+/// its value by `=`, `:` or whitespace. A line ending with an odd number
+/// of backslashes continues on the next line, and `\t`, `\n`, `\r`, `\f`,
+/// `\uXXXX` and `\` followed by any other character are unescaped.
+///
+/// # Examples
+/// ```
+/// use livy::properties;
+///
+/// let properties = properties::parse("# defaults\nspark.executor.memory  4g\nspark.jars=a.jar,\\\n    b.jar\n").unwrap();
+///
+/// assert_eq!(vec![("spark.executor.memory".to_string(), "4g".to_string()),
+///                 ("spark.jars".to_string(), "a.jar,b.jar".to_string())],
+///            properties);
+/// ```
+pub fn parse(s: &str) -> Result<Vec<(String, String)>, String> {
+    let mut properties = Vec::new();
+    let mut lines = s.lines().enumerate();
+
+    while let Some((i, line)) = lines.next() {
+        let line = line.trim_start();
+
+        if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+            continue;
+        }
+
+        let mut logical_line = String::new();
+        let mut physical_line = line;
+
+        while ends_with_continuation(physical_line) {
+            logical_line.push_str(&physical_line[..physical_line.len() - 1]);
+            physical_line = match lines.next() {
+                Some((_, next)) => next.trim_start(),
+                None => "",
+            };
+        }
+        logical_line.push_str(physical_line);
+
+        properties.push(parse_line(&logical_line).map_err(|err| format!("line {}: {}", i + 1, err))?);
+    }
+
+    Ok(properties)
+}
+
+/// Reads the properties file at `path` and returns its properties.
+/// When a key appears more than once, the last value wins.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<HashMap<String, String>, String> {
+    let path = path.as_ref();
+    let s = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    parse(&s)
+        .map(|properties| properties.into_iter().collect())
+        .map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+fn ends_with_continuation(line: &str) -> bool {
+    line.chars().rev().take_while(|&c| c == '\\').count() % 2 == 1
+}
+
+fn parse_line(line: &str) -> Result<(String, String), String> {
+    let mut chars = line.chars().peekable();
+    let mut key = String::new();
+
+    while let Some(&c) = chars.peek() {
+        if c == '=' || c == ':' || c.is_whitespace() {
+            break;
+        }
+        chars.next();
+        push_unescaped(&mut key, c, &mut chars)?;
+    }
+
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+    if chars.peek().is_some_and(|&c| c == '=' || c == ':') {
+        chars.next();
+    }
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+
+    let mut value = String::new();
+
+    while let Some(c) = chars.next() {
+        push_unescaped(&mut value, c, &mut chars)?;
+    }
+
+    Ok((key, value))
+}
+
+fn push_unescaped<I: Iterator<Item = char>>(s: &mut String, c: char, chars: &mut I) -> Result<(), String> {
+    if c != '\\' {
+        s.push(c);
+        return Ok(());
+    }
+
+    match chars.next() {
+        Some('t') => s.push('\t'),
+        Some('n') => s.push('\n'),
+        Some('r') => s.push('\r'),
+        Some('f') => s.push('\u{c}'),
+        Some('u') => {
+            let hex: String = chars.take(4).collect();
+            let c = u32::from_str_radix(&hex, 16).ok()
+                .filter(|_| hex.len() == 4)
+                .and_then(::std::char::from_u32)
+                .ok_or_else(|| format!("malformed \\uxxxx escape: \\u{}", hex))?;
+            s.push(c);
+        },
+        Some(c) => s.push(c),
+        None => (),
+    }
+
+    Ok(())
+}
+
+/// Fields shared by session and batch requests
+struct Fields<'a> {
+    jars: &'a mut Option<Vec<String>>,
+    py_files: &'a mut Option<Vec<String>>,
+    files: &'a mut Option<Vec<String>>,
+    driver_memory: &'a mut Option<MemorySize>,
+    driver_cores: &'a mut Option<i64>,
+    executor_memory: &'a mut Option<MemorySize>,
+    executor_cores: &'a mut Option<i64>,
+    num_executors: &'a mut Option<i64>,
+    archives: &'a mut Option<Vec<String>>,
+    queue: &'a mut Option<String>,
+    name: &'a mut Option<String>,
+    conf: &'a mut Option<HashMap<String, String>>,
+}
+
+/// Applies `properties` to `request`.
+///
+/// Well-known keys are mapped onto the dedicated fields of the request
+/// instead of `conf`:
+///
+/// | Key                        | Field             |
+/// |----------------------------|-------------------|
+/// | `spark.jars`               | `jars`            |
+/// | `spark.submit.pyFiles`     | `py_files`        |
+/// | `spark.files`              | `files`           |
+/// | `spark.driver.memory`      | `driver_memory`   |
+/// | `spark.driver.cores`       | `driver_cores`    |
+/// | `spark.executor.memory`    | `executor_memory` |
+/// | `spark.executor.cores`     | `executor_cores`  |
+/// | `spark.executor.instances` | `num_executors`   |
+/// | `spark.yarn.dist.archives` | `archives`        |
+/// | `spark.yarn.queue`         | `queue`           |
+/// | `spark.app.name`           | `name`            |
+///
+/// Other keys are merged into `conf`. `precedence` decides which value
+/// wins when both the request and `properties` set one.
+pub fn apply_to_session(request: &mut NewSessionRequest, properties: &HashMap<String, String>, precedence: Precedence) -> Result<(), String> {
+    apply(Fields {
+        jars: &mut request.jars,
+        py_files: &mut request.py_files,
+        files: &mut request.files,
+        driver_memory: &mut request.driver_memory,
+        driver_cores: &mut request.driver_cores,
+        executor_memory: &mut request.executor_memory,
+        executor_cores: &mut request.executor_cores,
+        num_executors: &mut request.num_executors,
+        archives: &mut request.archives,
+        queue: &mut request.queue,
+        name: &mut request.name,
+        conf: &mut request.conf,
+    }, properties, precedence)
+}
+
+/// Applies `properties` to `request` as `apply_to_session` does.
+pub fn apply_to_batch(request: &mut NewBatchRequest, properties: &HashMap<String, String>, precedence: Precedence) -> Result<(), String> {
+    apply(Fields {
+        jars: &mut request.jars,
+        py_files: &mut request.py_files,
+        files: &mut request.files,
+        driver_memory: &mut request.driver_memory,
+        driver_cores: &mut request.driver_cores,
+        executor_memory: &mut request.executor_memory,
+        executor_cores: &mut request.executor_cores,
+        num_executors: &mut request.num_executors,
+        archives: &mut request.archives,
+        queue: &mut request.queue,
+        name: &mut request.name,
+        conf: &mut request.conf,
+    }, properties, precedence)
+}
+
+fn apply(fields: Fields, properties: &HashMap<String, String>, precedence: Precedence) -> Result<(), String> {
+    let mut keys: Vec<&String> = properties.keys().collect();
+    keys.sort();
+
+    for key in keys {
+        let value = properties[key].clone();
+
+        match key.as_str() {
+            "spark.jars" => set(fields.jars, split_list(&value), precedence),
+            "spark.submit.pyFiles" => set(fields.py_files, split_list(&value), precedence),
+            "spark.files" => set(fields.files, split_list(&value), precedence),
+            "spark.driver.memory" => set(fields.driver_memory, parse_value(key, &value)?, precedence),
+            "spark.driver.cores" => set(fields.driver_cores, parse_value(key, &value)?, precedence),
+            "spark.executor.memory" => set(fields.executor_memory, parse_value(key, &value)?, precedence),
+            "spark.executor.cores" => set(fields.executor_cores, parse_value(key, &value)?, precedence),
+            "spark.executor.instances" => set(fields.num_executors, parse_value(key, &value)?, precedence),
+            "spark.yarn.dist.archives" => set(fields.archives, split_list(&value), precedence),
+            "spark.yarn.queue" => set(fields.queue, value, precedence),
+            "spark.app.name" => set(fields.name, value, precedence),
+            _ => {
+                let conf = fields.conf.get_or_insert_with(HashMap::new);
+                if precedence == Precedence::Properties || !conf.contains_key(key) {
+                    conf.insert(key.clone(), value);
+                }
+            },
+        }
+    }
+
+    Ok(())
+}
+
+fn set<T>(field: &mut Option<T>, value: T, precedence: Precedence) {
+    if precedence == Precedence::Properties || field.is_none() {
+        *field = Some(value);
+    }
+}
+
+/// Splits a comma-separated list, dropping empty items.
+pub(crate) fn split_list(value: &str) -> Vec<String> {
+    value.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn parse_value<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid value for {}: {}", key, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        struct TestCase {
+            s: &'static str,
+            expected: Result<Vec<(String, String)>, String>,
+        }
+
+        fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+            pairs.iter().map(|&(key, value)| (key.to_string(), value.to_string())).collect()
+        }
+
+        let test_cases = vec![
+            TestCase {
+                s: "",
+                expected: Ok(vec![]),
+            },
+            TestCase {
+                s: "# comment\n  ! comment\n\n",
+                expected: Ok(vec![]),
+            },
+            TestCase {
+                s: "a=1\nb : 2\nc\t3\nd\ne = \n",
+                expected: Ok(pairs(&[("a", "1"), ("b", "2"), ("c", "3"), ("d", ""), ("e", "")])),
+            },
+            TestCase {
+                s: "spark.driver.extraJavaOptions -Dx=y -Dz=w\n",
+                expected: Ok(pairs(&[("spark.driver.extraJavaOptions", "-Dx=y -Dz=w")])),
+            },
+            TestCase {
+                s: "key\\ with\\=specials = tab\\tnew\\nline\\u00e9\\\\\n",
+                expected: Ok(pairs(&[("key with=specials", "tab\tnew\nline\u{e9}\\")])),
+            },
+            TestCase {
+                s: "list = a,\\\n       b,\\\n       c\nnext = 1\n",
+                expected: Ok(pairs(&[("list", "a,b,c"), ("next", "1")])),
+            },
+            TestCase {
+                s: "path = C:\\\\\n",
+                expected: Ok(pairs(&[("path", "C:\\")])),
+            },
+            TestCase {
+                s: "a = 1\nb = \\u12\n",
+                expected: Err("line 2: malformed \\uxxxx escape: \\u12".to_string()),
+            },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected, parse(test_case.s));
+        }
+    }
+
+    #[test]
+    fn test_apply_to_batch() {
+        struct TestCase {
+            precedence: Precedence,
+            expected_executor_memory: Option<MemorySize>,
+            expected_num_executors: Option<i64>,
+            expected_conf: Vec<(&'static str, &'static str)>,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                precedence: Precedence::Request,
+                expected_executor_memory: Some(MemorySize::from_gib(2)),
+                expected_num_executors: Some(4),
+                expected_conf: vec![("spark.ui.enabled", "true"), ("spark.dynamicAllocation.enabled", "false")],
+            },
+            TestCase {
+                precedence: Precedence::Properties,
+                expected_executor_memory: Some(MemorySize::from_gib(8)),
+                expected_num_executors: Some(4),
+                expected_conf: vec![("spark.ui.enabled", "false"), ("spark.dynamicAllocation.enabled", "false")],
+            },
+        ];
+
+        let properties: HashMap<String, String> = parse("\
+            spark.executor.memory 8g\n\
+            spark.executor.instances 4\n\
+            spark.ui.enabled false\n\
+            spark.dynamicAllocation.enabled false\n").unwrap().into_iter().collect();
+
+        for test_case in test_cases {
+            let mut conf = HashMap::new();
+            conf.insert("spark.ui.enabled".to_string(), "true".to_string());
+
+            let mut request = NewBatchRequest {
+                file: "app.jar".to_string(),
+                proxy_user: None,
+                class_name: None,
+                args: None,
+                jars: None,
+                py_files: None,
+                files: None,
+                driver_memory: None,
+                driver_cores: None,
+                executor_memory: Some(MemorySize::from_gib(2)),
+                executor_cores: None,
+                num_executors: None,
+                archives: None,
+                queue: None,
+                name: None,
+                conf: Some(conf),
+            };
+
+            apply_to_batch(&mut request, &properties, test_case.precedence).unwrap();
+
+            let expected_conf = test_case.expected_conf.iter()
+                .map(|&(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+
+            assert_eq!(test_case.expected_executor_memory, request.executor_memory);
+            assert_eq!(test_case.expected_num_executors, request.num_executors);
+            assert_eq!(Some(expected_conf), request.conf);
+        }
+    }
+}
@@ -0,0 +1,489 @@
+//! Finding and killing orphaned sessions and batches
+//!
+//! A `Reaper` lists every session and batch of a server and selects those
+//! which match all the criteria of a `Policy`:
+//!
+//! * the owner is one of `owner`s,
+//! * the proxy user is one of `proxy_user`s,
+//! * the name matches one of the `name` patterns, in which `*` matches any
+//!   characters and `?` matches a single character,
+//! * the state is one of `state`s,
+//! * nothing happened for longer than `idle_for`.
+//!
+//! The idle time of a session is the time since the last start or
+//! completion of a statement, which Livy reports since 0.7. A session with
+//! a running statement is not idle. Logs have timestamps such as
+//! `24/01/15 10:30:12` (the Spark default) or `2024-01-15 10:30:12` in the
+//! local time of the cluster, which has no offset in it, so they are only
+//! used when `log_utc_offset` gives it: the idle time of a session is then
+//! measured from its last statement or log line, whichever is later, and
+//! that of a batch from its last log line. Sessions and batches whose idle
+//! time is unknown never match `idle_for`.
+//!
+//! # Examples
+//! ```no_run
+//! use livy::client::Client;
+//! use livy::reaper::{Policy, Reaper};
+//! use std::time::Duration;
+//!
+//! let client = Client::new("http://example.com:8998", None, None);
+//! let policy = Policy::new()
+//!     .proxy_user("etl")
+//!     .name("tmp-*")
+//!     .idle_for(Duration::from_secs(2 * 60 * 60))
+//!     .log_utc_offset(9 * 60 * 60);
+//! let reaper = Reaper::new(client, policy);
+//!
+//! for candidate in reaper.find().unwrap() {
+//!     println!("would kill {}", candidate);
+//! }
+//! ```
+
+use client::{Batch, Client, Session, SessionState, Target, DEFAULT_PAGE_SIZE};
+use std::fmt::{self, Display};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Criteria which sessions and batches must all match to be killed
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Policy {
+    idle_for: Option<Duration>,
+    log_utc_offset: Option<i64>,
+    owners: Vec<String>,
+    proxy_users: Vec<String>,
+    names: Vec<String>,
+    states: Vec<SessionState>,
+}
+
+impl Policy {
+    /// Constructs a new `Policy` without criteria.
+    pub fn new() -> Policy {
+        Policy::default()
+    }
+
+    /// Matches sessions and batches idle for longer than `idle_for`.
+    pub fn idle_for(mut self, idle_for: Duration) -> Policy {
+        self.idle_for = Some(idle_for);
+        self
+    }
+
+    /// Reads log timestamps as local times `seconds` ahead of UTC, e.g.
+    /// `9 * 60 * 60` for a cluster in Japan or `0` for one in UTC.
+    ///
+    /// Without it, log timestamps are ignored when judging the idle time.
+    /// A fixed offset does not follow daylight saving time.
+    pub fn log_utc_offset(mut self, seconds: i64) -> Policy {
+        self.log_utc_offset = Some(seconds);
+        self
+    }
+
+    /// Matches sessions and batches owned by `owner` or another added owner.
+    pub fn owner<S: Into<String>>(mut self, owner: S) -> Policy {
+        self.owners.push(owner.into());
+        self
+    }
+
+    /// Matches sessions and batches run as `proxy_user` or another added
+    /// proxy user.
+    pub fn proxy_user<S: Into<String>>(mut self, proxy_user: S) -> Policy {
+        self.proxy_users.push(proxy_user.into());
+        self
+    }
+
+    /// Matches sessions and batches whose name matches `pattern` or another
+    /// added pattern.
+    pub fn name<S: Into<String>>(mut self, pattern: S) -> Policy {
+        self.names.push(pattern.into());
+        self
+    }
+
+    /// Matches sessions and batches in `state` or another added state.
+    pub fn state(mut self, state: SessionState) -> Policy {
+        self.states.push(state);
+        self
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if (Policy { log_utc_offset: None, ..self.clone() }) == Policy::default() {
+            return Err("the policy has no criteria and would match every session and batch".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` if the attributes other than the idle time match.
+    fn matches(&self, owner: &Option<String>, proxy_user: &Option<String>, name: &Option<String>, state: &Option<String>) -> bool {
+        let any = |values: &[String], value: &Option<String>| {
+            values.is_empty() || value.as_ref().is_some_and(|value| values.contains(value))
+        };
+
+        any(&self.owners, owner)
+            && any(&self.proxy_users, proxy_user)
+            && (self.names.is_empty() || name.as_ref().is_some_and(|name| self.names.iter().any(|pattern| glob_match(pattern, name))))
+            && (self.states.is_empty() || state.as_ref().is_some_and(|state| self.states.iter().any(|s| s.to_string() == *state)))
+    }
+}
+
+/// Session or batch which matches a policy
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    pub target: Target,
+    pub name: Option<String>,
+    pub owner: Option<String>,
+    pub proxy_user: Option<String>,
+    pub state: Option<String>,
+    /// Time since the last activity, if the policy has an idle time
+    pub idle: Option<Duration>,
+}
+
+impl Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.target)?;
+
+        for (key, value) in &[("name", &self.name), ("owner", &self.owner), ("proxy_user", &self.proxy_user), ("state", &self.state)] {
+            if let Some(value) = value {
+                write!(f, " {}={}", key, value)?;
+            }
+        }
+        if let Some(idle) = self.idle {
+            write!(f, " idle={}s", idle.as_secs())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Result of killing a candidate
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+    pub candidate: Candidate,
+    pub result: Result<(), String>,
+}
+
+/// Finder and killer of sessions and batches which match a policy
+pub struct Reaper {
+    client: Client,
+    policy: Policy,
+    page_size: i64,
+}
+
+impl Reaper {
+    /// Constructs a new `Reaper` which sends requests through `client`.
+    pub fn new(client: Client, policy: Policy) -> Reaper {
+        Reaper {
+            client,
+            policy,
+            page_size: DEFAULT_PAGE_SIZE,
+        }
+    }
+
+    /// Sets the number of sessions or batches requested per page.
+    pub fn page_size(mut self, page_size: i64) -> Reaper {
+        self.page_size = page_size;
+        self
+    }
+
+    /// Returns the sessions and batches which match the policy without
+    /// killing them.
+    pub fn find(&self) -> Result<Vec<Candidate>, String> {
+        self.policy.validate()?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|err| format!("{}", err))?.as_millis() as i64;
+        let mut candidates = Vec::new();
+
+        for session in self.client.get_all_sessions(self.page_size)? {
+            if let Some(candidate) = self.session_candidate(session, now) {
+                candidates.push(candidate);
+            }
+        }
+        for batch in self.client.get_all_batches(self.page_size)? {
+            if let Some(candidate) = self.batch_candidate(batch, now) {
+                candidates.push(candidate);
+            }
+        }
+
+        Ok(candidates)
+    }
+
+    /// Kills the sessions and batches which match the policy.
+    ///
+    /// A failure to kill one candidate is reported in its outcome and does
+    /// not stop the others from being killed.
+    pub fn reap(&self) -> Result<Vec<Outcome>, String> {
+        let outcomes = self.find()?.into_iter()
+            .map(|candidate| {
+                let result = match candidate.target {
+                    Target::Session(id) => self.client.kill_session(id).map(|_| ()),
+                    Target::Batch(id) => self.client.kill_batch(id).map(|_| ()),
+                };
+
+                Outcome { candidate, result }
+            })
+            .collect();
+
+        Ok(outcomes)
+    }
+
+    fn session_candidate(&self, session: Session, now: i64) -> Option<Candidate> {
+        let state = session.state.map(|state| state.to_string());
+        if !self.policy.matches(&session.owner, &session.proxy_user, &session.name, &state) {
+            return None;
+        }
+
+        let id = session.id?;
+        let idle = match self.policy.idle_for {
+            Some(idle_for) => {
+                let idle = self.session_idle(id, session.state, now)?;
+                if idle < idle_for {
+                    return None;
+                }
+                Some(idle)
+            },
+            None => None,
+        };
+
+        Some(Candidate {
+            target: Target::Session(id),
+            name: session.name,
+            owner: session.owner,
+            proxy_user: session.proxy_user,
+            state,
+            idle,
+        })
+    }
+
+    fn batch_candidate(&self, batch: Batch, now: i64) -> Option<Candidate> {
+        if !self.policy.matches(&batch.owner, &batch.proxy_user, &batch.name, &batch.state) {
+            return None;
+        }
+
+        let id = batch.id?;
+        let idle = match self.policy.idle_for {
+            Some(idle_for) => {
+                let idle = idle_since(self.last_log_timestamp(Target::Batch(id))?, now);
+                if idle < idle_for {
+                    return None;
+                }
+                Some(idle)
+            },
+            None => None,
+        };
+
+        Some(Candidate {
+            target: Target::Batch(id),
+            name: batch.name,
+            owner: batch.owner,
+            proxy_user: batch.proxy_user,
+            state: batch.state,
+            idle,
+        })
+    }
+
+    /// Returns the idle time of a session, or `None` if it is unknown.
+    fn session_idle(&self, id: i64, state: Option<SessionState>, now: i64) -> Option<Duration> {
+        if state == Some(SessionState::Busy) {
+            return Some(Duration::from_secs(0));
+        }
+
+        let statements = self.client.get_statements(id).ok()?.statements.unwrap_or_default();
+        if statements.iter().any(|statement| !statement.state.is_some_and(|state| state.is_finished())) {
+            return Some(Duration::from_secs(0));
+        }
+        let last_statement = statements.iter().flat_map(|statement| statement.started.into_iter().chain(statement.completed)).max();
+
+        last_statement.max(self.last_log_timestamp(Target::Session(id))).map(|last| idle_since(last, now))
+    }
+
+    /// Returns the latest timestamp in the log of `target` in milliseconds
+    /// since the epoch, or `None` if the policy has no UTC offset for logs.
+    fn last_log_timestamp(&self, target: Target) -> Option<i64> {
+        let offset = self.policy.log_utc_offset?;
+        let log = match target {
+            Target::Session(id) => self.client.get_session_log(id, None, None).ok()?.log,
+            Target::Batch(id) => self.client.get_batch_log(id, None, None).ok()?.log,
+        };
+
+        log.unwrap_or_default().iter()
+            .filter_map(|line| parse_log_timestamp(line))
+            .max()
+            .map(|local| local - offset * 1000)
+    }
+}
+
+fn idle_since(last: i64, now: i64) -> Duration {
+    Duration::from_millis((now - last).max(0) as u64)
+}
+
+/// Returns `true` if `s` matches `pattern`, in which `*` matches any
+/// characters and `?` matches a single character.
+fn glob_match(pattern: &str, s: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+    let (mut p, mut i) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while i < s.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == s[i]) {
+            p += 1;
+            i += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, i));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            i = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Parses the first timestamp in a log line, e.g. `24/01/15 10:30:12` or
+/// `2024-01-15 10:30:12`, in milliseconds since the epoch as if the line
+/// were logged in UTC.
+fn parse_log_timestamp(line: &str) -> Option<i64> {
+    let bytes = line.as_bytes();
+
+    (0..bytes.len()).find_map(|i| {
+        if i > 0 && bytes[i - 1].is_ascii_digit() {
+            return None;
+        }
+
+        let rest = &bytes[i..];
+        let (year, month, day, time) = if rest.len() >= 19 && rest[4] == b'-' && rest[7] == b'-' && (rest[10] == b' ' || rest[10] == b'T') {
+            (number(&rest[0..4])?, number(&rest[5..7])?, number(&rest[8..10])?, &rest[11..19])
+        } else if rest.len() >= 17 && rest[2] == b'/' && rest[5] == b'/' && rest[8] == b' ' {
+            (2000 + number(&rest[0..2])?, number(&rest[3..5])?, number(&rest[6..8])?, &rest[9..17])
+        } else {
+            return None;
+        };
+
+        if time[2] != b':' || time[5] != b':' {
+            return None;
+        }
+        let (hour, minute, second) = (number(&time[0..2])?, number(&time[3..5])?, number(&time[6..8])?);
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+
+        Some(((days_from_civil(year, month, day) * 24 + hour) * 60 + minute) * 60_000 + second * 1000)
+    })
+}
+
+fn number(digits: &[u8]) -> Option<i64> {
+    if !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+
+    Some(digits.iter().fold(0, |n, digit| n * 10 + i64::from(digit - b'0')))
+}
+
+/// Returns the number of days from 1970-01-01 to a date of the proleptic
+/// Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::{NewBatchRequestBuilder, NewSessionRequestBuilder};
+    use client::{RunStatementRequest, SessionKind};
+    use testing::FakeLivy;
+
+    #[test]
+    fn test_parse_log_timestamp() {
+        struct TestCase {
+            line: &'static str,
+            expected: Option<i64>,
+        }
+
+        let test_cases = vec![
+            TestCase { line: "1970-01-01 00:00:01", expected: Some(1000) },
+            TestCase { line: "stderr: 24/01/15 10:30:12 INFO SparkContext: Running Spark", expected: Some(1_705_314_612_000) },
+            TestCase { line: "2024-02-29T23:59:59,123 INFO", expected: Some(1_709_251_199_000) },
+            TestCase { line: "12024-01-15 10:30:12", expected: None },
+            TestCase { line: "2024-13-01 00:00:00", expected: None },
+            TestCase { line: "session 3 created", expected: None },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected, parse_log_timestamp(test_case.line), "{}", test_case.line);
+        }
+    }
+
+    #[test]
+    fn test_glob_match() {
+        struct TestCase {
+            pattern: &'static str,
+            s: &'static str,
+            expected: bool,
+        }
+
+        let test_cases = vec![
+            TestCase { pattern: "tmp-*", s: "tmp-123", expected: true },
+            TestCase { pattern: "tmp-*", s: "etl-tmp-1", expected: false },
+            TestCase { pattern: "*-nightly-?", s: "etl-nightly-3", expected: true },
+            TestCase { pattern: "a*b*c", s: "abxbc", expected: true },
+            TestCase { pattern: "a*b", s: "abc", expected: false },
+            TestCase { pattern: "*", s: "", expected: true },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected, glob_match(test_case.pattern, test_case.s), "{} {}", test_case.pattern, test_case.s);
+        }
+    }
+
+    #[test]
+    fn test_reap() {
+        let livy = FakeLivy::start();
+        let client = livy.client();
+        let session = |name: &str, proxy_user: &str| {
+            client.create_session(NewSessionRequestBuilder::new(SessionKind::Pyspark).name(name).proxy_user(proxy_user).build().unwrap()).unwrap();
+        };
+
+        session("tmp-1", "etl");
+        session("tmp-2", "etl");
+        session("report", "etl");
+        session("tmp-3", "alice");
+        client.create_batch(NewBatchRequestBuilder::new("app.jar").name("tmp-batch").proxy_user("etl").build().unwrap()).unwrap();
+
+        for id in 0..4 {
+            livy.set_session_state(id, SessionState::Idle);
+            livy.push_session_log(id, "stderr: 20/01/15 10:30:12 INFO SparkContext: Running Spark");
+        }
+        livy.push_batch_log(0, "stderr: 20/01/15 10:30:12 INFO Client: Application report");
+        client.run_statement(1, RunStatementRequest { code: "1 + 1".to_string() }).unwrap();
+
+        let policy = Policy::new().proxy_user("etl").name("tmp-*").idle_for(Duration::from_secs(3600));
+        assert!(Reaper::new(client.clone(), policy.clone()).find().unwrap().is_empty());
+
+        let reaper = Reaper::new(client.clone(), policy.log_utc_offset(9 * 60 * 60)).page_size(2);
+
+        let targets: Vec<Target> = reaper.find().unwrap().into_iter().map(|candidate| candidate.target).collect();
+        assert_eq!(vec![Target::Session(0), Target::Batch(0)], targets);
+        assert_eq!(Some(SessionState::Idle), livy.session_state(0));
+
+        let outcomes = reaper.reap().unwrap();
+        assert_eq!(2, outcomes.len());
+        assert!(outcomes.iter().all(|outcome| outcome.result.is_ok()));
+        assert_eq!(None, livy.session_state(0));
+        assert!(reaper.find().unwrap().is_empty());
+
+        livy.set_session_state(3, SessionState::Dead);
+        let dead = Reaper::new(client.clone(), Policy::new().state(SessionState::Dead).state(SessionState::Error)).find().unwrap();
+        assert_eq!(1, dead.len());
+        assert_eq!("session 3 name=tmp-3 proxy_user=alice state=dead", dead[0].to_string());
+
+        assert!(Reaper::new(client.clone(), Policy::new()).find().unwrap_err().contains("no criteria"));
+        assert!(Reaper::new(client, Policy::new().log_utc_offset(0)).find().unwrap_err().contains("no criteria"));
+    }
+}
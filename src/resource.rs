@@ -0,0 +1,361 @@
+use client::{NewBatchRequest, NewSessionRequest};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, Mul};
+use std::str::FromStr;
+
+const KIB: u64 = 1 << 10;
+const MIB: u64 = 1 << 20;
+const GIB: u64 = 1 << 30;
+const TIB: u64 = 1 << 40;
+const PIB: u64 = 1 << 50;
+
+/// Units in descending order of size, as used by `Display`
+const UNITS: &[(u64, &str)] = &[(PIB, "p"), (TIB, "t"), (GIB, "g"), (MIB, "m"), (KIB, "k")];
+
+/// Memory size in the syntax of Spark and the JVM, e.g. `512m` or `4g`
+///
+/// Units are binary (`1k` is 1024 bytes) and a size without a unit is
+/// in mebibytes, as Spark reads `spark.driver.memory` and
+/// `spark.executor.memory`. The size is serialized as a string in the
+/// largest exact unit, which is `b` for sizes that are not a multiple
+/// of a kibibyte, so that Spark never reads it as mebibytes.
+///
+/// Sizes saturate at `u64::MAX` bytes instead of overflowing.
+///
+/// # Examples
+/// ```
+/// use livy::resource::MemorySize;
+///
+/// let driver: MemorySize = "4g".parse().unwrap();
+/// let executor: MemorySize = "512m".parse().unwrap();
+///
+/// assert_eq!("5g", format!("{}", driver + executor * 2));
+/// assert!(executor < driver);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct MemorySize {
+    bytes: u64,
+}
+
+impl MemorySize {
+    pub fn from_bytes(bytes: u64) -> MemorySize {
+        MemorySize { bytes }
+    }
+
+    pub fn from_kib(kib: u64) -> MemorySize {
+        MemorySize::from_bytes(kib.saturating_mul(KIB))
+    }
+
+    pub fn from_mib(mib: u64) -> MemorySize {
+        MemorySize::from_bytes(mib.saturating_mul(MIB))
+    }
+
+    pub fn from_gib(gib: u64) -> MemorySize {
+        MemorySize::from_bytes(gib.saturating_mul(GIB))
+    }
+
+    pub fn from_tib(tib: u64) -> MemorySize {
+        MemorySize::from_bytes(tib.saturating_mul(TIB))
+    }
+
+    /// Returns the sum of both sizes, or `None` on overflow.
+    pub fn checked_add(self, other: MemorySize) -> Option<MemorySize> {
+        self.bytes.checked_add(other.bytes).map(MemorySize::from_bytes)
+    }
+
+    /// Returns the size multiplied by `n`, or `None` on overflow.
+    pub fn checked_mul(self, n: u64) -> Option<MemorySize> {
+        self.bytes.checked_mul(n).map(MemorySize::from_bytes)
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Returns the size in mebibytes, rounded down.
+    pub fn mib(&self) -> u64 {
+        self.bytes / MIB
+    }
+}
+
+impl FromStr for MemorySize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<MemorySize, String> {
+        let digits = s.chars().take_while(char::is_ascii_digit).count();
+        let invalid = || format!("invalid memory size: {:?}; expected a size such as \"512m\" or \"4g\"", s);
+
+        if digits == 0 {
+            return Err(invalid());
+        }
+
+        let unit = match s[digits..].to_lowercase().as_str() {
+            "" => MIB,
+            "b" => 1,
+            "k" | "kb" => KIB,
+            "m" | "mb" => MIB,
+            "g" | "gb" => GIB,
+            "t" | "tb" => TIB,
+            "p" | "pb" => PIB,
+            _ => return Err(invalid()),
+        };
+
+        s[..digits].parse::<u64>().ok()
+            .and_then(|value| value.checked_mul(unit))
+            .map(MemorySize::from_bytes)
+            .ok_or_else(|| format!("memory size is too large: {}", s))
+    }
+}
+
+impl fmt::Display for MemorySize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &(unit, suffix) in UNITS {
+            if self.bytes > 0 && self.bytes.is_multiple_of(unit) {
+                return write!(f, "{}{}", self.bytes / unit, suffix);
+            }
+        }
+
+        write!(f, "{}b", self.bytes)
+    }
+}
+
+impl Add for MemorySize {
+    type Output = MemorySize;
+
+    fn add(self, other: MemorySize) -> MemorySize {
+        MemorySize::from_bytes(self.bytes.saturating_add(other.bytes))
+    }
+}
+
+impl Mul<u64> for MemorySize {
+    type Output = MemorySize;
+
+    fn mul(self, n: u64) -> MemorySize {
+        MemorySize::from_bytes(self.bytes.saturating_mul(n))
+    }
+}
+
+impl Sum for MemorySize {
+    fn sum<I: Iterator<Item = MemorySize>>(iter: I) -> MemorySize {
+        iter.fold(MemorySize::default(), Add::add)
+    }
+}
+
+impl Serialize for MemorySize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for MemorySize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<MemorySize, D::Error> {
+        let s = String::deserialize(deserializer)?;
+
+        s.parse().map_err(D::Error::custom)
+    }
+}
+
+/// Memory Spark uses for a driver or an executor when none is requested
+pub const DEFAULT_MEMORY: MemorySize = MemorySize { bytes: GIB };
+/// Cores Spark uses for a driver or an executor when none are requested
+pub const DEFAULT_CORES: i64 = 1;
+/// Executors Spark on YARN starts when none are requested
+pub const DEFAULT_NUM_EXECUTORS: i64 = 2;
+
+/// Cluster resources requested by a session or a batch
+///
+/// Unset fields of a request are counted with Spark's defaults
+/// (`DEFAULT_MEMORY`, `DEFAULT_CORES` and `DEFAULT_NUM_EXECUTORS`).
+/// Memory overhead added by the cluster manager is not included.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Resources {
+    pub memory: MemorySize,
+    pub cores: i64,
+    /// Number of containers, i.e. the driver and the executors
+    pub containers: i64,
+}
+
+impl Resources {
+    /// Computes the resources requested by a batch.
+    ///
+    /// # Examples
+    /// ```
+    /// use livy::builder::NewBatchRequestBuilder;
+    /// use livy::resource::{self, MemorySize};
+    ///
+    /// let request = NewBatchRequestBuilder::new("app.jar")
+    ///     .driver_memory("2g")
+    ///     .executor_memory("4g")
+    ///     .executor_cores(2)
+    ///     .num_executors(10)
+    ///     .build()
+    ///     .unwrap();
+    /// let resources = resource::Resources::of_batch(&request);
+    ///
+    /// assert_eq!(MemorySize::from_gib(42), resources.memory);
+    /// assert_eq!(21, resources.cores);
+    /// ```
+    pub fn of_batch(request: &NewBatchRequest) -> Resources {
+        Resources::of(request.driver_memory, request.driver_cores, request.executor_memory, request.executor_cores, request.num_executors)
+    }
+
+    /// Computes the resources requested by a session.
+    pub fn of_session(request: &NewSessionRequest) -> Resources {
+        Resources::of(request.driver_memory, request.driver_cores, request.executor_memory, request.executor_cores, request.num_executors)
+    }
+
+    fn of(driver_memory: Option<MemorySize>, driver_cores: Option<i64>, executor_memory: Option<MemorySize>, executor_cores: Option<i64>, num_executors: Option<i64>) -> Resources {
+        let num_executors = num_executors.unwrap_or(DEFAULT_NUM_EXECUTORS).max(0);
+
+        Resources {
+            memory: driver_memory.unwrap_or(DEFAULT_MEMORY) + executor_memory.unwrap_or(DEFAULT_MEMORY) * num_executors as u64,
+            cores: driver_cores.unwrap_or(DEFAULT_CORES).saturating_add(executor_cores.unwrap_or(DEFAULT_CORES).saturating_mul(num_executors)),
+            containers: 1 + num_executors,
+        }
+    }
+}
+
+impl Add for Resources {
+    type Output = Resources;
+
+    fn add(self, other: Resources) -> Resources {
+        Resources {
+            memory: self.memory + other.memory,
+            cores: self.cores.saturating_add(other.cores),
+            containers: self.containers.saturating_add(other.containers),
+        }
+    }
+}
+
+impl Sum for Resources {
+    fn sum<I: Iterator<Item = Resources>>(iter: I) -> Resources {
+        iter.fold(Resources::default(), Add::add)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn test_memory_size_from_str() {
+        struct TestCase {
+            s: &'static str,
+            expected: Result<MemorySize, ()>,
+        }
+
+        let test_cases = vec![
+            TestCase { s: "1024", expected: Ok(MemorySize::from_gib(1)) },
+            TestCase { s: "100b", expected: Ok(MemorySize::from_bytes(100)) },
+            TestCase { s: "512m", expected: Ok(MemorySize::from_mib(512)) },
+            TestCase { s: "512MB", expected: Ok(MemorySize::from_mib(512)) },
+            TestCase { s: "4g", expected: Ok(MemorySize::from_gib(4)) },
+            TestCase { s: "4G", expected: Ok(MemorySize::from_gib(4)) },
+            TestCase { s: "2gb", expected: Ok(MemorySize::from_gib(2)) },
+            TestCase { s: "1t", expected: Ok(MemorySize::from_tib(1)) },
+            TestCase { s: "0k", expected: Ok(MemorySize::from_bytes(0)) },
+            TestCase { s: "0g", expected: Ok(MemorySize::from_bytes(0)) },
+            TestCase { s: "", expected: Err(()) },
+            TestCase { s: "g", expected: Err(()) },
+            TestCase { s: "1.5g", expected: Err(()) },
+            TestCase { s: "4 g", expected: Err(()) },
+            TestCase { s: "-1g", expected: Err(()) },
+            TestCase { s: "-4g", expected: Err(()) },
+            TestCase { s: "4x", expected: Err(()) },
+            TestCase { s: "99999999999p", expected: Err(()) },
+            TestCase { s: "99999999999999", expected: Err(()) },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected, test_case.s.parse::<MemorySize>().map_err(|_| ()), "{}", test_case.s);
+        }
+    }
+
+    #[test]
+    fn test_memory_size_display() {
+        struct TestCase {
+            size: MemorySize,
+            expected: &'static str,
+        }
+
+        let test_cases = vec![
+            TestCase { size: MemorySize::from_bytes(0), expected: "0b" },
+            TestCase { size: MemorySize::from_bytes(100), expected: "100b" },
+            TestCase { size: MemorySize::from_kib(3), expected: "3k" },
+            TestCase { size: MemorySize::from_mib(1536), expected: "1536m" },
+            TestCase { size: MemorySize::from_mib(2048), expected: "2g" },
+            TestCase { size: MemorySize::from_tib(1), expected: "1t" },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected, format!("{}", test_case.size));
+        }
+    }
+
+    #[test]
+    fn test_memory_size_round_trip() {
+        struct TestCase {
+            s: &'static str,
+            expected: &'static str,
+        }
+
+        let test_cases = vec![
+            TestCase { s: "1024", expected: "1g" },
+            TestCase { s: "100", expected: "100m" },
+            TestCase { s: "100b", expected: "100b" },
+            TestCase { s: "1536m", expected: "1536m" },
+        ];
+
+        for test_case in test_cases {
+            let size: MemorySize = test_case.s.parse().unwrap();
+            assert_eq!(test_case.expected, size.to_string(), "{}", test_case.s);
+            assert_eq!(Ok(size), size.to_string().parse(), "{}", test_case.s);
+        }
+
+        let size = MemorySize::from_bytes(100);
+        assert_eq!(Ok(size), size.to_string().parse());
+    }
+
+    #[test]
+    fn test_memory_size_arithmetic() {
+        let max = MemorySize::from_bytes(u64::MAX);
+
+        assert_eq!(max, MemorySize::from_tib(u64::MAX));
+        assert_eq!(max, max + MemorySize::from_bytes(1));
+        assert_eq!(max, MemorySize::from_gib(1) * u64::MAX);
+        assert_eq!(None, max.checked_add(MemorySize::from_bytes(1)));
+        assert_eq!(None, MemorySize::from_gib(1).checked_mul(u64::MAX));
+        assert_eq!(Some(MemorySize::from_gib(3)), MemorySize::from_gib(1).checked_mul(3));
+    }
+
+    #[test]
+    fn test_memory_size_serde() {
+        let size: MemorySize = serde_json::from_str("\"4g\"").unwrap();
+
+        assert_eq!(MemorySize::from_gib(4), size);
+        assert_eq!("\"4g\"", serde_json::to_string(&size).unwrap());
+        assert!(serde_json::from_str::<MemorySize>("\"4x\"").is_err());
+    }
+
+    #[test]
+    fn test_resources_of_batch() {
+        let request: NewBatchRequest = serde_json::from_str(r#"{
+            "file": "app.jar",
+            "executorMemory": "2g",
+            "executorCores": 4
+        }"#).unwrap();
+
+        let resources = Resources::of_batch(&request);
+
+        assert_eq!(Resources {
+            memory: MemorySize::from_gib(5),
+            cores: 9,
+            containers: 3,
+        }, resources);
+        assert_eq!(MemorySize::from_gib(10), vec![resources, resources].into_iter().sum::<Resources>().memory);
+    }
+}
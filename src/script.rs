@@ -0,0 +1,300 @@
+use client::{Client, RunStatementRequest, SessionKind, Statement};
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+use wait;
+
+/// Cell marker used when none is specified, as in `# %%`
+pub const DEFAULT_MARKER: &str = "%%";
+
+/// Code block of a script which is sent to a session as a single statement
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cell {
+    /// One-based line number at which the cell's code starts
+    pub line: usize,
+    /// Text following the cell marker, if any
+    pub title: Option<String>,
+    pub code: String,
+}
+
+/// Result of running a single cell
+#[derive(Debug, PartialEq)]
+pub struct CellResult {
+    pub cell: Cell,
+    pub statement: Statement,
+    pub duration: Duration,
+}
+
+/// Cell whose statement could not be run or waited for
+#[derive(Debug, PartialEq)]
+pub struct CellError {
+    pub cell: Cell,
+    /// Error of the request to Livy
+    pub error: String,
+}
+
+/// Report of running a script
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    /// Results of the cells which were run, in order
+    pub results: Vec<CellResult>,
+    /// Cell after the results which stopped the execution because a
+    /// request to Livy failed
+    pub error: Option<CellError>,
+    /// Cells which were not run because a previous cell failed
+    pub skipped: Vec<Cell>,
+}
+
+impl Report {
+    /// Returns `true` if every cell was run successfully.
+    pub fn is_success(&self) -> bool {
+        self.error.is_none() && self.skipped.is_empty() && self.results.iter().all(|result| result.statement.is_success())
+    }
+
+    /// Returns the sum of the durations of the cells which were run.
+    pub fn duration(&self) -> Duration {
+        self.results.iter().map(|result| result.duration).sum()
+    }
+}
+
+/// Returns the comment prefix of a cell marker for `kind`, i.e. `"//"`
+/// for Scala, `"#"` for Python and R and `"--"` for SQL.
+///
+/// Shared sessions are treated as Scala.
+pub fn comment_prefix(kind: SessionKind) -> &'static str {
+    match kind {
+        SessionKind::Spark | SessionKind::Shared => "//",
+        SessionKind::Pyspark | SessionKind::Pyspark3 | SessionKind::Sparkr => "#",
+        SessionKind::Sql => "--",
+    }
+}
+
+/// Guesses the session kind from the extension of `path`.
+///
+/// # Examples
+/// ```
+/// use livy::client::SessionKind;
+/// use livy::script;
+/// use std::path::Path;
+///
+/// assert_eq!(Some(SessionKind::Pyspark), script::kind_of(Path::new("job.py")));
+/// assert_eq!(Some(SessionKind::Spark), script::kind_of(Path::new("job.scala")));
+/// assert_eq!(None, script::kind_of(Path::new("job.txt")));
+/// ```
+pub fn kind_of(path: &Path) -> Option<SessionKind> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("py") => Some(SessionKind::Pyspark),
+        Some("scala") => Some(SessionKind::Spark),
+        Some("r") | Some("R") => Some(SessionKind::Sparkr),
+        Some("sql") => Some(SessionKind::Sql),
+        _ => None,
+    }
+}
+
+/// Splits `source` into cells.
+///
+/// A cell starts at each line which consists of `prefix`, optional
+/// whitespace and `marker` (e.g. `# %%` or `// %% load data`).
+/// Code preceding the first marker forms a cell of its own.
+/// Cells which contain only whitespace are dropped.
+///
+/// # Examples
+/// ```
+/// use livy::script;
+///
+/// let cells = script::parse("import os\n# %% count\nspark.range(10).count()\n", "#", "%%");
+///
+/// assert_eq!(2, cells.len());
+/// assert_eq!("import os", cells[0].code);
+/// assert_eq!(Some("count".to_string()), cells[1].title);
+/// assert_eq!(3, cells[1].line);
+/// ```
+pub fn parse(source: &str, prefix: &str, marker: &str) -> Vec<Cell> {
+    let mut cells = Vec::new();
+    let mut title = None;
+    let mut start = 1;
+    let mut lines: Vec<&str> = Vec::new();
+
+    for (i, line) in source.lines().enumerate() {
+        if let Some(next_title) = parse_marker(line, prefix, marker) {
+            push_cell(&mut cells, start, title, &lines);
+            title = next_title;
+            start = i + 2;
+            lines.clear();
+        } else {
+            lines.push(line);
+        }
+    }
+
+    push_cell(&mut cells, start, title, &lines);
+
+    cells
+}
+
+/// Returns `Some(title)` if `line` is a cell marker.
+fn parse_marker(line: &str, prefix: &str, marker: &str) -> Option<Option<String>> {
+    let rest = line.trim().strip_prefix(prefix)?.trim_start().strip_prefix(marker)?;
+
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let title = rest.trim();
+
+    Some(if title.is_empty() { None } else { Some(title.to_string()) })
+}
+
+fn push_cell(cells: &mut Vec<Cell>, start: usize, title: Option<String>, lines: &[&str]) {
+    let leading_blank_lines = lines.iter().take_while(|line| line.trim().is_empty()).count();
+    let code = lines[leading_blank_lines..].join("\n").trim_end().to_string();
+
+    if code.is_empty() {
+        return;
+    }
+
+    cells.push(Cell {
+        line: start + leading_blank_lines,
+        title,
+        code,
+    });
+}
+
+/// Runs `cells` one by one in the session whose id is equal to `session_id`,
+/// polling each statement every `interval`, and returns the report.
+///
+/// Execution stops at the first cell which does not succeed, or whose
+/// request to Livy fails; the remaining cells are reported as skipped.
+/// Setting `interrupted` cancels the running statement, which also stops
+/// the execution.
+pub fn run(client: &Client, session_id: i64, cells: Vec<Cell>, interval: Duration, interrupted: &AtomicBool) -> Report {
+    let mut report = Report {
+        results: Vec::new(),
+        error: None,
+        skipped: Vec::new(),
+    };
+    let mut cells = cells.into_iter();
+
+    for cell in cells.by_ref() {
+        let started = Instant::now();
+        let statement = match run_cell(client, session_id, &cell, interval, interrupted) {
+            Ok(statement) => statement,
+            Err(error) => {
+                report.error = Some(CellError { cell, error });
+                break;
+            },
+        };
+        let success = statement.is_success();
+
+        report.results.push(CellResult {
+            cell,
+            statement,
+            duration: started.elapsed(),
+        });
+
+        if !success {
+            break;
+        }
+    }
+
+    report.skipped.extend(cells);
+
+    report
+}
+
+fn run_cell(client: &Client, session_id: i64, cell: &Cell, interval: Duration, interrupted: &AtomicBool) -> Result<Statement, String> {
+    let statement = client.run_statement(session_id, RunStatementRequest { code: cell.code.clone() })?;
+    let statement_id = statement.id.ok_or("Livy did not return a statement id")?;
+
+    wait::wait_for_statement(client, session_id, statement_id, interval, interrupted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::NewSessionRequestBuilder;
+    use testing::FakeLivy;
+
+    #[test]
+    fn test_parse() {
+        struct TestCase {
+            source: &'static str,
+            prefix: &'static str,
+            expected: Vec<Cell>,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                source: "",
+                prefix: "#",
+                expected: vec![],
+            },
+            TestCase {
+                source: "x = 1\n\nprint(x)\n",
+                prefix: "#",
+                expected: vec![
+                    Cell { line: 1, title: None, code: "x = 1\n\nprint(x)".to_string() },
+                ],
+            },
+            TestCase {
+                source: "# %%\n\nx = 1\n#%% second\ny = 2\n# %%\n\n",
+                prefix: "#",
+                expected: vec![
+                    Cell { line: 3, title: None, code: "x = 1".to_string() },
+                    Cell { line: 5, title: Some("second".to_string()), code: "y = 2".to_string() },
+                ],
+            },
+            TestCase {
+                source: "val x = 1\n// %% next\nx + 1\n# %% not a marker\n",
+                prefix: "//",
+                expected: vec![
+                    Cell { line: 1, title: None, code: "val x = 1".to_string() },
+                    Cell { line: 3, title: Some("next".to_string()), code: "x + 1\n# %% not a marker".to_string() },
+                ],
+            },
+            TestCase {
+                source: "# %%%\nx = 1\n",
+                prefix: "#",
+                expected: vec![
+                    Cell { line: 1, title: None, code: "# %%%\nx = 1".to_string() },
+                ],
+            },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected, parse(test_case.source, test_case.prefix, DEFAULT_MARKER));
+        }
+    }
+    #[test]
+    fn test_run() {
+        let livy = FakeLivy::start();
+        livy.on_statement("fail()", FakeLivy::error_output("NameError", "name 'fail' is not defined"));
+        let client = livy.client();
+        let session_id = client.create_session(NewSessionRequestBuilder::new(SessionKind::Pyspark).build().unwrap()).unwrap().id.unwrap();
+        wait::wait_for_session(&client, session_id, Duration::from_millis(1)).unwrap();
+        let interrupted = AtomicBool::new(false);
+
+        let cells = parse("x = 1\n# %%\nx + 1\n", "#", DEFAULT_MARKER);
+        let report = run(&client, session_id, cells.clone(), Duration::from_millis(1), &interrupted);
+        assert!(report.is_success());
+        assert_eq!(cells, report.results.into_iter().map(|result| result.cell).collect::<Vec<Cell>>());
+
+        let cells = parse("x = 1\n# %%\nfail()\n# %%\nx + 1\n", "#", DEFAULT_MARKER);
+        let report = run(&client, session_id, cells.clone(), Duration::from_millis(1), &interrupted);
+        assert!(!report.is_success());
+        assert_eq!(2, report.results.len());
+        assert!(!report.results[1].statement.is_success());
+        assert_eq!(None, report.error);
+        assert_eq!(cells[2..].to_vec(), report.skipped);
+
+        // A failed request stops the execution without losing the results so
+        // far: the first cell is run and polled once before the failure.
+        livy.fail_after(2, 503, 1);
+        let report = run(&client, session_id, cells.clone(), Duration::from_millis(1), &interrupted);
+        assert!(!report.is_success());
+        assert_eq!(vec![cells[0].clone()], report.results.into_iter().map(|result| result.cell).collect::<Vec<Cell>>());
+        let error = report.error.unwrap();
+        assert_eq!(cells[1], error.cell);
+        assert!(error.error.contains("code: 503"), "{}", error.error);
+        assert_eq!(cells[2..].to_vec(), report.skipped);
+    }
+}
@@ -0,0 +1,266 @@
+use client::NewBatchRequest;
+use properties::{self, split_list, Precedence};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Options of `spark-submit` which take a value but have no counterpart
+/// in a Livy batch request
+const UNSUPPORTED_OPTIONS: &[&str] = &[
+    "--master",
+    "--deploy-mode",
+    "--packages",
+    "--exclude-packages",
+    "--repositories",
+    "--driver-java-options",
+    "--driver-library-path",
+    "--driver-class-path",
+    "--total-executor-cores",
+    "--principal",
+    "--keytab",
+    "--kill",
+    "--status",
+];
+
+/// Flags of `spark-submit` which have no counterpart in a Livy batch request
+const UNSUPPORTED_FLAGS: &[&str] = &["--supervise", "--verbose", "-v"];
+
+/// Result of translating a `spark-submit` command line
+#[derive(Debug, PartialEq)]
+pub struct SparkSubmit {
+    pub request: NewBatchRequest,
+    /// Options which were ignored because Livy cannot honor them,
+    /// e.g. `"--master yarn"`
+    pub unsupported: Vec<String>,
+}
+
+/// Translates the arguments of a `spark-submit` command line, excluding
+/// `spark-submit` itself, into a `NewBatchRequest`.
+///
+/// Arguments following the application file are passed to the application.
+/// The properties of `--properties-file` are applied as `spark-submit` does,
+/// filling in what the other options leave unset. Options such as
+/// `--master` or `--deploy-mode`, which are decided by the Livy server, are
+/// reported in `SparkSubmit::unsupported`.
+///
+/// # Examples
+/// ```
+/// use livy::spark_submit;
+///
+/// let args: Vec<String> = vec!["--master", "yarn", "--class", "com.example.Main",
+///                              "--conf", "spark.ui.enabled=false", "app.jar", "2018-01-01"]
+///     .into_iter().map(String::from).collect();
+/// let submit = spark_submit::parse(&args).unwrap();
+///
+/// assert_eq!("app.jar", submit.request.file);
+/// assert_eq!(Some("com.example.Main".to_string()), submit.request.class_name);
+/// assert_eq!(Some(vec!["2018-01-01".to_string()]), submit.request.args);
+/// assert_eq!(vec!["--master yarn".to_string()], submit.unsupported);
+/// ```
+pub fn parse(args: &[String]) -> Result<SparkSubmit, String> {
+    let mut request = NewBatchRequest {
+        file: String::new(),
+        proxy_user: None,
+        class_name: None,
+        args: None,
+        jars: None,
+        py_files: None,
+        files: None,
+        driver_memory: None,
+        driver_cores: None,
+        executor_memory: None,
+        executor_cores: None,
+        num_executors: None,
+        archives: None,
+        queue: None,
+        name: None,
+        conf: None,
+    };
+    let mut unsupported = Vec::new();
+    let mut properties_file = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') {
+            request.file = arg.clone();
+            let app_args: Vec<String> = args.cloned().collect();
+            if !app_args.is_empty() {
+                request.args = Some(app_args);
+            }
+            if let Some(path) = properties_file {
+                properties::apply_to_batch(&mut request, &properties::load(path)?, Precedence::Request)?;
+            }
+            return Ok(SparkSubmit { request, unsupported });
+        }
+
+        if UNSUPPORTED_FLAGS.contains(&arg.as_str()) {
+            unsupported.push(arg.clone());
+            continue;
+        }
+
+        let (option, value) = match arg.find('=') {
+            Some(i) if arg.starts_with("--") => (&arg[..i], arg[i + 1..].to_string()),
+            _ => {
+                let value = match args.next() {
+                    Some(value) => value.clone(),
+                    None => return Err(format!("option {} requires a value", arg)),
+                };
+                (arg.as_str(), value)
+            },
+        };
+
+        match option {
+            "--class" => request.class_name = Some(value),
+            "--name" => request.name = Some(value),
+            "--jars" => request.jars = Some(split_list(&value)),
+            "--py-files" => request.py_files = Some(split_list(&value)),
+            "--files" => request.files = Some(split_list(&value)),
+            "--archives" => request.archives = Some(split_list(&value)),
+            "--conf" | "-c" => {
+                let (key, value) = split_conf(&value)?;
+                request.conf.get_or_insert_with(HashMap::new).insert(key, value);
+            },
+            "--driver-memory" => request.driver_memory = Some(parse_value(option, &value)?),
+            "--driver-cores" => request.driver_cores = Some(parse_value(option, &value)?),
+            "--executor-memory" => request.executor_memory = Some(parse_value(option, &value)?),
+            "--executor-cores" => request.executor_cores = Some(parse_value(option, &value)?),
+            "--num-executors" => request.num_executors = Some(parse_value(option, &value)?),
+            "--queue" => request.queue = Some(value),
+            "--proxy-user" => request.proxy_user = Some(value),
+            "--properties-file" => properties_file = Some(value),
+            _ if UNSUPPORTED_OPTIONS.contains(&option) => unsupported.push(format!("{} {}", option, value)),
+            _ => return Err(format!("unknown option: {}", option)),
+        }
+    }
+
+    Err("application file is missing".to_string())
+}
+
+fn split_conf(value: &str) -> Result<(String, String), String> {
+    match value.find('=') {
+        Some(i) if i > 0 => Ok((value[..i].to_string(), value[i + 1..].to_string())),
+        _ => Err(format!("invalid --conf value: {}; expected KEY=VALUE", value)),
+    }
+}
+
+fn parse_value<T: FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid value for {}: {}", option, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use resource::MemorySize;
+    use std::env;
+    use std::fs;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let args = strings(&[
+            "--master", "yarn",
+            "--deploy-mode=cluster",
+            "--supervise",
+            "--name", "nightly",
+            "--jars", "a.jar, b.jar",
+            "--py-files", "lib.zip",
+            "--files", "app.conf",
+            "--archives", "env.tar.gz#env",
+            "--conf", "spark.ui.enabled=false",
+            "--conf=spark.app.id=x=y",
+            "--driver-memory", "4g",
+            "--driver-cores", "2",
+            "--executor-memory", "8g",
+            "--executor-cores", "4",
+            "--num-executors", "10",
+            "--queue", "etl",
+            "--proxy-user", "alice",
+            "main.py", "--date", "2018-01-01",
+        ]);
+
+        let mut conf = HashMap::new();
+        conf.insert("spark.ui.enabled".to_string(), "false".to_string());
+        conf.insert("spark.app.id".to_string(), "x=y".to_string());
+
+        assert_eq!(Ok(SparkSubmit {
+            request: NewBatchRequest {
+                file: "main.py".to_string(),
+                proxy_user: Some("alice".to_string()),
+                class_name: None,
+                args: Some(strings(&["--date", "2018-01-01"])),
+                jars: Some(strings(&["a.jar", "b.jar"])),
+                py_files: Some(strings(&["lib.zip"])),
+                files: Some(strings(&["app.conf"])),
+                driver_memory: Some(MemorySize::from_gib(4)),
+                driver_cores: Some(2),
+                executor_memory: Some(MemorySize::from_gib(8)),
+                executor_cores: Some(4),
+                num_executors: Some(10),
+                archives: Some(strings(&["env.tar.gz#env"])),
+                queue: Some("etl".to_string()),
+                name: Some("nightly".to_string()),
+                conf: Some(conf),
+            },
+            unsupported: strings(&["--master yarn", "--deploy-mode cluster", "--supervise"]),
+        }), parse(&args));
+    }
+
+    #[test]
+    fn test_parse_properties_file() {
+        let path = env::temp_dir().join(format!("livy-spark-submit-{}.conf", ::std::process::id()));
+        fs::write(&path, "spark.executor.memory 8g\nspark.yarn.queue etl\nspark.ui.enabled true\n").unwrap();
+
+        let args = strings(&["--properties-file", path.to_str().unwrap(), "--queue", "adhoc", "--conf", "spark.ui.enabled=false", "app.jar"]);
+        let submit = parse(&args);
+        fs::remove_file(&path).unwrap();
+        let request = submit.unwrap().request;
+
+        assert_eq!(Some(MemorySize::from_gib(8)), request.executor_memory);
+        assert_eq!(Some("adhoc".to_string()), request.queue);
+        assert_eq!(Some(&"false".to_string()), request.conf.unwrap().get("spark.ui.enabled"));
+
+        let err = parse(&strings(&["--properties-file", "/nonexistent/spark.conf", "app.jar"])).unwrap_err();
+        assert!(err.starts_with("/nonexistent/spark.conf"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        struct TestCase {
+            args: Vec<String>,
+            expected: String,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                args: strings(&["--class", "Main"]),
+                expected: "application file is missing".to_string(),
+            },
+            TestCase {
+                args: strings(&["--class"]),
+                expected: "option --class requires a value".to_string(),
+            },
+            TestCase {
+                args: strings(&["--num-executors", "many", "app.jar"]),
+                expected: "invalid value for --num-executors: many".to_string(),
+            },
+            TestCase {
+                args: strings(&["--driver-memory", "4gigs", "app.jar"]),
+                expected: "invalid value for --driver-memory: 4gigs".to_string(),
+            },
+            TestCase {
+                args: strings(&["--conf", "spark.ui.enabled", "app.jar"]),
+                expected: "invalid --conf value: spark.ui.enabled; expected KEY=VALUE".to_string(),
+            },
+            TestCase {
+                args: strings(&["--unknown", "x", "app.jar"]),
+                expected: "unknown option: --unknown".to_string(),
+            },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(Err(test_case.expected), parse(&test_case.args));
+        }
+    }
+}
@@ -0,0 +1,377 @@
+//! Loading session and batch requests from job spec files
+//!
+//! A job spec is a JSON, TOML or YAML file with a `batch` or a `session`
+//! table, which has the fields of `NewBatchRequest` or `NewSessionRequest`,
+//! and optional `profiles` which override some of the fields:
+//!
+//! ```toml
+//! [batch]
+//! file = "hdfs:///jobs/etl-${ETL_VERSION}.jar"
+//! className = "com.example.Etl"
+//! args = ["${RUN_DATE}"]
+//! queue = "default"
+//! executorMemory = "4g"
+//!
+//! [batch.conf]
+//! "spark.ui.enabled" = "false"
+//!
+//! [profiles.prod]
+//! queue = "production"
+//! executorMemory = "16g"
+//!
+//! [profiles.prod.conf]
+//! "spark.dynamicAllocation.enabled" = "true"
+//! ```
+//!
+//! Tables of a profile are merged into those of the request, other values
+//! replace the values of the request and `null`, where the format has it,
+//! removes them. Then `${NAME}` in string values is replaced with the
+//! environment variable `NAME`, `${NAME:-default}` falls back to `default`
+//! if `NAME` is not set, and `$$` is replaced with `$`.
+//!
+//! # Examples
+//! ```no_run
+//! use livy::spec::Loader;
+//!
+//! let request = Loader::new().profile("prod").load_batch("etl.toml").unwrap();
+//!
+//! assert_eq!(Some("production".to_string()), request.queue);
+//! ```
+
+use builder::{NewBatchRequestBuilder, NewSessionRequestBuilder};
+use client::{NewBatchRequest, NewSessionRequest};
+use format;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{self, Map, Value};
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+
+/// Loader of job spec files
+#[derive(Debug, Default)]
+pub struct Loader {
+    profile: Option<String>,
+    vars: Option<HashMap<String, String>>,
+}
+
+impl Loader {
+    /// Constructs a new `Loader` which applies no profile and interpolates
+    /// environment variables.
+    pub fn new() -> Loader {
+        Loader::default()
+    }
+
+    /// Applies the profile named `profile`.
+    pub fn profile(mut self, profile: &str) -> Loader {
+        self.profile = Some(profile.to_string());
+        self
+    }
+
+    /// Interpolates `vars` instead of the environment variables.
+    pub fn vars(mut self, vars: HashMap<String, String>) -> Loader {
+        self.vars = Some(vars);
+        self
+    }
+
+    /// Loads and validates the `batch` of the spec at `path`.
+    pub fn load_batch<P: AsRef<Path>>(&self, path: P) -> Result<NewBatchRequest, String> {
+        let path = path.as_ref();
+
+        format::load(path)
+            .and_then(|spec| self.batch(spec))
+            .map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    /// Loads and validates the `session` of the spec at `path`.
+    pub fn load_session<P: AsRef<Path>>(&self, path: P) -> Result<NewSessionRequest, String> {
+        let path = path.as_ref();
+
+        format::load(path)
+            .and_then(|spec| self.session(spec))
+            .map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    fn batch(&self, spec: Value) -> Result<NewBatchRequest, String> {
+        let request = self.request(spec, "batch")?;
+
+        NewBatchRequestBuilder::from_request(request).build().map_err(|err| format!("{}", err))
+    }
+
+    fn session(&self, spec: Value) -> Result<NewSessionRequest, String> {
+        let request = self.request(spec, "session")?;
+
+        NewSessionRequestBuilder::from_request(request).build().map_err(|err| format!("{}", err))
+    }
+
+    /// Returns the request in the table `section` of `spec` with the profile
+    /// applied and the variables interpolated.
+    fn request<T: DeserializeOwned + Serialize>(&self, spec: Value, section: &str) -> Result<T, String> {
+        let mut spec = match spec {
+            Value::Object(spec) => spec,
+            _ => return Err("the spec must be a table".to_string()),
+        };
+        if let Some(key) = spec.keys().find(|key| !["batch", "session", "profiles"].contains(&key.as_str())) {
+            return Err(format!("unknown table: {}", key));
+        }
+
+        let mut request = spec.remove(section).ok_or_else(|| format!("the spec has no {} table", section))?;
+        if !request.is_object() {
+            return Err(format!("{} must be a table", section));
+        }
+
+        if let Some(ref name) = self.profile {
+            let mut profiles = match spec.remove("profiles") {
+                Some(Value::Object(profiles)) => profiles,
+                Some(_) => return Err("profiles must be a table".to_string()),
+                None => Map::new(),
+            };
+            let profile = profiles.remove(name).ok_or_else(|| {
+                let names: Vec<&str> = profiles.keys().map(String::as_str).collect();
+                format!("unknown profile: {}; the spec has {}", name,
+                        if names.is_empty() { "no profiles".to_string() } else { names.join(", ") })
+            })?;
+            if !profile.is_object() {
+                return Err(format!("profile {} must be a table", name));
+            }
+            merge(&mut request, profile);
+        }
+
+        let lookup = |name: &str| match self.vars {
+            Some(ref vars) => vars.get(name).cloned(),
+            None => env::var(name).ok(),
+        };
+        interpolate_value(&mut request, &lookup)?;
+
+        let parsed: T = serde_json::from_value(request.clone()).map_err(|err| format!("{}: {}", section, err))?;
+        check_fields(&request, &serde_json::to_value(&parsed).map_err(|err| format!("{}", err))?)
+            .map_err(|err| format!("{}: {}", section, err))?;
+
+        Ok(parsed)
+    }
+}
+
+/// Merges the tables of `overlay` into those of `base` and replaces the
+/// other values of `base` with those of `overlay`. `null` removes a value.
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (&mut Value::Object(ref mut base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                if value.is_null() {
+                    base.remove(&key);
+                } else {
+                    merge(base.entry(key).or_insert(Value::Null), value);
+                }
+            }
+        },
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Interpolates every string in `value`.
+fn interpolate_value(value: &mut Value, lookup: &dyn Fn(&str) -> Option<String>) -> Result<(), String> {
+    match *value {
+        Value::String(ref mut s) => *s = interpolate(s, lookup)?,
+        Value::Array(ref mut values) => values.iter_mut().try_for_each(|value| interpolate_value(value, lookup))?,
+        Value::Object(ref mut values) => values.values_mut().try_for_each(|value| interpolate_value(value, lookup))?,
+        _ => {},
+    }
+
+    Ok(())
+}
+
+/// Replaces `${NAME}` and `${NAME:-default}` in `s` with the values of
+/// `lookup` and `$$` with `$`.
+fn interpolate(s: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = s;
+
+    while let Some(i) = rest.find('$') {
+        result.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        if let Some(after) = rest.strip_prefix("$$") {
+            result.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after.find('}').ok_or_else(|| format!("unterminated variable in {:?}", s))?;
+            let (name, default) = match after[..end].find(":-") {
+                Some(j) => (&after[..j], Some(&after[j + 2..end])),
+                None => (&after[..end], None),
+            };
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!("invalid variable name {:?} in {:?}", name, s));
+            }
+
+            match lookup(name).or_else(|| default.map(String::from)) {
+                Some(value) => result.push_str(&value),
+                None => return Err(format!("environment variable {} is not set", name)),
+            }
+            rest = &after[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+/// Returns an error naming the first field of `input` which is not in
+/// `parsed`, i.e. a field which the request does not have.
+fn check_fields(input: &Value, parsed: &Value) -> Result<(), String> {
+    let unknown = input.as_object()
+        .into_iter()
+        .flat_map(|fields| fields.iter())
+        .find(|&(key, value)| !value.is_null() && parsed.get(key).is_none());
+
+    match unknown {
+        Some((key, _)) => Err(format!("unknown field: {}", key)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "toml")]
+    use builder::NewBatchRequestBuilder;
+    #[cfg(feature = "yaml")]
+    use client::SessionKind;
+    #[cfg(any(feature = "toml", feature = "yaml"))]
+    use format::Format;
+
+    #[test]
+    fn test_interpolate() {
+        struct TestCase {
+            s: &'static str,
+            expected: Result<&'static str, String>,
+        }
+
+        let test_cases = vec![
+            TestCase { s: "plain", expected: Ok("plain") },
+            TestCase { s: "etl-${VERSION}.jar", expected: Ok("etl-1.2.jar") },
+            TestCase { s: "${QUEUE:-default}/${VERSION:-0}", expected: Ok("default/1.2") },
+            TestCase { s: "$$HOME costs $5", expected: Ok("$HOME costs $5") },
+            TestCase { s: "${QUEUE}", expected: Err("environment variable QUEUE is not set".to_string()) },
+            TestCase { s: "${VERSION", expected: Err("unterminated variable in \"${VERSION\"".to_string()) },
+            TestCase { s: "${A B}", expected: Err("invalid variable name \"A B\" in \"${A B}\"".to_string()) },
+        ];
+
+        let lookup = |name: &str| if name == "VERSION" { Some("1.2".to_string()) } else { None };
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected.map(String::from), interpolate(test_case.s, &lookup), "{}", test_case.s);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_batch() {
+        let toml = r#"
+            [batch]
+            file = "hdfs:///jobs/etl-${VERSION}.jar"
+            className = "com.example.Etl"
+            queue = "default"
+            executorMemory = "4g"
+            numExecutors = 2
+
+            [batch.conf]
+            "spark.ui.enabled" = "false"
+
+            [profiles.prod]
+            queue = "production"
+            executorMemory = "16g"
+
+            [profiles.prod.conf]
+            "spark.dynamicAllocation.enabled" = "true"
+
+            [profiles.dev]
+            queue = ""
+        "#;
+
+        struct TestCase {
+            s: &'static str,
+            profile: Option<&'static str>,
+            expected: Result<NewBatchRequest, String>,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                s: toml,
+                profile: None,
+                expected: Ok(NewBatchRequestBuilder::new("hdfs:///jobs/etl-1.2.jar")
+                    .class_name("com.example.Etl")
+                    .queue("default")
+                    .executor_memory("4g")
+                    .num_executors(2)
+                    .conf("spark.ui.enabled", "false")
+                    .build()
+                    .unwrap()),
+            },
+            TestCase {
+                s: toml,
+                profile: Some("prod"),
+                expected: Ok(NewBatchRequestBuilder::new("hdfs:///jobs/etl-1.2.jar")
+                    .class_name("com.example.Etl")
+                    .queue("production")
+                    .executor_memory("16g")
+                    .num_executors(2)
+                    .conf("spark.ui.enabled", "false")
+                    .conf("spark.dynamicAllocation.enabled", "true")
+                    .build()
+                    .unwrap()),
+            },
+            TestCase {
+                s: toml,
+                profile: Some("dev"),
+                expected: Err("invalid request: queue: must not be empty".to_string()),
+            },
+            TestCase {
+                s: toml,
+                profile: Some("staging"),
+                expected: Err("unknown profile: staging; the spec has dev, prod".to_string()),
+            },
+            TestCase {
+                s: "[batch]\nfile = \"app.jar\"\nqueu = \"default\"\n",
+                profile: None,
+                expected: Err("batch: unknown field: queu".to_string()),
+            },
+            TestCase {
+                s: "[batch]\nfile = \"app.jar\"\nnumExecutors = \"many\"\n",
+                profile: None,
+                expected: Err("batch: invalid type: string \"many\", expected i64".to_string()),
+            },
+            TestCase {
+                s: "[session]\nkind = \"pyspark\"\n",
+                profile: None,
+                expected: Err("the spec has no batch table".to_string()),
+            },
+        ];
+
+        let vars: HashMap<String, String> = vec![("VERSION".to_string(), "1.2".to_string())].into_iter().collect();
+
+        for test_case in test_cases {
+            let mut loader = Loader::new().vars(vars.clone());
+            if let Some(profile) = test_case.profile {
+                loader = loader.profile(profile);
+            }
+
+            let spec = format::parse(test_case.s, Format::Toml).unwrap();
+            assert_eq!(test_case.expected, loader.batch(spec), "{:?}", test_case.profile);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "yaml")]
+    fn test_session() {
+        let yaml = "session:\n  kind: pyspark\n  driverMemory: 2g\n  queue: default\n\
+                    profiles:\n  prod:\n    driverMemory: 8g\n    queue: ~\n";
+        let spec = format::parse(yaml, Format::Yaml).unwrap();
+
+        let request = Loader::new().profile("prod").session(spec).unwrap();
+
+        assert_eq!(NewSessionRequestBuilder::new(SessionKind::Pyspark).driver_memory("8g").build().unwrap(), request);
+    }
+}
@@ -0,0 +1,500 @@
+//! Statement code with placeholders for safely encoded values
+//!
+//! A placeholder such as `{{table}}` stands for a whole expression and is
+//! replaced with a literal of its parameter in the language of the session,
+//! so that user values cannot change the code around them. Placeholders must
+//! not be put inside string literals of the template. `{{` which is not
+//! followed by a name and `}}` is left as is.
+//!
+//! Spark SQL strings are escaped with backslashes, which assumes that
+//! `spark.sql.parser.escapedStringLiterals` is disabled, as it is by default.
+//!
+//! # Examples
+//! ```
+//! use livy::client::SessionKind;
+//! use livy::template::Template;
+//!
+//! let template = Template::new("spark.table({{table}}).where(col(\"day\").isin({{days}}))")
+//!     .param("table", "logs\"; sys.exit(1); \"")
+//!     .param("days", vec!["2018-01-01", "2018-01-02"]);
+//!
+//! assert_eq!(
+//!     Ok(r#"spark.table(u"logs\"; sys.exit(1); \"").where(col("day").isin([u"2018-01-01", u"2018-01-02"]))"#.to_string()),
+//!     template.render(SessionKind::Pyspark));
+//! ```
+
+use client::{RunStatementRequest, SessionKind};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+
+/// Largest integer which a double represents exactly, as R numbers are
+const MAX_EXACT_DOUBLE: i64 = 1 << 53;
+
+/// Value of a template parameter
+#[derive(Clone, Debug, PartialEq)]
+pub enum Param {
+    Null,
+    Bool(bool),
+    Int(i64),
+    /// Finite number; NaN and infinities are refused on rendering
+    Float(f64),
+    Str(String),
+    List(Vec<Param>),
+    Map(BTreeMap<String, Param>),
+}
+
+impl From<bool> for Param {
+    fn from(b: bool) -> Param {
+        Param::Bool(b)
+    }
+}
+
+impl From<i32> for Param {
+    fn from(n: i32) -> Param {
+        Param::Int(i64::from(n))
+    }
+}
+
+impl From<i64> for Param {
+    fn from(n: i64) -> Param {
+        Param::Int(n)
+    }
+}
+
+impl From<f64> for Param {
+    fn from(x: f64) -> Param {
+        Param::Float(x)
+    }
+}
+
+impl<'a> From<&'a str> for Param {
+    fn from(s: &'a str) -> Param {
+        Param::Str(s.to_string())
+    }
+}
+
+impl From<String> for Param {
+    fn from(s: String) -> Param {
+        Param::Str(s)
+    }
+}
+
+impl<T: Into<Param>> From<Option<T>> for Param {
+    fn from(value: Option<T>) -> Param {
+        value.map_or(Param::Null, Into::into)
+    }
+}
+
+impl<T: Into<Param>> From<Vec<T>> for Param {
+    fn from(values: Vec<T>) -> Param {
+        Param::List(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<K: Into<String>, V: Into<Param>> From<BTreeMap<K, V>> for Param {
+    fn from(values: BTreeMap<K, V>) -> Param {
+        Param::Map(values.into_iter().map(|(key, value)| (key.into(), value.into())).collect())
+    }
+}
+
+/// Statement code with named placeholders and their values
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    code: String,
+    params: BTreeMap<String, Param>,
+}
+
+impl Template {
+    /// Constructs a new `Template` of `code` without parameters.
+    pub fn new<S: Into<String>>(code: S) -> Template {
+        Template {
+            code: code.into(),
+            params: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the value of the placeholder `{{name}}`.
+    pub fn param<V: Into<Param>>(mut self, name: &str, value: V) -> Template {
+        self.params.insert(name.to_string(), value.into());
+        self
+    }
+
+    /// Replaces the placeholders with literals in the language of `kind`.
+    ///
+    /// Returns an error if a placeholder has no value, a parameter is not
+    /// used or a value cannot be encoded in the language.
+    pub fn render(&self, kind: SessionKind) -> Result<String, String> {
+        let mut code = String::new();
+        let mut used = BTreeSet::new();
+        let mut rest = self.code.as_str();
+
+        while let Some(i) = rest.find("{{") {
+            code.push_str(&rest[..i]);
+            rest = &rest[i..];
+
+            match placeholder(rest) {
+                Some((name, len)) => {
+                    let value = self.params.get(name).ok_or_else(|| format!("no value for the placeholder {{{{{}}}}}", name))?;
+                    code.push_str(&literal(value, kind).map_err(|err| format!("parameter {}: {}", name, err))?);
+                    used.insert(name);
+                    rest = &rest[len..];
+                },
+                None => {
+                    code.push_str("{{");
+                    rest = &rest[2..];
+                },
+            }
+        }
+        code.push_str(rest);
+
+        if let Some(name) = self.params.keys().find(|name| !used.contains(name.as_str())) {
+            return Err(format!("unused parameter: {}", name));
+        }
+
+        Ok(code)
+    }
+
+    /// Renders the template into a request to run it in a session of `kind`.
+    pub fn statement(&self, kind: SessionKind) -> Result<RunStatementRequest, String> {
+        Ok(RunStatementRequest { code: self.render(kind)? })
+    }
+}
+
+/// Returns the name of the placeholder at the start of `s` and its length.
+fn placeholder(s: &str) -> Option<(&str, usize)> {
+    let end = s.find("}}")?;
+    let name = s[2..end].trim();
+    let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if valid { Some((name, end + 2)) } else { None }
+}
+
+/// Returns the literal of `value` in the language of `kind`.
+pub fn literal(value: &Param, kind: SessionKind) -> Result<String, String> {
+    match kind {
+        SessionKind::Spark => scala(value),
+        SessionKind::Pyspark | SessionKind::Pyspark3 => python(value),
+        SessionKind::Sparkr => r(value),
+        SessionKind::Sql => sql(value),
+        SessionKind::Shared => Err("shared sessions have no single language; render for the kind of the statement".to_string()),
+    }
+}
+
+fn scala(value: &Param) -> Result<String, String> {
+    Ok(match *value {
+        Param::Null => "null".to_string(),
+        Param::Bool(b) => b.to_string(),
+        Param::Int(n) if i32::try_from(n).is_ok() => negative(n.to_string()),
+        Param::Int(n) => negative(format!("{}L", n)),
+        Param::Float(x) => negative(float(x)?),
+        // Scala 2 turns `\u` escapes into characters before reading the
+        // literal, so only the C1 controls and DEL are escaped that way.
+        Param::Str(ref s) => quote(s, '"', |c| match c {
+            '\u{8}' => Ok("\\b".to_string()),
+            '\u{c}' => Ok("\\f".to_string()),
+            c if (c as u32) < 0x20 => Err(format!("Scala strings cannot contain the control character \\u{:04x}", c as u32)),
+            c if c.is_control() => Ok(format!("\\u{:04x}", c as u32)),
+            c => Ok(c.to_string()),
+        })?,
+        Param::List(ref values) => format!("Seq({})", join(values, scala)?),
+        Param::Map(ref values) => format!("Map({})", join_entries(values, |key, value| {
+            Ok(format!("{} -> {}", scala(&Param::Str(key.to_string()))?, scala(value)?))
+        })?),
+    })
+}
+
+fn python(value: &Param) -> Result<String, String> {
+    Ok(match *value {
+        Param::Null => "None".to_string(),
+        Param::Bool(true) => "True".to_string(),
+        Param::Bool(false) => "False".to_string(),
+        Param::Int(n) => negative(n.to_string()),
+        Param::Float(x) => negative(float(x)?),
+        // Non-ASCII characters are escaped and the literal is a unicode
+        // string so that it means the same in Python 2 and 3.
+        Param::Str(ref s) => format!("u{}", quote(s, '"', |c| match c as u32 {
+            0x20..=0x7e => Ok(c.to_string()),
+            n if n > 0xffff => Ok(format!("\\U{:08x}", n)),
+            n => Ok(format!("\\u{:04x}", n)),
+        })?),
+        Param::List(ref values) => format!("[{}]", join(values, python)?),
+        Param::Map(ref values) => format!("{{{}}}", join_entries(values, |key, value| {
+            Ok(format!("{}: {}", python(&Param::Str(key.to_string()))?, python(value)?))
+        })?),
+    })
+}
+
+fn r(value: &Param) -> Result<String, String> {
+    Ok(match *value {
+        Param::Null => "NULL".to_string(),
+        Param::Bool(true) => "TRUE".to_string(),
+        Param::Bool(false) => "FALSE".to_string(),
+        // R integers have 32 bits and other numbers are doubles.
+        Param::Int(n) if i32::try_from(n).is_ok() => negative(format!("{}L", n)),
+        Param::Int(n) if n.unsigned_abs() <= MAX_EXACT_DOUBLE as u64 => negative(n.to_string()),
+        Param::Int(n) => return Err(format!("{} cannot be represented exactly in R", n)),
+        Param::Float(x) => negative(float(x)?),
+        Param::Str(ref s) => quote(s, '"', |c| match c {
+            '\0' => Err("R strings cannot contain NUL".to_string()),
+            c if c.is_control() => Ok(format!("\\u{{{:04x}}}", c as u32)),
+            c => Ok(c.to_string()),
+        })?,
+        Param::List(ref values) => format!("list({})", join(values, r)?),
+        Param::Map(ref values) => format!("list({})", join_entries(values, |key, value| {
+            Ok(format!("{} = {}", r(&Param::Str(key.to_string()))?, r(value)?))
+        })?),
+    })
+}
+
+fn sql(value: &Param) -> Result<String, String> {
+    Ok(match *value {
+        Param::Null => "NULL".to_string(),
+        Param::Bool(true) => "TRUE".to_string(),
+        Param::Bool(false) => "FALSE".to_string(),
+        Param::Int(n) => negative(n.to_string()),
+        // Numbers with a fraction are decimals in Spark SQL without `D`.
+        Param::Float(x) => negative(format!("{}D", float(x)?)),
+        Param::Str(ref s) => quote(s, '\'', |c| match c {
+            '\0' => Err("Spark SQL strings cannot contain NUL".to_string()),
+            c if c.is_control() => Ok(format!("\\u{:04x}", c as u32)),
+            c => Ok(c.to_string()),
+        })?,
+        Param::List(ref values) => format!("array({})", join(values, sql)?),
+        Param::Map(ref values) => format!("map({})", join_entries(values, |key, value| {
+            Ok(format!("{}, {}", sql(&Param::Str(key.to_string()))?, sql(value)?))
+        })?),
+    })
+}
+
+/// Wraps a negative number in parentheses so that it cannot merge with a
+/// preceding operator, e.g. into the SQL comment `--`.
+fn negative(number: String) -> String {
+    if number.starts_with('-') { format!("({})", number) } else { number }
+}
+
+/// Returns the shortest representation of `x` which reads back as `x` and
+/// has a fraction or an exponent.
+fn float(x: f64) -> Result<String, String> {
+    if !x.is_finite() {
+        return Err(format!("{} cannot be encoded", x));
+    }
+
+    Ok(format!("{:?}", x))
+}
+
+/// Quotes `s` with `quote`, escaping the quote, backslashes and common
+/// control characters, and the other characters with `escape`.
+fn quote<F>(s: &str, quote: char, escape: F) -> Result<String, String> where F: Fn(char) -> Result<String, String> {
+    let mut literal = quote.to_string();
+
+    for c in s.chars() {
+        match c {
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c if c == quote => {
+                literal.push('\\');
+                literal.push(c);
+            },
+            c => literal.push_str(&escape(c)?),
+        }
+    }
+    literal.push(quote);
+
+    Ok(literal)
+}
+
+fn join(values: &[Param], literal: fn(&Param) -> Result<String, String>) -> Result<String, String> {
+    let literals: Result<Vec<String>, String> = values.iter().map(literal).collect();
+
+    Ok(literals?.join(", "))
+}
+
+fn join_entries<F>(values: &BTreeMap<String, Param>, entry: F) -> Result<String, String>
+    where F: Fn(&str, &Param) -> Result<String, String> {
+    let entries: Result<Vec<String>, String> = values.iter().map(|(key, value)| entry(key, value)).collect();
+
+    Ok(entries?.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64;
+
+    #[test]
+    fn test_literal() {
+        struct TestCase {
+            value: Param,
+            expected_scala: Result<&'static str, &'static str>,
+            expected_python: Result<&'static str, &'static str>,
+            expected_r: Result<&'static str, &'static str>,
+            expected_sql: Result<&'static str, &'static str>,
+        }
+
+        let map: BTreeMap<&str, Param> = vec![("a", Param::Int(1)), ("b'\"", Param::Null)].into_iter().collect();
+
+        let test_cases = vec![
+            TestCase {
+                value: Param::Null,
+                expected_scala: Ok("null"),
+                expected_python: Ok("None"),
+                expected_r: Ok("NULL"),
+                expected_sql: Ok("NULL"),
+            },
+            TestCase {
+                value: Param::from(true),
+                expected_scala: Ok("true"),
+                expected_python: Ok("True"),
+                expected_r: Ok("TRUE"),
+                expected_sql: Ok("TRUE"),
+            },
+            TestCase {
+                value: Param::from(-5),
+                expected_scala: Ok("(-5)"),
+                expected_python: Ok("(-5)"),
+                expected_r: Ok("(-5L)"),
+                expected_sql: Ok("(-5)"),
+            },
+            TestCase {
+                value: Param::from(1i64 << 40),
+                expected_scala: Ok("1099511627776L"),
+                expected_python: Ok("1099511627776"),
+                expected_r: Ok("1099511627776"),
+                expected_sql: Ok("1099511627776"),
+            },
+            TestCase {
+                value: Param::from(i64::MAX),
+                expected_scala: Ok("9223372036854775807L"),
+                expected_python: Ok("9223372036854775807"),
+                expected_r: Err("9223372036854775807 cannot be represented exactly in R"),
+                expected_sql: Ok("9223372036854775807"),
+            },
+            TestCase {
+                value: Param::from(i64::MIN),
+                expected_scala: Ok("(-9223372036854775808L)"),
+                expected_python: Ok("(-9223372036854775808)"),
+                expected_r: Err("-9223372036854775808 cannot be represented exactly in R"),
+                expected_sql: Ok("(-9223372036854775808)"),
+            },
+            TestCase {
+                value: Param::from(2.5),
+                expected_scala: Ok("2.5"),
+                expected_python: Ok("2.5"),
+                expected_r: Ok("2.5"),
+                expected_sql: Ok("2.5D"),
+            },
+            TestCase {
+                value: Param::from(1e100),
+                expected_scala: Ok("1e100"),
+                expected_python: Ok("1e100"),
+                expected_r: Ok("1e100"),
+                expected_sql: Ok("1e100D"),
+            },
+            TestCase {
+                value: Param::from(f64::NAN),
+                expected_scala: Err("NaN cannot be encoded"),
+                expected_python: Err("NaN cannot be encoded"),
+                expected_r: Err("NaN cannot be encoded"),
+                expected_sql: Err("NaN cannot be encoded"),
+            },
+            TestCase {
+                value: Param::from("it's \"x\"\\\n\u{7}é😀"),
+                expected_scala: Err("Scala strings cannot contain the control character \\u0007"),
+                expected_python: Ok("u\"it's \\\"x\\\"\\\\\\n\\u0007\\u00e9\\U0001f600\""),
+                expected_r: Ok("\"it's \\\"x\\\"\\\\\\n\\u{0007}é😀\""),
+                expected_sql: Ok("'it\\'s \"x\"\\\\\\n\\u0007é😀'"),
+            },
+            TestCase {
+                value: Param::from("\0"),
+                expected_scala: Err("Scala strings cannot contain the control character \\u0000"),
+                expected_python: Ok("u\"\\u0000\""),
+                expected_r: Err("R strings cannot contain NUL"),
+                expected_sql: Err("Spark SQL strings cannot contain NUL"),
+            },
+            TestCase {
+                value: Param::from("\u{8}\u{c}\u{7f}\u{85}"),
+                expected_scala: Ok("\"\\b\\f\\u007f\\u0085\""),
+                expected_python: Ok("u\"\\u0008\\u000c\\u007f\\u0085\""),
+                expected_r: Ok("\"\\u{0008}\\u{000c}\\u{007f}\\u{0085}\""),
+                expected_sql: Ok("'\\u0008\\u000c\\u007f\\u0085'"),
+            },
+            TestCase {
+                value: Param::from(vec![Param::from("a"), Param::from(vec![1, 2])]),
+                expected_scala: Ok("Seq(\"a\", Seq(1, 2))"),
+                expected_python: Ok("[u\"a\", [1, 2]]"),
+                expected_r: Ok("list(\"a\", list(1L, 2L))"),
+                expected_sql: Ok("array('a', array(1, 2))"),
+            },
+            TestCase {
+                value: Param::from(map),
+                expected_scala: Ok("Map(\"a\" -> 1, \"b'\\\"\" -> null)"),
+                expected_python: Ok("{u\"a\": 1, u\"b'\\\"\": None}"),
+                expected_r: Ok("list(\"a\" = 1L, \"b'\\\"\" = NULL)"),
+                expected_sql: Ok("map('a', 1, 'b\\'\"', NULL)"),
+            },
+        ];
+
+        for test_case in test_cases {
+            let expected = vec![
+                (SessionKind::Spark, test_case.expected_scala),
+                (SessionKind::Pyspark, test_case.expected_python),
+                (SessionKind::Sparkr, test_case.expected_r),
+                (SessionKind::Sql, test_case.expected_sql),
+            ];
+
+            for (kind, expected) in expected {
+                assert_eq!(expected.map(String::from).map_err(String::from), literal(&test_case.value, kind),
+                           "{:?} {:?}", kind, test_case.value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        struct TestCase {
+            template: Template,
+            kind: SessionKind,
+            expected: Result<&'static str, &'static str>,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                template: Template::new("SELECT * FROM logs WHERE day = {{ day }} AND n > {{n}}").param("day", "x' OR '1'='1").param("n", -1),
+                kind: SessionKind::Sql,
+                expected: Ok("SELECT * FROM logs WHERE day = 'x\\' OR \\'1\\'=\\'1' AND n > (-1)"),
+            },
+            TestCase {
+                template: Template::new("val m = Map(1 -> 2); {{{{not a placeholder}} {{x}}").param("x", "}}"),
+                kind: SessionKind::Spark,
+                expected: Ok("val m = Map(1 -> 2); {{{{not a placeholder}} \"}}\""),
+            },
+            TestCase {
+                template: Template::new("print({{x}})"),
+                kind: SessionKind::Pyspark,
+                expected: Err("no value for the placeholder {{x}}"),
+            },
+            TestCase {
+                template: Template::new("print(1)").param("x", 1),
+                kind: SessionKind::Pyspark,
+                expected: Err("unused parameter: x"),
+            },
+            TestCase {
+                template: Template::new("x <- {{x}}").param("x", f64::INFINITY),
+                kind: SessionKind::Sparkr,
+                expected: Err("parameter x: inf cannot be encoded"),
+            },
+            TestCase {
+                template: Template::new("{{x}}").param("x", 1),
+                kind: SessionKind::Shared,
+                expected: Err("parameter x: shared sessions have no single language; render for the kind of the statement"),
+            },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected.map(String::from).map_err(String::from), test_case.template.render(test_case.kind));
+        }
+    }
+}
@@ -0,0 +1,853 @@
+//! In-process fake Livy server for testing code built on `client::Client`
+//!
+//! `FakeLivy` serves the REST endpoints used by the client from an
+//! in-memory state machine on a local port:
+//!
+//! * sessions start in `starting` and become `idle` after being polled
+//!   `startup_polls` times,
+//! * statements start in `waiting` and become `available` after being
+//!   polled `statement_polls` times, with the output scripted by
+//!   `on_statement` or an empty `text/plain` result, and report their
+//!   `started` and `completed` times like Livy 0.7,
+//! * batches go from `starting` through `running` to `batch_outcome`
+//!   (`success` by default), advancing once per `batch_polls` polls.
+//!
+//! Failures are injected with `fail_next` and `fail_after`, which answer
+//! the next requests with an error status, and
+//! `set_session_state`/`set_batch_state`, which simulate e.g. a session
+//! dying on the cluster.
+//!
+//! # Examples
+//! ```
+//! use livy::builder::NewSessionRequestBuilder;
+//! use livy::client::{RunStatementRequest, SessionKind};
+//! use livy::testing::FakeLivy;
+//! use livy::wait;
+//! use std::sync::atomic::AtomicBool;
+//! use std::time::Duration;
+//!
+//! let livy = FakeLivy::start();
+//! livy.on_statement("1 + 1", FakeLivy::text_output("2"));
+//!
+//! let client = livy.client();
+//! let session = client.create_session(NewSessionRequestBuilder::new(SessionKind::Pyspark).build().unwrap()).unwrap();
+//! let session_id = session.id.unwrap();
+//! wait::wait_for_session(&client, session_id, Duration::from_millis(1)).unwrap();
+//!
+//! let statement = client.run_statement(session_id, RunStatementRequest { code: "1 + 1".to_string() }).unwrap();
+//! let statement = wait::wait_for_statement(&client, session_id, statement.id.unwrap(),
+//!                                          Duration::from_millis(1), &AtomicBool::new(false)).unwrap();
+//!
+//! assert!(statement.is_success());
+//! ```
+
+use client::{Client, SessionKind, SessionState, StatementOutput};
+use http;
+use serde_json::{self, Value};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// HTTP request received by `FakeLivy`
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Path including the query string, e.g. `/sessions?from=0`
+    pub path: String,
+    /// Headers in order of appearance, with lowercase names
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    /// Returns the value of the first header named `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+
+        self.headers.iter().find(|(key, _)| *key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+struct FakeSession {
+    kind: Value,
+    name: Option<String>,
+    state: SessionState,
+    proxy_user: Option<String>,
+    polls_until_idle: u32,
+    log: Vec<String>,
+    statements: Vec<FakeStatement>,
+}
+
+struct FakeStatement {
+    code: String,
+    state: &'static str,
+    polls_until_available: u32,
+    output: Option<Value>,
+    started: Option<i64>,
+    completed: Option<i64>,
+}
+
+struct FakeBatch {
+    state: String,
+    outcome: String,
+    name: Option<String>,
+    proxy_user: Option<String>,
+    polls_until_next_state: u32,
+    log: Vec<String>,
+}
+
+struct State {
+    next_session_id: i64,
+    next_batch_id: i64,
+    sessions: BTreeMap<i64, FakeSession>,
+    batches: BTreeMap<i64, FakeBatch>,
+    outputs: HashMap<String, Value>,
+    /// Statuses answering the next requests, or `None` to serve one
+    failures: VecDeque<Option<u32>>,
+    requests: Vec<RecordedRequest>,
+    startup_polls: u32,
+    statement_polls: u32,
+    batch_polls: u32,
+    batch_outcome: String,
+    batch_outcomes: HashMap<String, VecDeque<String>>,
+    session_cookie: Option<String>,
+    next_cookie_id: u32,
+    valid_cookies: Vec<String>,
+}
+
+/// Fake Livy server listening on `127.0.0.1`
+///
+/// The server is stopped when the value is dropped.
+pub struct FakeLivy {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl FakeLivy {
+    /// Starts a new server on a free port.
+    ///
+    /// # Panics
+    /// Panics if no local port can be bound.
+    pub fn start() -> FakeLivy {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind a local port");
+        let addr = listener.local_addr().expect("failed to get the local address");
+        let state = Arc::new(Mutex::new(State {
+            next_session_id: 0,
+            next_batch_id: 0,
+            sessions: BTreeMap::new(),
+            batches: BTreeMap::new(),
+            outputs: HashMap::new(),
+            failures: VecDeque::new(),
+            requests: Vec::new(),
+            startup_polls: 1,
+            statement_polls: 1,
+            batch_polls: 1,
+            batch_outcome: "success".to_string(),
+            batch_outcomes: HashMap::new(),
+            session_cookie: None,
+            next_cookie_id: 0,
+            valid_cookies: Vec::new(),
+        }));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let handle = {
+            let state = Arc::clone(&state);
+            let shutdown = Arc::clone(&shutdown);
+
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if shutdown.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        // A broken connection only affects the client which made it.
+                        let _ = serve(stream, &state);
+                    }
+                }
+            })
+        };
+
+        FakeLivy {
+            addr,
+            state,
+            shutdown,
+            handle: Some(handle),
+        }
+    }
+
+    /// Returns the base URL of the server, e.g. `http://127.0.0.1:12345`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Constructs a `Client` of the server.
+    pub fn client(&self) -> Client {
+        Client::new(&self.url(), None, None)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Makes statements whose code is equal to `code` produce `output`.
+    pub fn on_statement<S: Into<String>>(&self, code: S, output: StatementOutput) {
+        let output = serde_json::to_value(output).expect("failed to serialize a statement output");
+
+        self.lock().outputs.insert(code.into(), output);
+    }
+
+    /// Returns a successful output whose `text/plain` data is `text`.
+    pub fn text_output(text: &str) -> StatementOutput {
+        let mut data = HashMap::new();
+        data.insert("text/plain".to_string(), Value::from(text));
+
+        StatementOutput {
+            status: Some("ok".to_string()),
+            execution_count: None,
+            data: Some(data),
+            ename: None,
+            evalue: None,
+            traceback: None,
+        }
+    }
+
+    /// Returns an error output such as an uncaught exception produces.
+    pub fn error_output(ename: &str, evalue: &str) -> StatementOutput {
+        StatementOutput {
+            status: Some("error".to_string()),
+            execution_count: None,
+            data: None,
+            ename: Some(ename.to_string()),
+            evalue: Some(evalue.to_string()),
+            traceback: Some(vec![format!("{}: {}\n", ename, evalue)]),
+        }
+    }
+
+    /// Answers the next `count` requests with `status` instead of serving them.
+    pub fn fail_next(&self, status: u32, count: usize) {
+        self.fail_after(0, status, count);
+    }
+
+    /// Serves the next `requests` requests and answers the `count` requests
+    /// after them with `status`.
+    pub fn fail_after(&self, requests: usize, status: u32, count: usize) {
+        let mut state = self.lock();
+
+        state.failures.extend((0..requests).map(|_| None));
+        state.failures.extend((0..count).map(|_| Some(status)));
+    }
+
+    /// Sets how many times a new session is polled before it becomes idle.
+    pub fn set_startup_polls(&self, polls: u32) {
+        self.lock().startup_polls = polls;
+    }
+
+    /// Sets how many times a new statement is polled before it becomes available.
+    pub fn set_statement_polls(&self, polls: u32) {
+        self.lock().statement_polls = polls;
+    }
+
+    /// Sets how many times a batch is polled before it advances to its next state.
+    pub fn set_batch_polls(&self, polls: u32) {
+        self.lock().batch_polls = polls;
+    }
+
+    /// Sets the state which new batches finish in, e.g. `"dead"`.
+    pub fn set_batch_outcome(&self, state: &str) {
+        self.lock().batch_outcome = state.to_string();
+    }
+
+    /// Makes the next batch whose file is equal to `file` finish in `state`
+    /// instead of the outcome set by `set_batch_outcome`. Outcomes of the
+    /// same file are used in the order they were added.
+    pub fn on_batch(&self, file: &str, state: &str) {
+        self.lock().batch_outcomes.entry(file.to_string()).or_default().push_back(state.to_string());
+    }
+
+    /// Makes the server behave like an Apache Knox gateway, which issues a
+    /// session cookie named `name` to requests with an `Authorization`
+    /// header and rejects requests with neither the header nor a valid cookie.
+    pub fn set_session_cookie(&self, name: &str) {
+        self.lock().session_cookie = Some(name.to_string());
+    }
+
+    /// Invalidates the session cookies issued so far.
+    pub fn expire_session_cookies(&self) {
+        self.lock().valid_cookies.clear();
+    }
+
+    /// Forces the state of a session, e.g. to simulate it dying.
+    pub fn set_session_state(&self, session_id: i64, state: SessionState) {
+        if let Some(session) = self.lock().sessions.get_mut(&session_id) {
+            session.state = state;
+            session.polls_until_idle = 0;
+        }
+    }
+
+    /// Forces the state of a batch.
+    pub fn set_batch_state(&self, batch_id: i64, state: &str) {
+        if let Some(batch) = self.lock().batches.get_mut(&batch_id) {
+            batch.state = state.to_string();
+        }
+    }
+
+    /// Appends a line to the log of a session.
+    pub fn push_session_log(&self, session_id: i64, line: &str) {
+        if let Some(session) = self.lock().sessions.get_mut(&session_id) {
+            session.log.push(line.to_string());
+        }
+    }
+
+    /// Appends a line to the log of a batch.
+    pub fn push_batch_log(&self, batch_id: i64, line: &str) {
+        if let Some(batch) = self.lock().batches.get_mut(&batch_id) {
+            batch.log.push(line.to_string());
+        }
+    }
+
+    /// Returns the requests received so far.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.lock().requests.clone()
+    }
+
+    /// Returns the current state of a session.
+    pub fn session_state(&self, session_id: i64) -> Option<SessionState> {
+        self.lock().sessions.get(&session_id).map(|session| session.state)
+    }
+
+    /// Returns the current state of a batch.
+    pub fn batch_state(&self, batch_id: i64) -> Option<String> {
+        self.lock().batches.get(&batch_id).map(|batch| batch.state.clone())
+    }
+}
+
+impl Drop for FakeLivy {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wakes up the accept loop so that it sees the shutdown flag.
+        let _ = TcpStream::connect(self.addr);
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn serve(stream: TcpStream, state: &Mutex<State>) -> Result<(), String> {
+    let mut reader = BufReader::new(stream.try_clone().map_err(|err| format!("{}", err))?);
+    let mut stream = stream;
+    let request = match read_request(&mut reader, &mut stream)? {
+        Some(request) => request,
+        None => return Ok(()),
+    };
+
+    let mut headers = Vec::new();
+    let (status, body) = {
+        let mut state = state.lock().unwrap_or_else(|err| err.into_inner());
+        state.requests.push(request.clone());
+
+        match state.failures.pop_front() {
+            Some(Some(status)) => (status, json!({"msg": "injected failure"})),
+            _ if authenticate(&mut state, &request, &mut headers) => {
+                let (status, body) = route(&mut state, &request);
+                // Livy points to created sessions, statements and batches.
+                if let (201, Some(id)) = (status, body.get("id").and_then(Value::as_i64)) {
+                    headers.push(format!("Location: {}/{}", request.path.split('?').next().unwrap_or(""), id));
+                }
+                (status, body)
+            },
+            _ => (401, json!({"msg": "authentication required"})),
+        }
+    };
+
+    let body = body.to_string();
+    let headers: String = headers.iter().map(|header| format!("{}\r\n", header)).collect();
+    write!(stream,
+           "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+           status, reason(status), body.len(), headers, body)
+        .map_err(|err| format!("{}", err))
+}
+
+/// Checks the credentials of a request if session cookies are enabled and
+/// adds a `Set-Cookie` header to `headers` for authenticated requests.
+fn authenticate(state: &mut State, request: &RecordedRequest, headers: &mut Vec<String>) -> bool {
+    let name = match state.session_cookie {
+        Some(ref name) => name.clone(),
+        None => return true,
+    };
+
+    if request.header("authorization").is_some() {
+        let value = format!("session{}", state.next_cookie_id);
+        state.next_cookie_id += 1;
+        headers.push(format!("Set-Cookie: {}={}; Path=/; HttpOnly", name, value));
+        state.valid_cookies.push(value);
+        return true;
+    }
+
+    request.header("cookie")
+        .map(|cookie| cookie.split(';').any(|pair| {
+            let mut parts = pair.trim().splitn(2, '=');
+            parts.next() == Some(name.as_str()) && parts.next().is_some_and(|value| state.valid_cookies.iter().any(|valid| valid == value))
+        }))
+        .unwrap_or(false)
+}
+
+fn read_request<R: BufRead>(reader: &mut R, stream: &mut TcpStream) -> Result<Option<RecordedRequest>, String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).map_err(|err| format!("{}", err))? == 0 {
+        return Ok(None);
+    }
+
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let path = parts.next().unwrap_or("").to_string();
+    let mut headers = Vec::new();
+
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).map_err(|err| format!("{}", err))?;
+        let line = line.trim_end();
+
+        if line.is_empty() {
+            break;
+        }
+        if let Some(i) = line.find(':') {
+            headers.push((line[..i].trim().to_lowercase(), line[i + 1..].trim().to_string()));
+        }
+    }
+
+    let mut request = RecordedRequest {
+        method,
+        path,
+        headers,
+        body: String::new(),
+    };
+
+    if request.header("expect").is_some_and(|value| value.eq_ignore_ascii_case("100-continue")) {
+        stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n").map_err(|err| format!("{}", err))?;
+    }
+
+    let length = request.header("content-length").and_then(|value| value.parse().ok()).unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|err| format!("{}", err))?;
+    request.body = String::from_utf8_lossy(&body).into_owned();
+
+    Ok(Some(request))
+}
+
+fn reason(status: u32) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+fn not_found(what: &str) -> (u32, Value) {
+    (404, json!({"msg": format!("{} not found.", what)}))
+}
+
+fn route(state: &mut State, request: &RecordedRequest) -> (u32, Value) {
+    let (path, query) = match request.path.find('?') {
+        Some(i) => (&request.path[..i], parse_query(&request.path[i + 1..])),
+        None => (request.path.as_str(), HashMap::new()),
+    };
+    let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+    let id = |i: usize| segments.get(i).and_then(|segment| segment.parse::<i64>().ok());
+    let body: Value = serde_json::from_str(&request.body).unwrap_or(Value::Null);
+
+    match (request.method.as_str(), segments.len(), segments.first().cloned()) {
+        ("GET", 1, Some("sessions")) => list_sessions(state, &query),
+        ("POST", 1, Some("sessions")) => create_session(state, &body),
+        ("GET", 2, Some("sessions")) => with_session(state, id(1), |session, id| {
+            poll_session(session);
+            (200, session_json(id, session))
+        }),
+        ("DELETE", 2, Some("sessions")) => match id(1).and_then(|id| state.sessions.remove(&id)) {
+            Some(_) => (200, json!({"msg": "deleted"})),
+            None => not_found("Session"),
+        },
+        ("GET", 3, Some("sessions")) if segments[2] == "state" => with_session(state, id(1), |session, id| {
+            poll_session(session);
+            (200, json!({"id": id, "state": session.state}))
+        }),
+        ("GET", 3, Some("sessions")) if segments[2] == "log" => with_session(state, id(1), |session, id| {
+            (200, log_json(id, &session.log, &query))
+        }),
+        ("GET", 3, Some("sessions")) if segments[2] == "statements" => with_session(state, id(1), |session, _| {
+            let statements: Vec<Value> = session.statements.iter().enumerate()
+                .map(|(i, statement)| statement_json(i as i64, statement))
+                .collect();
+            (200, json!({"total_statements": statements.len(), "statements": statements}))
+        }),
+        ("POST", 3, Some("sessions")) if segments[2] == "statements" => {
+            let polls = state.statement_polls;
+            let output = body.get("code").and_then(Value::as_str).and_then(|code| state.outputs.get(code)).cloned();
+
+            with_session(state, id(1), |session, id| run_statement(session, id, &body, polls, output))
+        },
+        ("GET", 4, Some("sessions")) if segments[2] == "statements" => with_session(state, id(1), |session, _| {
+            match id(3).and_then(|statement_id| session.statements.get_mut(statement_id as usize).map(|s| (statement_id, s))) {
+                Some((statement_id, statement)) => {
+                    poll_statement(statement, statement_id);
+                    (200, statement_json(statement_id, statement))
+                },
+                None => not_found("Statement"),
+            }
+        }),
+        ("POST", 5, Some("sessions")) if segments[2] == "statements" && segments[4] == "cancel" => with_session(state, id(1), |session, _| {
+            match id(3).and_then(|statement_id| session.statements.get_mut(statement_id as usize)) {
+                Some(statement) => {
+                    if statement.state != "available" {
+                        statement.state = "cancelled";
+                    }
+                    (200, json!({"msg": "canceled"}))
+                },
+                None => not_found("Statement"),
+            }
+        }),
+        ("GET", 1, Some("batches")) => list_batches(state, &query),
+        ("POST", 1, Some("batches")) => create_batch(state, &body),
+        ("GET", 2, Some("batches")) => with_batch(state, id(1), |batch, id, polls| {
+            poll_batch(batch, polls);
+            (200, batch_json(id, batch))
+        }),
+        ("DELETE", 2, Some("batches")) => match id(1).and_then(|id| state.batches.remove(&id)) {
+            Some(_) => (200, json!({"msg": "deleted"})),
+            None => not_found("Batch"),
+        },
+        ("GET", 3, Some("batches")) if segments[2] == "state" => with_batch(state, id(1), |batch, id, polls| {
+            poll_batch(batch, polls);
+            (200, json!({"id": id, "state": batch.state}))
+        }),
+        ("GET", 3, Some("batches")) if segments[2] == "log" => with_batch(state, id(1), |batch, id, _| {
+            (200, log_json(id, &batch.log, &query))
+        }),
+        _ => (404, json!({"msg": format!("{} {} is not supported", request.method, path)})),
+    }
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query.split('&')
+        .filter_map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            Some((http::decode(parts.next()?)?, http::decode(parts.next().unwrap_or(""))?))
+        })
+        .collect()
+}
+
+fn page(query: &HashMap<String, String>, total: usize) -> (usize, usize) {
+    let from = query.get("from").and_then(|from| from.parse().ok()).unwrap_or(0).min(total);
+    let size = query.get("size").and_then(|size| size.parse().ok()).unwrap_or(100);
+
+    (from, size)
+}
+
+fn with_session<F>(state: &mut State, id: Option<i64>, f: F) -> (u32, Value)
+    where F: FnOnce(&mut FakeSession, i64) -> (u32, Value) {
+    match id.and_then(|id| state.sessions.get_mut(&id).map(|session| (id, session))) {
+        Some((id, session)) => f(session, id),
+        None => not_found("Session"),
+    }
+}
+
+fn with_batch<F>(state: &mut State, id: Option<i64>, f: F) -> (u32, Value)
+    where F: FnOnce(&mut FakeBatch, i64, u32) -> (u32, Value) {
+    let polls = state.batch_polls;
+
+    match id.and_then(|id| state.batches.get_mut(&id).map(|batch| (id, batch))) {
+        Some((id, batch)) => f(batch, id, polls),
+        None => not_found("Batch"),
+    }
+}
+
+fn list_sessions(state: &mut State, query: &HashMap<String, String>) -> (u32, Value) {
+    let (from, size) = page(query, state.sessions.len());
+    let sessions: Vec<Value> = state.sessions.iter()
+        .skip(from)
+        .take(size)
+        .map(|(&id, session)| session_json(id, session))
+        .collect();
+
+    (200, json!({"from": from, "total": state.sessions.len(), "sessions": sessions}))
+}
+
+fn create_session(state: &mut State, body: &Value) -> (u32, Value) {
+    let kind = match body.get("kind") {
+        Some(kind) if serde_json::from_value::<SessionKind>(kind.clone()).is_ok() => kind.clone(),
+        _ => return (400, json!({"msg": "invalid kind"})),
+    };
+    let id = state.next_session_id;
+    state.next_session_id += 1;
+
+    let session = FakeSession {
+        kind,
+        name: body.get("name").and_then(Value::as_str).map(String::from),
+        state: SessionState::Starting,
+        proxy_user: body.get("proxyUser").and_then(Value::as_str).map(String::from),
+        polls_until_idle: state.startup_polls,
+        log: vec![format!("session {} created", id)],
+        statements: Vec::new(),
+    };
+    let json = session_json(id, &session);
+    state.sessions.insert(id, session);
+
+    (201, json)
+}
+
+fn poll_session(session: &mut FakeSession) {
+    if session.state == SessionState::Starting {
+        if session.polls_until_idle <= 1 {
+            session.state = SessionState::Idle;
+        }
+        session.polls_until_idle = session.polls_until_idle.saturating_sub(1);
+    }
+}
+
+fn session_json(id: i64, session: &FakeSession) -> Value {
+    json!({
+        "id": id,
+        "name": session.name,
+        "appId": format!("application_0000000000000_{:04}", id),
+        "owner": null,
+        "proxyUser": session.proxy_user,
+        "kind": session.kind,
+        "log": session.log,
+        "state": session.state,
+        "appInfo": {"driverLogUrl": null, "sparkUiUrl": null},
+    })
+}
+
+fn run_statement(session: &mut FakeSession, session_id: i64, body: &Value, polls: u32, output: Option<Value>) -> (u32, Value) {
+    match session.state {
+        SessionState::Idle | SessionState::Busy => (),
+        state => return (400, json!({"msg": format!("Session {} is in state {:?}", session_id, state)})),
+    }
+
+    let code = match body.get("code").and_then(Value::as_str) {
+        Some(code) => code.to_string(),
+        None => return (400, json!({"msg": "code is missing"})),
+    };
+    let statement = FakeStatement {
+        code,
+        state: "waiting",
+        polls_until_available: polls,
+        output,
+        started: None,
+        completed: None,
+    };
+    let id = session.statements.len() as i64;
+    let json = statement_json(id, &statement);
+    session.statements.push(statement);
+
+    (201, json)
+}
+
+fn poll_statement(statement: &mut FakeStatement, id: i64) {
+    if statement.state != "waiting" && statement.state != "running" {
+        return;
+    }
+
+    if statement.polls_until_available <= 1 {
+        let output = statement.output.take().unwrap_or_else(|| json!({
+            "status": "ok",
+            "data": {"text/plain": ""},
+        }));
+        let mut output = output;
+        output["execution_count"] = Value::from(id);
+        statement.state = "available";
+        statement.output = Some(output);
+        statement.started = statement.started.or(Some(now()));
+        statement.completed = Some(now());
+    } else {
+        statement.state = "running";
+        statement.started = statement.started.or(Some(now()));
+    }
+    statement.polls_until_available = statement.polls_until_available.saturating_sub(1);
+}
+
+fn statement_json(id: i64, statement: &FakeStatement) -> Value {
+    let output = match statement.state {
+        "available" => statement.output.clone().unwrap_or(Value::Null),
+        _ => Value::Null,
+    };
+
+    json!({
+        "id": id,
+        "code": statement.code,
+        "state": statement.state,
+        "output": output,
+        "started": statement.started,
+        "completed": statement.completed,
+    })
+}
+
+/// Returns the current time in milliseconds since the epoch.
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as i64)
+}
+
+fn list_batches(state: &mut State, query: &HashMap<String, String>) -> (u32, Value) {
+    let (from, size) = page(query, state.batches.len());
+    let batches: Vec<Value> = state.batches.iter()
+        .skip(from)
+        .take(size)
+        .map(|(&id, batch)| batch_json(id, batch))
+        .collect();
+
+    (200, json!({"from": from, "total": state.batches.len(), "sessions": batches}))
+}
+
+fn create_batch(state: &mut State, body: &Value) -> (u32, Value) {
+    if !body.get("file").is_some_and(Value::is_string) {
+        return (400, json!({"msg": "file is missing"}));
+    }
+    let id = state.next_batch_id;
+    state.next_batch_id += 1;
+
+    let file = body["file"].as_str().unwrap_or_default();
+    let outcome = state.batch_outcomes.get_mut(file)
+        .and_then(VecDeque::pop_front)
+        .unwrap_or_else(|| state.batch_outcome.clone());
+    let batch = FakeBatch {
+        state: "starting".to_string(),
+        outcome,
+        name: body.get("name").and_then(Value::as_str).map(String::from),
+        proxy_user: body.get("proxyUser").and_then(Value::as_str).map(String::from),
+        polls_until_next_state: state.batch_polls,
+        log: vec![format!("batch {} submitted", id)],
+    };
+    let json = batch_json(id, &batch);
+    state.batches.insert(id, batch);
+
+    (201, json)
+}
+
+fn poll_batch(batch: &mut FakeBatch, polls: u32) {
+    let next = match batch.state.as_str() {
+        "starting" => "running",
+        "running" => batch.outcome.as_str(),
+        _ => return,
+    };
+
+    if batch.polls_until_next_state <= 1 {
+        batch.state = next.to_string();
+        batch.polls_until_next_state = polls;
+    } else {
+        batch.polls_until_next_state -= 1;
+    }
+}
+
+fn batch_json(id: i64, batch: &FakeBatch) -> Value {
+    json!({
+        "id": id,
+        "name": batch.name,
+        "appId": format!("application_0000000000000_{:04}", 1000 + id),
+        "appInfo": {"driverLogUrl": null, "sparkUiUrl": null},
+        "proxyUser": batch.proxy_user,
+        "log": batch.log,
+        "state": batch.state,
+    })
+}
+
+fn log_json(id: i64, log: &[String], query: &HashMap<String, String>) -> Value {
+    let (from, size) = page(query, log.len());
+
+    json!({
+        "id": id,
+        "from": from,
+        "total": log.len(),
+        "log": log[from..].iter().take(size).collect::<Vec<_>>(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::{NewBatchRequestBuilder, NewSessionRequestBuilder};
+    use client::{RunStatementRequest, StatementState};
+    use std::time::Duration;
+    use wait;
+
+    fn run(livy: &FakeLivy, code: &str) -> Result<::client::Statement, String> {
+        let client = livy.client();
+        let session_id = client.create_session(NewSessionRequestBuilder::new(SessionKind::Spark).build().unwrap())?.id.unwrap();
+        wait::wait_for_session(&client, session_id, Duration::from_millis(1))?;
+
+        let statement = client.run_statement(session_id, RunStatementRequest { code: code.to_string() })?;
+
+        wait::wait_for_statement(&client, session_id, statement.id.unwrap(), Duration::from_millis(1), &AtomicBool::new(false))
+    }
+
+    #[test]
+    fn test_statement_outputs() {
+        let livy = FakeLivy::start();
+        livy.on_statement("x", FakeLivy::text_output("42"));
+        livy.on_statement("y", FakeLivy::error_output("NameError", "name 'y' is not defined"));
+
+        let statement = run(&livy, "x").unwrap();
+        assert_eq!(Some(StatementState::Available), statement.state);
+        assert_eq!(Some(&Value::from("42")), statement.output.unwrap().data.unwrap().get("text/plain"));
+
+        let statement = run(&livy, "y").unwrap();
+        assert_eq!(Some(StatementState::Available), statement.state);
+        assert!(!statement.is_success());
+        assert_eq!(Some("NameError".to_string()), statement.output.unwrap().ename);
+    }
+
+    #[test]
+    fn test_failure_injection() {
+        let livy = FakeLivy::start();
+        let client = livy.client();
+
+        livy.fail_next(503, 1);
+        let err = client.get_sessions(None, None).unwrap_err();
+        assert!(err.contains("503"), "{}", err);
+        assert_eq!(Some(0), client.get_sessions(None, None).unwrap().total);
+
+        let session_id = client.create_session(NewSessionRequestBuilder::new(SessionKind::Pyspark).build().unwrap()).unwrap().id.unwrap();
+        let interrupted = AtomicBool::new(true);
+        let err = wait::wait_for_session_until(&client, session_id, Duration::from_millis(1), &interrupted).unwrap_err();
+        assert!(err.contains("interrupted"), "{}", err);
+        assert!(!interrupted.load(Ordering::SeqCst));
+        livy.set_session_state(session_id, SessionState::Dead);
+        assert!(wait::wait_for_session(&client, session_id, Duration::from_millis(1)).is_err());
+        assert!(client.run_statement(session_id, RunStatementRequest { code: "1".to_string() }).is_err());
+    }
+
+    #[test]
+    fn test_batches() {
+        let livy = FakeLivy::start();
+        let client = livy.client();
+        livy.set_batch_outcome("dead");
+
+        let batch = client.create_batch(NewBatchRequestBuilder::new("app.jar").proxy_user("alice").build().unwrap()).unwrap();
+        let batch_id = batch.id.unwrap();
+        assert_eq!(Some("starting".to_string()), batch.state);
+        assert_eq!(Some("running".to_string()), client.get_batch_state(batch_id).unwrap().state);
+        assert_eq!(Some("dead".to_string()), client.get_batch_state(batch_id).unwrap().state);
+
+        livy.push_batch_log(batch_id, "line 2");
+        let log = client.get_batch_log(batch_id, Some(1), Some(10)).unwrap();
+        assert_eq!(Some(vec!["line 2".to_string()]), log.log);
+
+        assert_eq!(Some(1), client.get_batches(None, None).unwrap().total);
+        client.kill_batch(batch_id).unwrap();
+        assert!(client.get_batch(batch_id).is_err());
+
+        let request = livy.requests().into_iter().find(|request| request.method == "POST").unwrap();
+        assert_eq!("/batches", request.path);
+        assert_eq!(Some("x"), request.header("X-Requested-By"));
+    }
+}
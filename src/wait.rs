@@ -0,0 +1,59 @@
+use client::{Client, Session, SessionState, Statement};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Polls the session whose id is equal to `session_id` every `interval`
+/// until it becomes idle and returns it.
+///
+/// Returns an error if the session reaches a state in which it can
+/// no longer run statements.
+pub fn wait_for_session(client: &Client, session_id: i64, interval: Duration) -> Result<Session, String> {
+    wait_for_session_until(client, session_id, interval, &AtomicBool::new(false))
+}
+
+/// Same as `wait_for_session`, but gives up with an error as soon as
+/// `interrupted` becomes `true`. `interrupted` is reset to `false` then.
+pub fn wait_for_session_until(client: &Client, session_id: i64, interval: Duration, interrupted: &AtomicBool) -> Result<Session, String> {
+    loop {
+        if interrupted.swap(false, Ordering::SeqCst) {
+            return Err(format!("interrupted while waiting for session {}", session_id));
+        }
+
+        let session = client.get_session(session_id)?;
+
+        match session.state {
+            Some(SessionState::Idle) => return Ok(session),
+            Some(state) if state.is_finished() => {
+                return Err(format!("session {} is not available; state: {:?}", session_id, state));
+            },
+            _ => thread::sleep(interval),
+        }
+    }
+}
+
+/// Polls a statement every `interval` until it finishes and returns it.
+///
+/// If `interrupted` becomes `true` while waiting, the statement is
+/// cancelled once and polling continues until Livy reports the
+/// cancellation. `interrupted` is reset to `false` afterwards.
+pub fn wait_for_statement(client: &Client, session_id: i64, statement_id: i64, interval: Duration, interrupted: &AtomicBool) -> Result<Statement, String> {
+    let mut cancelled = false;
+
+    loop {
+        if !cancelled && interrupted.swap(false, Ordering::SeqCst) {
+            client.cancel_statement(session_id, statement_id)?;
+            cancelled = true;
+        }
+
+        let statement = client.get_statement(session_id, statement_id)?;
+
+        match statement.state {
+            Some(state) if state.is_finished() => {
+                interrupted.store(false, Ordering::SeqCst);
+                return Ok(statement);
+            },
+            _ => thread::sleep(interval),
+        }
+    }
+}
@@ -0,0 +1,737 @@
+//! Running batches which depend on each other
+//!
+//! A workflow is a set of named steps, each of which submits a batch after
+//! the steps it depends on succeed. Steps whose dependencies fail are
+//! skipped.
+//!
+//! Workflows can be loaded from JSON, TOML or YAML files such as:
+//!
+//! ```toml
+//! maxParallel = 2
+//!
+//! [retry]
+//! retries = 1
+//! delaySeconds = 60
+//!
+//! [steps.extract]
+//! file = "hdfs:///jobs/etl.jar"
+//! className = "com.example.Extract"
+//!
+//! [steps.load]
+//! dependsOn = ["extract"]
+//! file = "hdfs:///jobs/etl.jar"
+//! className = "com.example.Load"
+//! timeoutSeconds = 3600
+//! ```
+//!
+//! # Examples
+//! ```no_run
+//! use livy::client::Client;
+//! use livy::workflow::{Workflow, WorkflowRunner};
+//!
+//! let client = Client::new("http://example.com:8998", None, None);
+//! let workflow = Workflow::load("etl.toml").unwrap();
+//!
+//! let report = WorkflowRunner::new(&client).run(&workflow).unwrap();
+//! print!("{}", report);
+//! ```
+
+use batch::{self, BatchResult, BatchRunner};
+use client::{Client, NewBatchRequest};
+use format;
+use serde_json::{self, Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Retry policy of failed steps
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Number of times a failed step is submitted again
+    ///
+    /// A step is not submitted again while its batch may still be running,
+    /// or when submitting it failed in a way which may have created it.
+    #[serde(default)]
+    pub retries: u32,
+    /// Seconds to wait before a retry
+    #[serde(default)]
+    pub delay_seconds: u64,
+}
+
+impl RetryPolicy {
+    /// Constructs a new `RetryPolicy` which retries `retries` times after
+    /// waiting for `delay`, rounded up to whole seconds.
+    pub fn new(retries: u32, delay: Duration) -> RetryPolicy {
+        RetryPolicy {
+            retries,
+            delay_seconds: ceil_secs(delay),
+        }
+    }
+}
+
+/// Step of a workflow, which submits a batch
+///
+/// Unknown fields are rejected, so that e.g. a misspelt `dependsOn` does
+/// not silently drop a dependency.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", try_from = "Map<String, Value>")]
+pub struct Step {
+    /// Names of the steps which must succeed before this step runs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    /// Retry policy overriding the one of the workflow
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryPolicy>,
+    /// Seconds after which the batch is killed and the attempt fails
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u64>,
+    #[serde(flatten)]
+    pub request: NewBatchRequest,
+}
+
+impl Step {
+    /// Constructs a new `Step` which submits `request`.
+    pub fn new(request: NewBatchRequest) -> Step {
+        Step {
+            depends_on: Vec::new(),
+            retry: None,
+            timeout_seconds: None,
+            request,
+        }
+    }
+
+    /// Runs the step after the step named `name` succeeds.
+    pub fn after(mut self, name: &str) -> Step {
+        self.depends_on.push(name.to_string());
+        self
+    }
+
+    /// Sets the retry policy of the step.
+    pub fn retry(mut self, retry: RetryPolicy) -> Step {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Kills the batch and fails the attempt after `timeout`, rounded up to
+    /// whole seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Step {
+        self.timeout_seconds = Some(ceil_secs(timeout));
+        self
+    }
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs().saturating_add(if duration.subsec_nanos() > 0 { 1 } else { 0 })
+}
+
+impl TryFrom<Map<String, Value>> for Step {
+    type Error = String;
+
+    /// Splits the fields of the step from those of its batch request,
+    /// which would otherwise ignore unknown fields.
+    fn try_from(mut fields: Map<String, Value>) -> Result<Step, String> {
+        let mut take = |name: &str| fields.remove(name).unwrap_or(Value::Null);
+        let depends_on: Option<Vec<String>> = serde_json::from_value(take("dependsOn")).map_err(|err| format!("dependsOn: {}", err))?;
+        let retry = serde_json::from_value(take("retry")).map_err(|err| format!("retry: {}", err))?;
+        let timeout_seconds = serde_json::from_value(take("timeoutSeconds")).map_err(|err| format!("timeoutSeconds: {}", err))?;
+
+        let request: NewBatchRequest = serde_json::from_value(Value::Object(fields.clone())).map_err(|err| format!("{}", err))?;
+        let parsed = serde_json::to_value(&request).map_err(|err| format!("{}", err))?;
+        if let Some((key, _)) = fields.iter().find(|&(key, value)| !value.is_null() && parsed.get(key).is_none()) {
+            return Err(format!("unknown field: {}", key));
+        }
+
+        Ok(Step {
+            depends_on: depends_on.unwrap_or_default(),
+            retry,
+            timeout_seconds,
+            request,
+        })
+    }
+}
+
+/// Named steps and how they are run
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Workflow {
+    /// Maximum number of batches running at once; unlimited if absent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_parallel: Option<usize>,
+    /// Retry policy of the steps without their own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryPolicy>,
+    pub steps: BTreeMap<String, Step>,
+}
+
+impl Workflow {
+    /// Constructs a new `Workflow` without steps.
+    pub fn new() -> Workflow {
+        Workflow::default()
+    }
+
+    /// Loads a workflow from a `.json`, `.toml`, `.yaml` or `.yml` file and
+    /// validates it.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Workflow, String> {
+        let path = path.as_ref();
+        let workflow: Workflow = serde_json::from_value(format::load(path)?)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        workflow.validate().map_err(|err| format!("{}: {}", path.display(), err))?;
+
+        Ok(workflow)
+    }
+
+    /// Adds a step named `name`.
+    pub fn step(mut self, name: &str, step: Step) -> Workflow {
+        self.steps.insert(name.to_string(), step);
+        self
+    }
+
+    /// Limits the number of batches running at once.
+    pub fn max_parallel(mut self, max_parallel: usize) -> Workflow {
+        self.max_parallel = Some(max_parallel);
+        self
+    }
+
+    /// Sets the retry policy of the steps without their own.
+    pub fn retry(mut self, retry: RetryPolicy) -> Workflow {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Checks that the workflow has steps, that every dependency names a
+    /// step and that the dependencies have no cycle.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.steps.is_empty() {
+            return Err("the workflow has no steps".to_string());
+        }
+        if self.max_parallel == Some(0) {
+            return Err("invalid max parallel: 0".to_string());
+        }
+        for (name, step) in &self.steps {
+            if step.timeout_seconds == Some(0) {
+                return Err(format!("invalid timeout of step {}: 0", name));
+            }
+            if let Some(dependency) = step.depends_on.iter().find(|dependency| !self.steps.contains_key(*dependency)) {
+                return Err(format!("step {} depends on an unknown step: {}", name, dependency));
+            }
+        }
+
+        // Kahn's algorithm: the steps left over are on or behind a cycle.
+        let mut unresolved: BTreeMap<&str, BTreeSet<&str>> = self.steps
+            .iter()
+            .map(|(name, step)| (name.as_str(), step.depends_on.iter().map(String::as_str).collect()))
+            .collect();
+        loop {
+            let resolved: Vec<&str> = unresolved.iter()
+                .filter(|(_, dependencies)| dependencies.is_empty())
+                .map(|(&name, _)| name)
+                .collect();
+            if resolved.is_empty() {
+                break;
+            }
+            for name in resolved {
+                unresolved.remove(name);
+                for dependencies in unresolved.values_mut() {
+                    dependencies.remove(name);
+                }
+            }
+        }
+
+        if !unresolved.is_empty() {
+            let names: Vec<&str> = unresolved.keys().cloned().collect();
+            return Err(format!("the dependencies of these steps form a cycle: {}", names.join(", ")));
+        }
+
+        Ok(())
+    }
+}
+
+/// Final status of a step
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StepStatus {
+    Succeeded,
+    Failed,
+    /// Not run because a dependency did not succeed
+    Skipped,
+}
+
+impl fmt::Display for StepStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            StepStatus::Succeeded => "succeeded",
+            StepStatus::Failed => "failed",
+            StepStatus::Skipped => "skipped",
+        })
+    }
+}
+
+/// Report of a step
+#[derive(Debug, PartialEq)]
+pub struct StepReport {
+    pub name: String,
+    pub status: StepStatus,
+    /// Number of batches submitted
+    pub attempts: u32,
+    /// Result of the last batch
+    pub result: Option<BatchResult>,
+    /// Error of the last attempt, or why the step was skipped
+    pub error: Option<String>,
+}
+
+impl fmt::Display for StepReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.status)?;
+        if let Some(ref result) = self.result {
+            write!(f, ", batch {} ended in {}", result.id, result.state.as_deref().unwrap_or("an unknown state"))?;
+            if result.timed_out {
+                f.write_str(" at the timeout")?;
+            }
+        }
+        if self.attempts > 1 {
+            write!(f, " after {} attempts", self.attempts)?;
+        }
+        if let Some(ref error) = self.error {
+            write!(f, ": {}", error)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Report of a workflow run
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    /// Reports of the steps in the order they finished or were skipped
+    pub steps: Vec<StepReport>,
+    pub duration: Duration,
+}
+
+impl Report {
+    /// Returns `true` if all the steps succeeded.
+    pub fn is_success(&self) -> bool {
+        self.steps.iter().all(|step| step.status == StepStatus::Succeeded)
+    }
+
+    /// Returns the report of the step named `name`.
+    pub fn step(&self, name: &str) -> Option<&StepReport> {
+        self.steps.iter().find(|step| step.name == name)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for step in &self.steps {
+            writeln!(f, "{}", step)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Executor of workflows
+///
+/// Every running step has a thread which submits its batch through a clone
+/// of the client and waits for it with a `BatchRunner`.
+pub struct WorkflowRunner<'a> {
+    client: &'a Client,
+    interval: Duration,
+}
+
+impl<'a> WorkflowRunner<'a> {
+    /// Constructs a new `WorkflowRunner` which sends requests through `client`.
+    pub fn new(client: &'a Client) -> WorkflowRunner<'a> {
+        WorkflowRunner {
+            client,
+            interval: batch::DEFAULT_INTERVAL,
+        }
+    }
+
+    /// Sets the interval of polling the batches.
+    pub fn interval(mut self, interval: Duration) -> WorkflowRunner<'a> {
+        self.interval = interval;
+        self
+    }
+
+    /// Runs the steps of `workflow` after their dependencies and waits for
+    /// all of them to finish or be skipped.
+    pub fn run(&self, workflow: &Workflow) -> Result<Report, String> {
+        workflow.validate()?;
+
+        let start = Instant::now();
+        let max_parallel = workflow.max_parallel.unwrap_or(usize::MAX);
+        let mut pending: Vec<&str> = workflow.steps.keys().map(String::as_str).collect();
+        let mut statuses: HashMap<String, StepStatus> = HashMap::new();
+        let mut steps = Vec::new();
+        let mut running = 0;
+        let (sender, receiver) = mpsc::channel();
+
+        while !pending.is_empty() || running > 0 {
+            let mut i = 0;
+            while i < pending.len() {
+                let name = pending[i];
+                let step = &workflow.steps[name];
+                let failed = step.depends_on.iter().find(|dependency| {
+                    statuses.get(*dependency).is_some_and(|status| *status != StepStatus::Succeeded)
+                });
+
+                if let Some(dependency) = failed {
+                    statuses.insert(name.to_string(), StepStatus::Skipped);
+                    steps.push(StepReport {
+                        name: name.to_string(),
+                        status: StepStatus::Skipped,
+                        attempts: 0,
+                        result: None,
+                        error: Some(format!("{} did not succeed", dependency)),
+                    });
+                    pending.remove(i);
+                    // An earlier step may depend on the skipped one.
+                    i = 0;
+                } else if running < max_parallel && step.depends_on.iter().all(|dependency| statuses.contains_key(dependency)) {
+                    let retry = step.retry.as_ref().or(workflow.retry.as_ref()).cloned().unwrap_or_default();
+                    let (client, interval, step, sender) = (self.client.clone(), self.interval, step.clone(), sender.clone());
+                    let name = name.to_string();
+
+                    thread::spawn(move || {
+                        let report = run_step(&client, interval, name, &step, &retry);
+                        // The receiver outlives the senders.
+                        let _ = sender.send(report);
+                    });
+                    running += 1;
+                    pending.remove(i);
+                } else {
+                    i += 1;
+                }
+            }
+
+            if running > 0 {
+                let report = receiver.recv().map_err(|err| format!("{}", err))?;
+                running -= 1;
+                statuses.insert(report.name.clone(), report.status);
+                steps.push(report);
+            }
+        }
+
+        Ok(Report {
+            steps,
+            duration: start.elapsed(),
+        })
+    }
+}
+
+/// Runs the batch of `step` until it succeeds or the retries run out.
+fn run_step(client: &Client, interval: Duration, name: String, step: &Step, retry: &RetryPolicy) -> StepReport {
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+
+        let mut runner = BatchRunner::new(client).interval(interval).kill_on_timeout();
+        if let Some(timeout_seconds) = step.timeout_seconds {
+            runner = runner.timeout(Duration::from_secs(timeout_seconds));
+        }
+        let (result, error, retryable) = match submit(client, &mut runner, step) {
+            Ok(result) => (Some(result), None, true),
+            Err((err, retryable)) => (None, Some(err), retryable),
+        };
+        let succeeded = result.as_ref().is_some_and(BatchResult::is_success);
+
+        if succeeded || !retryable || attempts > retry.retries {
+            return StepReport {
+                name,
+                status: if succeeded { StepStatus::Succeeded } else { StepStatus::Failed },
+                attempts,
+                result,
+                error,
+            };
+        }
+
+        thread::sleep(Duration::from_secs(retry.delay_seconds));
+    }
+}
+
+/// Submits a step's batch and waits for it.
+///
+/// An error is not retryable when the batch may still be running, or may
+/// have been created without Livy answering.
+fn submit(client: &Client, runner: &mut BatchRunner, step: &Step) -> Result<BatchResult, (String, bool)> {
+    let batch = client.create_batch(step.request.clone()).map_err(|err| {
+        let retryable = was_not_created(&err);
+        (err, retryable)
+    })?;
+    let id = batch.id.ok_or(("the created batch has no id".to_string(), false))?;
+
+    wait_or_kill(client, runner, id)
+}
+
+/// Returns `true` if the request which failed with `err` cannot have
+/// created anything: the server could not be reached, or it refused the
+/// request with a 4xx status.
+fn was_not_created(err: &str) -> bool {
+    // Failures of curl start with its error code, e.g. `[7] Couldn't connect to server`.
+    const UNREACHABLE: [&str; 3] = ["[5] ", "[6] ", "[7] "];
+
+    UNREACHABLE.iter().any(|code| err.starts_with(code)) || err.starts_with("invalid status code; code: 4")
+}
+
+/// Waits for the batch `id`, killing it if waiting fails.
+fn wait_or_kill(client: &Client, runner: &mut BatchRunner, id: i64) -> Result<BatchResult, (String, bool)> {
+    runner.wait(id).map_err(|err| match client.kill_batch(id) {
+        Err(ref kill_err) if !kill_err.contains("code: 404") => (
+            format!("{}; batch {} may still be running and was not retried: {}", err, id, kill_err),
+            false,
+        ),
+        _ => (err, true),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use builder::NewBatchRequestBuilder;
+    #[cfg(all(feature = "toml", feature = "yaml"))]
+    use format::Format;
+    use testing::FakeLivy;
+
+    fn step(file: &str) -> Step {
+        Step::new(NewBatchRequestBuilder::new(file).build().unwrap())
+    }
+
+    #[test]
+    fn test_validate() {
+        struct TestCase {
+            workflow: Workflow,
+            expected: Result<(), String>,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                workflow: Workflow::new().step("a", step("a.jar")).step("b", step("b.jar").after("a")),
+                expected: Ok(()),
+            },
+            TestCase {
+                workflow: Workflow::new(),
+                expected: Err("the workflow has no steps".to_string()),
+            },
+            TestCase {
+                workflow: Workflow::new().step("a", step("a.jar")).max_parallel(0),
+                expected: Err("invalid max parallel: 0".to_string()),
+            },
+            TestCase {
+                workflow: Workflow::new().step("a", step("a.jar").after("x")),
+                expected: Err("step a depends on an unknown step: x".to_string()),
+            },
+            TestCase {
+                workflow: Workflow::new().step("a", step("a.jar").timeout(Duration::from_secs(0))),
+                expected: Err("invalid timeout of step a: 0".to_string()),
+            },
+            TestCase {
+                workflow: Workflow::new()
+                    .step("a", step("a.jar").after("c"))
+                    .step("b", step("b.jar").after("a"))
+                    .step("c", step("c.jar").after("b"))
+                    .step("d", step("d.jar").after("c"))
+                    .step("e", step("e.jar")),
+                expected: Err("the dependencies of these steps form a cycle: a, b, c, d".to_string()),
+            },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(test_case.expected, test_case.workflow.validate());
+        }
+    }
+
+    #[test]
+    fn test_durations() {
+        struct TestCase {
+            duration: Duration,
+            expected: u64,
+        }
+
+        let test_cases = vec![
+            TestCase { duration: Duration::from_secs(0), expected: 0 },
+            TestCase { duration: Duration::from_millis(500), expected: 1 },
+            TestCase { duration: Duration::from_secs(60), expected: 60 },
+            TestCase { duration: Duration::from_millis(60_001), expected: 61 },
+            TestCase { duration: Duration::new(u64::MAX, 1), expected: u64::MAX },
+        ];
+
+        for test_case in test_cases {
+            assert_eq!(Some(test_case.expected), step("a.jar").timeout(test_case.duration).timeout_seconds, "{:?}", test_case.duration);
+            assert_eq!(test_case.expected, RetryPolicy::new(1, test_case.duration).delay_seconds, "{:?}", test_case.duration);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "toml", feature = "yaml"))]
+    fn test_deserialize() {
+        struct TestCase {
+            s: &'static str,
+            format: Format,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                s: r#"{"maxParallel": 2, "retry": {"retries": 1},
+                       "steps": {"extract": {"file": "etl.jar", "className": "Extract"},
+                                 "load": {"file": "etl.jar", "dependsOn": ["extract"], "timeoutSeconds": 60}}}"#,
+                format: Format::Json,
+            },
+            TestCase {
+                s: "maxParallel = 2\n\
+                    [retry]\nretries = 1\n\
+                    [steps.extract]\nfile = \"etl.jar\"\nclassName = \"Extract\"\n\
+                    [steps.load]\nfile = \"etl.jar\"\ndependsOn = [\"extract\"]\ntimeoutSeconds = 60\n",
+                format: Format::Toml,
+            },
+            TestCase {
+                s: "maxParallel: 2\n\
+                    retry:\n  retries: 1\n\
+                    steps:\n  extract:\n    file: etl.jar\n    className: Extract\n\
+                    \x20 load:\n    file: etl.jar\n    dependsOn: [extract]\n    timeoutSeconds: 60\n",
+                format: Format::Yaml,
+            },
+        ];
+
+        let expected = Workflow::new()
+            .max_parallel(2)
+            .retry(RetryPolicy::new(1, Duration::from_secs(0)))
+            .step("extract", Step::new(NewBatchRequestBuilder::new("etl.jar").class_name("Extract").build().unwrap()))
+            .step("load", step("etl.jar").after("extract").timeout(Duration::from_secs(60)));
+
+        for test_case in test_cases {
+            let value = format::parse(test_case.s, test_case.format).unwrap();
+            assert_eq!(expected, serde_json::from_value::<Workflow>(value).unwrap(), "{:?}", test_case.format);
+        }
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        struct TestCase {
+            s: &'static str,
+            expected: &'static str,
+        }
+
+        let test_cases = vec![
+            TestCase {
+                s: r#"{"steps": {"a": {"file": "a.jar"}, "b": {"file": "b.jar", "dependOn": ["a"]}}}"#,
+                expected: "unknown field: dependOn",
+            },
+            TestCase {
+                s: r#"{"steps": {"a": {"file": "a.jar", "dependsOn": "b"}}}"#,
+                expected: "dependsOn: invalid type",
+            },
+            TestCase {
+                s: r#"{"maxparallel": 2, "steps": {"a": {"file": "a.jar"}}}"#,
+                expected: "unknown field `maxparallel`",
+            },
+            TestCase {
+                s: r#"{"retry": {"retry": 1}, "steps": {"a": {"file": "a.jar"}}}"#,
+                expected: "unknown field `retry`",
+            },
+        ];
+
+        for test_case in test_cases {
+            let err = serde_json::from_str::<Workflow>(test_case.s).unwrap_err().to_string();
+            assert!(err.starts_with(test_case.expected), "{}: {}", test_case.s, err);
+        }
+
+        let workflow: Workflow = serde_json::from_str(r#"{"steps": {"a": {"file": "a.jar", "queue": null, "dependsOn": []}}}"#).unwrap();
+        assert_eq!(Workflow::new().step("a", step("a.jar")), workflow);
+    }
+
+    #[test]
+    fn test_run() {
+        let livy = FakeLivy::start();
+        livy.set_batch_polls(2);
+        livy.on_batch("flaky.jar", "dead");
+        livy.on_batch("broken.jar", "dead");
+        livy.on_batch("broken.jar", "dead");
+        let client = livy.client();
+
+        //   extract -> flaky -> load
+        //   extract -> broken -> report -> publish
+        //   cleanup
+        let workflow = Workflow::new()
+            .max_parallel(2)
+            .step("extract", step("extract.jar"))
+            .step("flaky", step("flaky.jar").after("extract").retry(RetryPolicy::new(1, Duration::from_secs(0))))
+            .step("load", step("load.jar").after("flaky"))
+            .step("broken", step("broken.jar").after("extract"))
+            .step("report", step("report.jar").after("broken").after("load"))
+            .step("publish", step("publish.jar").after("report"))
+            .step("cleanup", step("cleanup.jar"));
+
+        let report = WorkflowRunner::new(&client).interval(Duration::from_millis(1)).run(&workflow).unwrap();
+
+        struct Expected {
+            name: &'static str,
+            status: StepStatus,
+            attempts: u32,
+            state: Option<&'static str>,
+        }
+
+        let expected = vec![
+            Expected { name: "extract", status: StepStatus::Succeeded, attempts: 1, state: Some("success") },
+            Expected { name: "cleanup", status: StepStatus::Succeeded, attempts: 1, state: Some("success") },
+            Expected { name: "flaky", status: StepStatus::Succeeded, attempts: 2, state: Some("success") },
+            Expected { name: "broken", status: StepStatus::Failed, attempts: 1, state: Some("dead") },
+            Expected { name: "load", status: StepStatus::Succeeded, attempts: 1, state: Some("success") },
+            Expected { name: "report", status: StepStatus::Skipped, attempts: 0, state: None },
+            Expected { name: "publish", status: StepStatus::Skipped, attempts: 0, state: None },
+        ];
+
+        assert!(!report.is_success());
+        assert_eq!(expected.len(), report.steps.len());
+        for expected in expected {
+            let step = report.step(expected.name).unwrap();
+            assert_eq!((expected.status, expected.attempts), (step.status, step.attempts), "{}", expected.name);
+            assert_eq!(expected.state, step.result.as_ref().and_then(|result| result.state.as_deref()), "{}", expected.name);
+        }
+        assert_eq!(Some("broken did not succeed"), report.step("report").unwrap().error.as_deref());
+        assert_eq!(Some("report did not succeed"), report.step("publish").unwrap().error.as_deref());
+        // Skipped steps are reported after the steps they depend on.
+        let position = |name| report.steps.iter().position(|step| step.name == name).unwrap();
+        assert!(position("broken") < position("report") && position("report") < position("publish"));
+        assert!(report.to_string().contains("\npublish: skipped: report did not succeed\n"), "{}", report);
+    }
+
+    #[test]
+    fn test_wait_or_kill() {
+        let livy = FakeLivy::start();
+        let client = livy.client();
+
+        for &(failures, retryable) in &[(2, true), (3, false)] {
+            let id = client.create_batch(NewBatchRequestBuilder::new("app.jar").build().unwrap()).unwrap().id.unwrap();
+            livy.fail_next(503, failures);
+
+            let mut runner = BatchRunner::new(&client).interval(Duration::from_millis(1)).poll_retries(1);
+            let (err, actual) = wait_or_kill(&client, &mut runner, id).unwrap_err();
+
+            assert_eq!(retryable, actual, "{}", err);
+            assert_eq!(retryable, !err.contains("may still be running and was not retried"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_submit() {
+        let livy = FakeLivy::start();
+        let client = livy.client();
+        let unreachable = Client::new("http://127.0.0.1:1", None, None);
+
+        for &(client, status, retryable) in &[(&client, 400, true), (&client, 503, false), (&unreachable, 0, true)] {
+            if status != 0 {
+                livy.fail_next(status, 1);
+            }
+
+            let mut runner = BatchRunner::new(client).interval(Duration::from_millis(1));
+            let (err, actual) = submit(client, &mut runner, &step("app.jar")).unwrap_err();
+
+            assert_eq!(retryable, actual, "{}", err);
+        }
+    }
+}